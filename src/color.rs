@@ -1,6 +1,7 @@
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
 use anyhow::Context;
@@ -33,6 +34,15 @@ pub fn native_color(c: u32, format: &TextureFormat) -> [f32; 4] {
     }
 }
 
+/// Blends `amount` of `other` into `color`
+pub fn mix_colors(color: [f32; 4], other: [f32; 4], amount: f32) -> [f32; 4] {
+    let mut mixed = color;
+    for (channel, other) in mixed.iter_mut().zip(other) {
+        *channel += (other - *channel) * amount;
+    }
+    mixed
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Theme {
     pub text_color: u32,
@@ -42,6 +52,24 @@ pub struct Theme {
     pub link_color: u32,
     pub select_color: u32,
     pub checkbox_color: u32,
+    pub mark_color: u32,
+    pub diff_added_color: u32,
+    pub diff_removed_color: u32,
+    pub diff_hunk_color: u32,
+    /// Text colors for `h1` through `h6`. Levels without one use the text color
+    pub heading_colors: [Option<u32>; 6],
+    /// Falls back to the text color
+    pub table_border_color: Option<u32>,
+    pub table_header_color: Option<u32>,
+    /// Background for every other body row of a table
+    pub table_stripe_color: Option<u32>,
+    /// Falls back to the text color
+    pub hr_color: Option<u32>,
+    pub scrollbar_track_color: Option<u32>,
+    pub scrollbar_thumb_color: u32,
+    pub code_background_color: Option<u32>,
+    /// Selected text keeps its own colors when this isn't set
+    pub select_text_color: Option<u32>,
     pub code_highlighter: SyntectTheme,
 }
 
@@ -60,6 +88,19 @@ impl Theme {
             link_color: 0x4182EB,
             select_color: 0x3675CB,
             checkbox_color: 0x0A5301,
+            mark_color: 0x5C4B0E,
+            diff_added_color: 0x1C3324,
+            diff_removed_color: 0x3F2024,
+            diff_hunk_color: 0x1F2A3C,
+            heading_colors: [None; 6],
+            table_border_color: None,
+            table_header_color: None,
+            table_stripe_color: None,
+            hr_color: None,
+            scrollbar_track_color: None,
+            scrollbar_thumb_color: 0x959595,
+            code_background_color: None,
+            select_text_color: None,
             code_highlighter,
         }
     }
@@ -78,6 +119,19 @@ impl Theme {
             link_color: 0x5466FF,
             select_color: 0xCDE8F0,
             checkbox_color: 0x96ECAE,
+            mark_color: 0xFFF3A3,
+            diff_added_color: 0xE6FFEC,
+            diff_removed_color: 0xFFEBE9,
+            diff_hunk_color: 0xDDF4FF,
+            heading_colors: [None; 6],
+            table_border_color: None,
+            table_header_color: None,
+            table_stripe_color: None,
+            hr_color: None,
+            scrollbar_track_color: None,
+            scrollbar_thumb_color: 0x959595,
+            code_background_color: None,
+            select_text_color: None,
             code_highlighter,
         }
     }
@@ -91,6 +145,9 @@ impl Theme {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SyntaxTheme {
     Defaults(ThemeDefaults),
+    /// A theme from the user's `syntax-themes` directory selected by its file name. It's only
+    /// looked up once the options get loaded
+    User(String),
     Custom(ThemeCustom),
 }
 
@@ -103,15 +160,25 @@ impl SyntaxTheme {
     pub fn custom(path: PathBuf) -> Self {
         Self::Custom(ThemeCustom { path })
     }
-}
 
-impl TryFrom<SyntaxTheme> for SyntectTheme {
-    type Error = anyhow::Error;
-
-    fn try_from(syntax_theme: SyntaxTheme) -> Result<Self, Self::Error> {
-        match syntax_theme {
-            SyntaxTheme::Defaults(default) => Ok(SyntectTheme::from(default)),
-            SyntaxTheme::Custom(ThemeCustom { path }) => {
+    /// Loads the theme where user themes are selected from `user_themes`
+    pub fn load(
+        self,
+        user_themes: &BTreeMap<String, SyntectTheme>,
+    ) -> anyhow::Result<SyntectTheme> {
+        match self {
+            Self::Defaults(default) => Ok(SyntectTheme::from(default)),
+            Self::User(name) => user_themes.get(&name).cloned().with_context(|| {
+                let variants = ThemeDefaults::kebab_pairs()
+                    .iter()
+                    .map(|&(kebab, _)| kebab)
+                    .chain(user_themes.keys().map(String::as_str))
+                    .map(|name| format!("\"{name}\""))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("\"{name}\" didn't match any of the expected variants: [{variants}]")
+            }),
+            Self::Custom(ThemeCustom { path }) => {
                 let mut reader = BufReader::new(File::open(&path).with_context(|| {
                     format!("Failed opening theme from path {}", path.display())
                 })?);
@@ -147,25 +214,41 @@ impl<'de> Deserialize<'de> for SyntaxTheme {
             // Unfortunately #[serde(untagged)] uses private internals to reuse a deserializer
             // multiple times. We can't so now we have to fall back to other means to give a good
             // error message ;-;
+            // Anything else may be a user theme which gets checked when loading the options
             Untagged::Defaults(theme_name) => match ThemeDefaults::from_kebab(&theme_name) {
                 Some(theme) => Ok(Self::Defaults(theme)),
-                None => {
-                    let variants = ThemeDefaults::kebab_pairs()
-                        .iter()
-                        .map(|(kebab, _)| format!("\"{kebab}\""))
-                        .collect::<Vec<_>>()
-                        .join(", ");
-                    let msg = format!(
-                        "\"{theme_name}\" didn't match any of the expected variants: [{variants}]"
-                    );
-                    Err(serde::de::Error::custom(msg))
-                }
+                None => Ok(Self::User(theme_name)),
             },
             Untagged::Custom(custom) => Ok(Self::Custom(custom)),
         }
     }
 }
 
+/// Themes from the `.tmTheme` files in `dir` keyed by their file names
+pub fn load_user_themes(dir: &Path) -> BTreeMap<String, SyntectTheme> {
+    let paths = match SyntectThemeSet::discover_theme_paths(dir) {
+        Ok(paths) => paths,
+        Err(err) => {
+            tracing::warn!("Failed reading themes from {}: {err}", dir.display());
+            return BTreeMap::new();
+        }
+    };
+
+    let mut themes = BTreeMap::new();
+    for path in paths {
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        match SyntectThemeSet::get_theme(&path) {
+            Ok(theme) => {
+                themes.insert(name.to_owned(), theme);
+            }
+            Err(err) => tracing::warn!("Failed loading theme from {}: {err}", path.display()),
+        }
+    }
+    themes
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ThemeDefaults {
     Base16EightiesDark,
@@ -79,6 +79,7 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         is_italic: bool,
         is_underlined: bool,
         is_striked: bool,
+        is_key: bool,
     }
 
     impl StyleWrapper {
@@ -88,9 +89,10 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 is_italic,
                 is_underlined,
                 is_striked,
+                is_key,
             } = self;
 
-            ![is_bold, is_italic, is_underlined, is_striked].contains(&true)
+            ![is_bold, is_italic, is_underlined, is_striked, is_key].contains(&true)
         }
     }
 
@@ -101,6 +103,7 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 is_italic,
                 is_underlined,
                 is_striked,
+                is_key,
             } = *self;
 
             if self.is_regular() {
@@ -118,6 +121,9 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 if is_striked {
                     f.write_str("STRIKED ")?;
                 }
+                if is_key {
+                    f.write_str("KEY ")?;
+                }
             }
 
             Ok(())
@@ -132,7 +138,12 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         is_italic,
         is_underlined,
         is_striked,
+        is_key,
+        highlight,
+        title,
         font_family,
+        script,
+        image,
         // Globally consistent so avoid displaying as noise
         hidpi_scale: _,
         default_color,
@@ -144,7 +155,8 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     debug.field("text", text);
 
     // Fields that we only display when set to unique values
-    if font_family != &FamilyOwned::SansSerif {
+    debug_inline_some(&mut debug, "image", image);
+    if font_family != &FamilyOwned::SansSerif && image.is_none() {
         debug.field("font_family", font_family);
     }
     if color.is_none() {
@@ -158,11 +170,15 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         is_italic: *is_italic,
         is_underlined: *is_underlined,
         is_striked: *is_striked,
+        is_key: *is_key,
     };
     if !style.is_regular() {
         debug.field("style", &style);
     }
+    debug_inline_some(&mut debug, "script", script);
+    debug_inline_some(&mut debug, "highlight", &highlight.map(DebugF32Color));
     debug_inline_some(&mut debug, "link", link);
+    debug_inline_some(&mut debug, "title", title);
 
     debug.finish_non_exhaustive()
 }
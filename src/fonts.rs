@@ -1,10 +1,44 @@
-use glyphon::FontSystem;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use glyphon::{fontdb, Family, FamilyOwned, FontSystem, Style, Weight};
 
 use crate::opts::FontOptions;
 
+/// Fonts to fall back to on systems that don't have any, like minimal containers
+#[cfg(feature = "bundled-fonts")]
+pub const BUNDLED: &[&[u8]] = &[
+    include_bytes!("../assets/fonts/DejaVuSans.ttf"),
+    include_bytes!("../assets/fonts/DejaVuSansMono.ttf"),
+];
+#[cfg(feature = "bundled-fonts")]
+pub const BUNDLED_SANS_SERIF: &str = "DejaVu Sans";
+#[cfg(feature = "bundled-fonts")]
+pub const BUNDLED_MONOSPACE: &str = "DejaVu Sans Mono";
+
+/// A font without any outlines where each of the placeholder characters is just an advance of its
+/// width. Text can't set the width of anything on its own, so inline images get a run of these to
+/// take up their space while the text wraps around them. Document text that uses these characters
+/// itself keeps its font's glyphs when the font has them, and otherwise falls back to this font and
+/// shows up as blank space. `cargo xtask placeholder-font` builds it
+const PLACEHOLDER: &[u8] = include_bytes!("../assets/fonts/placeholder.ttf");
+/// Family of the font that holds the room for inline images within text
+pub const PLACEHOLDER_FAMILY: &str = "Inlyne Placeholder";
+// The placeholder glyphs sit in the private use area. The first one is an em wide and each one
+// after it is half as wide as the one before
+const PLACEHOLDER_START: u32 = 0xE000;
+const PLACEHOLDER_GLYPHS: u16 = 8;
+
 pub fn get_fonts(font_opts: &FontOptions) -> FontSystem {
     let mut font_system = FontSystem::new();
 
+    #[cfg(feature = "bundled-fonts")]
+    if font_system.db().is_empty() {
+        tracing::info!("Didn't find any system fonts. Falling back to the bundled fonts");
+        load_bundled(font_system.db_mut());
+    }
+    load_placeholder(font_system.db_mut());
+
     if let Some(regular_name) = &font_opts.regular_font {
         font_system.db_mut().set_sans_serif_family(regular_name)
     }
@@ -15,3 +49,381 @@ pub fn get_fonts(font_opts: &FontOptions) -> FontSystem {
 
     font_system
 }
+
+/// The parts of a font database that the bundled fonts get loaded into. `glyphon` and `usvg` each
+/// depend on their own version of `fontdb`
+#[cfg(feature = "bundled-fonts")]
+pub trait FontDatabase {
+    fn load_font_data(&mut self, data: Vec<u8>);
+    fn set_generic_families(&mut self, sans_serif: &str, monospace: &str);
+}
+
+#[cfg(feature = "bundled-fonts")]
+impl FontDatabase for fontdb::Database {
+    fn load_font_data(&mut self, data: Vec<u8>) {
+        self.load_font_data(data);
+    }
+
+    fn set_generic_families(&mut self, sans_serif: &str, monospace: &str) {
+        self.set_sans_serif_family(sans_serif);
+        self.set_serif_family(sans_serif);
+        self.set_monospace_family(monospace);
+    }
+}
+
+#[cfg(feature = "bundled-fonts")]
+impl FontDatabase for resvg::usvg::fontdb::Database {
+    fn load_font_data(&mut self, data: Vec<u8>) {
+        self.load_font_data(data);
+    }
+
+    fn set_generic_families(&mut self, sans_serif: &str, monospace: &str) {
+        self.set_sans_serif_family(sans_serif);
+        self.set_serif_family(sans_serif);
+        self.set_monospace_family(monospace);
+    }
+}
+
+/// Loads the bundled fonts and makes them the generic families
+#[cfg(feature = "bundled-fonts")]
+pub fn load_bundled(db: &mut impl FontDatabase) {
+    for font in BUNDLED {
+        db.load_font_data(font.to_vec());
+    }
+    db.set_generic_families(BUNDLED_SANS_SERIF, BUNDLED_MONOSPACE);
+}
+
+/// Loads the font that holds the room for inline images and raised or lowered text
+pub fn load_placeholder(db: &mut fontdb::Database) {
+    db.load_font_data(PLACEHOLDER.to_vec());
+}
+
+/// The families picked for text based on what kind of text it is
+#[derive(Clone, Debug, Default)]
+pub struct FontFamilies {
+    pub body: Option<FamilyOwned>,
+    pub heading: Option<FamilyOwned>,
+    /// Tried in order for any characters that a text's own font is missing
+    pub fallbacks: Vec<FamilyOwned>,
+}
+
+impl FontFamilies {
+    pub fn new(font_opts: &FontOptions) -> Self {
+        Self {
+            body: font_opts.body_font.as_deref().map(parse_family),
+            heading: font_opts.heading_font.as_deref().map(parse_family),
+            fallbacks: font_opts
+                .fallback_fonts
+                .iter()
+                .map(|name| parse_family(name))
+                .collect(),
+        }
+    }
+
+    /// Swaps regular text over to the body or heading font while leaving text that asked for a
+    /// specific family, like code, alone
+    pub fn family_for<'a>(&'a self, family: Family<'a>, heading: bool) -> Family<'a> {
+        if family != Family::SansSerif {
+            return family;
+        }
+        let role = if heading {
+            self.heading.as_ref().or(self.body.as_ref())
+        } else {
+            self.body.as_ref()
+        };
+        role.map_or(family, FamilyOwned::as_family)
+    }
+
+    /// Each kind of text along with the family that it asks for
+    pub fn roles(&self) -> Vec<(String, Family<'_>)> {
+        let body = self.family_for(Family::SansSerif, false);
+        let heading = self.family_for(Family::SansSerif, true);
+        let mut roles = vec![
+            ("regular".to_owned(), Family::SansSerif),
+            ("monospace".to_owned(), Family::Monospace),
+            ("body".to_owned(), body),
+            ("heading".to_owned(), heading),
+        ];
+        for (i, fallback) in self.fallbacks.iter().enumerate() {
+            roles.push((format!("fallback {}", i + 1), fallback.as_family()));
+        }
+        roles
+    }
+
+    /// Splits `text` into runs where each one uses the first of `family` and then the fallbacks
+    /// that has glyphs for all of it. Anything that none of them cover stays with `family`, so
+    /// that it still goes through the system's fallbacks
+    pub fn fallback_runs<'a>(
+        &'a self,
+        font_system: &mut FontSystem,
+        text: &str,
+        family: Family<'a>,
+        weight: Weight,
+        style: Style,
+    ) -> Vec<(Range<usize>, Family<'a>)> {
+        if self.fallbacks.is_empty() || text.is_ascii() {
+            return vec![(0..text.len(), family)];
+        }
+
+        let families: Vec<_> = std::iter::once(family)
+            .chain(self.fallbacks.iter().map(FamilyOwned::as_family))
+            .collect();
+        let fonts: Vec<_> = families
+            .iter()
+            .map(|&family| {
+                let query = fontdb::Query {
+                    families: &[family],
+                    weight,
+                    style,
+                    ..Default::default()
+                };
+                let id = font_system.db().query(&query)?;
+                font_system.get_font(id)
+            })
+            .collect();
+
+        let mut runs: Vec<(Range<usize>, usize)> = Vec::new();
+        for (i, c) in text.char_indices() {
+            let end = i + c.len_utf8();
+            let prev = runs.last().map(|(_, font_index)| *font_index);
+            let font_index = match prev {
+                // Keep joiners, variation selectors, and the like with whatever came before them
+                Some(prev) if c.is_whitespace() || is_joining(c) => prev,
+                _ => fonts
+                    .iter()
+                    .position(|font| {
+                        font.as_ref()
+                            .is_some_and(|font| font.rustybuzz().glyph_index(c).is_some())
+                    })
+                    .unwrap_or(0),
+            };
+            match runs.last_mut() {
+                Some((range, prev)) if *prev == font_index => range.end = end,
+                _ => runs.push((i..end, font_index)),
+            }
+        }
+
+        runs.into_iter()
+            .map(|(range, font_index)| (range, families[font_index]))
+            .collect()
+    }
+}
+
+/// Text in the placeholder font that's `width` ems wide, down to a 128th of an em
+pub fn placeholder_text(width: f32) -> String {
+    let smallest = 1 << (PLACEHOLDER_GLYPHS - 1);
+    let units = (width.max(0.) * smallest as f32).round() as u32;
+    let placeholder = |i: u16| char::from_u32(PLACEHOLDER_START + u32::from(i)).unwrap();
+
+    let mut text: String = std::iter::repeat(placeholder(0))
+        .take((units / smallest) as usize)
+        .collect();
+    for i in 1..PLACEHOLDER_GLYPHS {
+        if units & (smallest >> i) != 0 {
+            text.push(placeholder(i));
+        }
+    }
+    text
+}
+
+fn is_joining(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200C}'..='\u{200D}'
+            | '\u{0300}'..='\u{036F}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{1F3FB}'..='\u{1F3FF}'
+            | '\u{E0020}'..='\u{E007F}'
+    )
+}
+
+/// Reads a family from the config where the generic CSS family names pick the matching generic
+/// family
+pub fn parse_family(name: &str) -> FamilyOwned {
+    match name {
+        "serif" => FamilyOwned::Serif,
+        "sans-serif" => FamilyOwned::SansSerif,
+        "monospace" => FamilyOwned::Monospace,
+        "cursive" => FamilyOwned::Cursive,
+        "fantasy" => FamilyOwned::Fantasy,
+        name => FamilyOwned::Name(name.to_owned()),
+    }
+}
+
+/// How `family` would be written in the config
+pub fn family_label<'a>(family: &Family<'a>) -> &'a str {
+    match *family {
+        Family::Name(name) => name,
+        Family::Serif => "serif",
+        Family::SansSerif => "sans-serif",
+        Family::Cursive => "cursive",
+        Family::Fantasy => "fantasy",
+        Family::Monospace => "monospace",
+    }
+}
+
+/// The font that text asking for `family` ends up using, if it's installed
+pub fn resolve<'db>(
+    db: &'db fontdb::Database,
+    family: Family<'_>,
+) -> Option<&'db fontdb::FaceInfo> {
+    let query = fontdb::Query {
+        families: &[family],
+        ..Default::default()
+    };
+    db.face(db.query(&query)?)
+}
+
+/// Every family that has a font installed
+pub fn family_names(db: &fontdb::Database) -> BTreeSet<&str> {
+    db.faces()
+        .flat_map(|face| face.families.iter().map(|(name, _)| name.as_str()))
+        .filter(|&name| name != PLACEHOLDER_FAMILY)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn families_for_each_role() {
+        let font_opts = FontOptions {
+            body_font: Some("serif".to_owned()),
+            fallback_fonts: vec!["Noto Sans CJK JP".to_owned()],
+            ..Default::default()
+        };
+        let families = FontFamilies::new(&font_opts);
+        assert_eq!(families.family_for(Family::SansSerif, false), Family::Serif);
+        // Headings fall back to the body font
+        assert_eq!(families.family_for(Family::SansSerif, true), Family::Serif);
+        assert_eq!(
+            families.family_for(Family::Monospace, false),
+            Family::Monospace
+        );
+        assert_eq!(
+            families.fallbacks,
+            [FamilyOwned::Name("Noto Sans CJK JP".to_owned())]
+        );
+
+        let font_opts = FontOptions {
+            heading_font: Some("Fancy Font".to_owned()),
+            ..Default::default()
+        };
+        let families = FontFamilies::new(&font_opts);
+        assert_eq!(
+            families.family_for(Family::SansSerif, true),
+            Family::Name("Fancy Font")
+        );
+        assert_eq!(
+            families.family_for(Family::SansSerif, false),
+            Family::SansSerif
+        );
+    }
+
+    #[test]
+    fn placeholder_widths() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".to_owned(), fontdb::Database::new());
+        load_placeholder(font_system.db_mut());
+        assert!(family_names(font_system.db()).is_empty());
+
+        let font_size = 16.;
+        let metrics = glyphon::Metrics::new(font_size, font_size);
+        let mut buffer = glyphon::Buffer::new(&mut font_system, metrics);
+        buffer.set_size(&mut font_system, f32::INFINITY, f32::INFINITY);
+        for width in [0., 1., 2.5, 0.3, 10.01] {
+            let text = placeholder_text(width);
+            let attrs = glyphon::Attrs::new().family(Family::Name(PLACEHOLDER_FAMILY));
+            buffer.set_text(&mut font_system, &text, attrs, glyphon::Shaping::Advanced);
+            let measured: f32 = buffer
+                .layout_runs()
+                .flat_map(|run| run.glyphs.iter())
+                .map(|glyph| glyph.w)
+                .sum();
+            let expected = width * font_size;
+            assert!(
+                (measured - expected).abs() <= font_size / 256.,
+                "{width} ems measured {measured}px instead of {expected}px",
+            );
+        }
+    }
+
+    #[test]
+    fn private_use_text_uses_placeholders() {
+        let text_system = crate::test_utils::text::text_system();
+        let mut font_system = text_system.font_system.lock().unwrap();
+
+        let metrics = glyphon::Metrics::new(16., 16.);
+        let mut buffer = glyphon::Buffer::new(&mut font_system, metrics);
+        buffer.set_size(&mut font_system, f32::INFINITY, f32::INFINITY);
+        let attrs = glyphon::Attrs::new();
+        buffer.set_text(
+            &mut font_system,
+            "a\u{E000}b",
+            attrs,
+            glyphon::Shaping::Advanced,
+        );
+        let glyphs: Vec<_> = buffer
+            .layout_runs()
+            .flat_map(|run| run.glyphs.iter())
+            .map(|glyph| {
+                let face = font_system.db().face(glyph.font_id).unwrap();
+                (face.families[0].0.clone(), glyph.w)
+            })
+            .collect();
+        // Fonts without the character fall back to a blank placeholder of the same width
+        assert_eq!(glyphs[1], (PLACEHOLDER_FAMILY.to_owned(), 16.));
+    }
+
+    #[cfg(feature = "bundled-fonts")]
+    #[test]
+    fn bundled_fonts() {
+        let mut db = fontdb::Database::new();
+        load_bundled(&mut db);
+        let names = family_names(&db);
+        assert!(names.contains(BUNDLED_SANS_SERIF));
+        assert!(names.contains(BUNDLED_MONOSPACE));
+        let sans_serif = db.family_name(&Family::SansSerif);
+        assert_eq!(sans_serif, BUNDLED_SANS_SERIF);
+        assert_eq!(db.family_name(&Family::Monospace), BUNDLED_MONOSPACE);
+    }
+
+    #[test]
+    fn fallback_runs_for_mixed_scripts() {
+        let text_system = crate::test_utils::text::text_system();
+        let mut font_system = text_system.font_system.lock().unwrap();
+        let families = FontFamilies::new(&FontOptions {
+            fallback_fonts: vec!["DejaVu Sans".to_owned()],
+            ..Default::default()
+        });
+        let mut runs = |text| -> Vec<_> {
+            families
+                .fallback_runs(
+                    &mut font_system,
+                    text,
+                    Family::Monospace,
+                    Weight::NORMAL,
+                    Style::Normal,
+                )
+                .into_iter()
+                .map(|(range, family)| (&text[range], family))
+                .collect()
+        };
+
+        // The monospace font is missing Hebrew and emoji. Nothing covers the CJK, so it stays
+        // with the monospace font, and the variation selector sticks with its emoji
+        let sans = Family::Name("DejaVu Sans");
+        assert_eq!(
+            runs("let x = אב; 中文 ж😀\u{FE0F}!"),
+            [
+                ("let x = ", Family::Monospace),
+                ("אב", sans),
+                ("; 中文 ж", Family::Monospace),
+                ("😀\u{FE0F}", sans),
+                ("!", Family::Monospace),
+            ]
+        );
+        assert_eq!(runs("all ascii"), [("all ascii", Family::Monospace)]);
+    }
+}
@@ -4,6 +4,7 @@ use std::time::Instant;
 use crate::metrics::{histogram, HistTag};
 use crate::utils::usize_in_mib;
 
+use image::imageops::FilterType;
 use image::GenericImageView;
 use lz4_flex::frame::{BlockSize, FrameDecoder, FrameEncoder, FrameInfo};
 
@@ -29,15 +30,33 @@ pub fn lz4_decompress(blob: &[u8], size: usize) -> anyhow::Result<Vec<u8>> {
     Ok(decompressed)
 }
 
-pub type ImageParts = (Vec<u8>, (u32, u32));
-
-pub fn decode_and_compress(contents: &[u8]) -> anyhow::Result<ImageParts> {
-    let image = image::load_from_memory(contents)?;
+/// The compressed image, the dimensions it was decoded at, and the original dimensions when it was
+/// downscaled while decoding
+pub type ImageParts = (Vec<u8>, (u32, u32), Option<(u32, u32)>);
+
+pub fn decode_and_compress(contents: &[u8], max_width: Option<u32>) -> anyhow::Result<ImageParts> {
+    let mut image = image::load_from_memory(contents)?;
+    let full_dimensions = image.dimensions();
+    let downscaled = match max_width {
+        Some(max_width) if full_dimensions.0 > max_width => {
+            let start = Instant::now();
+            image = image.resize(max_width, u32::MAX, FilterType::Triangle);
+            tracing::debug!(
+                "Downscaled image from {:?} to {:?}: Time {:.2?}",
+                full_dimensions,
+                image.dimensions(),
+                start.elapsed(),
+            );
+            true
+        }
+        _ => false,
+    };
     let dimensions = image.dimensions();
     let image_data = image.into_rgba8().into_raw();
     tracing::debug!(
         "Decoded full image in memory {:.3} MiB",
         usize_in_mib(image_data.len()),
     );
-    lz4_compress(&mut io::Cursor::new(image_data)).map(|lz4_blob| (lz4_blob, dimensions))
+    lz4_compress(&mut io::Cursor::new(image_data))
+        .map(|lz4_blob| (lz4_blob, dimensions, downscaled.then_some(full_dimensions)))
 }
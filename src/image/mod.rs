@@ -15,8 +15,7 @@ use std::{
 use crate::debug_impls::{DebugBytesPrefix, DebugInline};
 use crate::interpreter::ImageCallback;
 use crate::metrics::{histogram, HistTag};
-use crate::positioner::DEFAULT_MARGIN;
-use crate::utils::{usize_in_mib, Align, Point, Size};
+use crate::utils::{usize_in_mib, Align, Float, Point, Size};
 
 use anyhow::Context;
 use bytemuck::{Pod, Zeroable};
@@ -61,6 +60,10 @@ impl ImageSize {
     }
 }
 
+/// Extra resolution kept around when downscaling images, so that zooming in a bit doesn't
+/// immediately require decoding the image again
+pub const ZOOM_HEADROOM: f32 = 1.5;
+
 #[derive(SmartDebug, Default, Clone, PartialEq)]
 pub struct ImageData {
     #[debug(wrapper = DebugBytesPrefix)]
@@ -68,18 +71,104 @@ pub struct ImageData {
     scale: bool,
     #[debug(wrapper = DebugInline)]
     dimensions: (u32, u32),
+    // Only set when the image was downscaled while decoding
+    #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
+    full_dimensions: Option<(u32, u32)>,
+    // The encoded image is kept for downscaled images, so that they can be decoded again at a
+    // higher resolution when needed
+    #[debug(skip)]
+    source: Option<Arc<[u8]>>,
+    // Set while the image is being decoded again in the background
+    #[debug(skip)]
+    redecoding: bool,
+}
+
+// Decoding a downscaled image again at a higher resolution, which can happen without holding on to
+// the image data
+struct Redecode {
+    source: Arc<[u8]>,
+    max_width: Option<u32>,
+}
+
+impl Redecode {
+    fn run(self) -> anyhow::Result<(Vec<u8>, (u32, u32))> {
+        let (lz4_blob, dimensions, _) = decode::decode_and_compress(&self.source, self.max_width)?;
+        Ok((lz4_blob, dimensions))
+    }
 }
 
 impl ImageData {
     pub fn load(bytes: &[u8], scale: bool) -> anyhow::Result<Self> {
-        let (lz4_blob, dimensions) = decode::decode_and_compress(bytes)?;
+        Self::load_with_max_width(bytes, scale, None)
+    }
+
+    /// Loads the image, downscaling it to be at most `max_width` pixels wide
+    pub fn load_with_max_width(
+        bytes: &[u8],
+        scale: bool,
+        max_width: Option<u32>,
+    ) -> anyhow::Result<Self> {
+        let (lz4_blob, dimensions, full_dimensions) =
+            decode::decode_and_compress(bytes, max_width)?;
+        let source = full_dimensions.map(|_| Arc::from(bytes));
         Ok(Self {
             lz4_blob,
             scale,
             dimensions,
+            full_dimensions,
+            source,
+            redecoding: false,
         })
     }
 
+    /// The dimensions of the original image regardless of what resolution it was decoded at
+    pub fn natural_dimensions(&self) -> (u32, u32) {
+        self.full_dimensions.unwrap_or(self.dimensions)
+    }
+
+    /// Decodes a downscaled image again so that it's at least `width` pixels wide (or its full
+    /// size). Returns whether the image data changed
+    pub fn redecode_for_width(&mut self, width: u32) -> bool {
+        match self.redecode(width) {
+            Some(redecode) => self.finish_redecode(redecode.run()),
+            None => false,
+        }
+    }
+
+    fn redecode(&self, width: u32) -> Option<Redecode> {
+        let (Some(source), Some(full_dimensions)) = (&self.source, self.full_dimensions) else {
+            return None;
+        };
+        if width <= self.dimensions.0 || self.dimensions == full_dimensions {
+            return None;
+        }
+
+        let max_width = (width < full_dimensions.0).then_some(width);
+        Some(Redecode {
+            source: Arc::clone(source),
+            max_width,
+        })
+    }
+
+    fn finish_redecode(&mut self, decoded: anyhow::Result<(Vec<u8>, (u32, u32))>) -> bool {
+        self.redecoding = false;
+        match decoded {
+            // Zooming can go back and forth while decoding, so never swap in a smaller image
+            Ok((lz4_blob, dimensions)) if dimensions.0 > self.dimensions.0 => {
+                self.lz4_blob = lz4_blob;
+                self.dimensions = dimensions;
+                true
+            }
+            Ok(_) => false,
+            Err(err) => {
+                tracing::warn!("Failed decoding image at a higher resolution: {err}");
+                // Don't keep trying on every frame
+                self.source = None;
+                false
+            }
+        }
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         decode::lz4_decompress(&self.lz4_blob, self.rgba_image_byte_size())
             .expect("Size matches and I/O is in memory")
@@ -102,6 +191,7 @@ impl ImageData {
             dimensions,
             lz4_blob,
             scale,
+            ..Default::default()
         }
     }
 
@@ -111,7 +201,7 @@ impl ImageData {
     }
 }
 
-#[derive(SmartDebug, Default)]
+#[derive(SmartDebug, Default, Clone)]
 pub struct Image {
     // TODO: Instead of sharing a mutex with the image loading thread change this to hold a oneshot
     // channel that stores the image?
@@ -120,6 +210,8 @@ pub struct Image {
     #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
     pub is_aligned: Option<Align>,
     #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
+    pub float: Option<Float>,
+    #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
     pub size: Option<ImageSize>,
     #[debug(skip)]
     pub bind_group: Option<Arc<wgpu::BindGroup>>,
@@ -127,6 +219,13 @@ pub struct Image {
     pub is_link: Option<String>,
     #[debug(skip)]
     pub hidpi_scale: f32,
+    // Where the image was loaded from, which gets reported again after decoding it at a higher
+    // resolution
+    #[debug(skip)]
+    pub src: Option<String>,
+    // The dimensions of the image data that the texture was created from
+    #[debug(skip)]
+    pub texture_dimensions: Option<(u32, u32)>,
 }
 
 // NOTE: Internally performs some expensive operations. Avoid calling often
@@ -136,18 +235,24 @@ impl PartialEq for Image {
         let Self {
             image_data,
             is_aligned,
+            float,
             size,
             bind_group,
             is_link,
             hidpi_scale,
+            src: _,
+            texture_dimensions: _,
         } = self;
         let Self {
             image_data: other_image_data,
             is_aligned: other_is_aligned,
+            float: other_float,
             size: other_size,
             bind_group: other_bind_group,
             is_link: other_is_link,
             hidpi_scale: other_hidpi_scale,
+            src: _,
+            texture_dimensions: _,
         } = other;
 
         let clone_image_data = |shared_image: &Mutex<Option<_>>| {
@@ -165,6 +270,7 @@ impl PartialEq for Image {
 
         image_data == other_image_data
             && is_aligned == other_is_aligned
+            && float == other_float
             && size == other_size
             && is_link == other_is_link
             && hidpi_scale == other_hidpi_scale
@@ -254,17 +360,77 @@ impl Image {
         });
         let bind_group = Arc::new(bind_group);
         self.bind_group = Some(bind_group.clone());
+        self.texture_dimensions = Some(dimensions);
         Some(bind_group)
     }
 
+    /// The number of bytes the uploaded texture takes up on the GPU (`0` when not uploaded)
+    pub fn texture_byte_size(&self) -> usize {
+        self.texture_dimensions
+            .map_or(0, |(width, height)| width as usize * height as usize * 4)
+    }
+
+    /// Drops the GPU texture. It gets recreated from the compressed image data the next time the
+    /// image is drawn
+    pub fn evict_texture(&mut self) {
+        self.bind_group = None;
+        self.texture_dimensions = None;
+    }
+
+    /// Decodes a downscaled image at a higher resolution on the image loading thread when it's
+    /// displayed larger than what it was decoded at (e.g. after zooming in). The current texture
+    /// keeps getting drawn until the new image data is loaded
+    pub fn ensure_resolution(
+        &mut self,
+        displayed_width: f32,
+        image_callback: impl FnOnce() -> Box<dyn ImageCallback + Send>,
+    ) {
+        let mut image_data = self.image_data.lock().unwrap();
+        let Some(data) = image_data.as_mut() else {
+            return;
+        };
+        // The image data was swapped out for the new resolution since the texture was created
+        if self
+            .texture_dimensions
+            .is_some_and(|dimensions| dimensions != data.dimensions)
+        {
+            self.bind_group = None;
+            self.texture_dimensions = None;
+        }
+        if data.redecoding || displayed_width as u32 <= data.dimensions.0 {
+            return;
+        }
+        let Some(redecode) = data.redecode((displayed_width * ZOOM_HEADROOM) as u32) else {
+            return;
+        };
+        data.redecoding = true;
+        drop(image_data);
+
+        let image_data = Arc::clone(&self.image_data);
+        let src = self.src.clone().unwrap_or_default();
+        let image_callback = image_callback();
+        std::thread::spawn(move || {
+            let decoded = redecode.run();
+            let changed = match image_data.lock().unwrap().as_mut() {
+                Some(data) => data.finish_redecode(decoded),
+                None => false,
+            };
+            if changed {
+                image_callback.loaded_image(src, image_data);
+            }
+        });
+    }
+
     pub fn from_src(
         src: String,
         file_path: PathBuf,
         hidpi_scale: f32,
+        max_width: Option<u32>,
         image_callback: Box<dyn ImageCallback + Send>,
     ) -> anyhow::Result<Image> {
         let image_data = Arc::new(Mutex::new(None));
         let image_data_clone = image_data.clone();
+        let src_clone = src.clone();
 
         std::thread::spawn(move || {
             let start = Instant::now();
@@ -285,7 +451,9 @@ impl Image {
                 return;
             };
 
-            let image = if let Ok(image) = ImageData::load(&image_data, true) {
+            let image = if let Ok(image) =
+                ImageData::load_with_max_width(&image_data, true, max_width)
+            {
                 image
             } else {
                 let opt = usvg::Options::default();
@@ -314,6 +482,10 @@ impl Image {
                 let fontdb = FONTDB.get_or_init(|| {
                     let mut db = fontdb::Database::new();
                     db.load_system_fonts();
+                    #[cfg(feature = "bundled-fonts")]
+                    if db.is_empty() {
+                        crate::fonts::load_bundled(&mut db);
+                    }
                     db
                 });
                 tree.postprocess(Default::default(), fontdb);
@@ -338,16 +510,22 @@ impl Image {
         let image = Image {
             image_data,
             hidpi_scale,
+            src: Some(src_clone),
             ..Default::default()
         };
 
         Ok(image)
     }
 
-    pub fn from_image_data(image_data: Arc<Mutex<Option<ImageData>>>, hidpi_scale: f32) -> Image {
+    pub fn from_image_data(
+        image_data: Arc<Mutex<Option<ImageData>>>,
+        src: String,
+        hidpi_scale: f32,
+    ) -> Image {
         Image {
             image_data,
             hidpi_scale,
+            src: Some(src),
             ..Default::default()
         }
     }
@@ -361,13 +539,18 @@ impl Image {
         self
     }
 
+    pub fn with_float(mut self, float: Float) -> Self {
+        self.float = Some(float);
+        self
+    }
+
     pub fn with_size(mut self, size: ImageSize) -> Self {
         self.size = Some(size);
         self
     }
 
     pub fn dimensions_from_image_size(&mut self, size: &ImageSize) -> Option<(u32, u32)> {
-        let image_dimensions = self.buffer_dimensions()?;
+        let image_dimensions = self.natural_dimensions()?;
         match size {
             ImageSize::PxWidth(px_width) => Some((
                 px_width.0,
@@ -386,8 +569,18 @@ impl Image {
         Some(self.image_data.lock().unwrap().as_ref()?.dimensions)
     }
 
-    fn dimensions(&mut self, screen_size: Size, zoom: f32) -> Option<(u32, u32)> {
-        let buffer_size = self.buffer_dimensions()?;
+    fn natural_dimensions(&self) -> Option<(u32, u32)> {
+        Some(
+            self.image_data
+                .lock()
+                .unwrap()
+                .as_ref()?
+                .natural_dimensions(),
+        )
+    }
+
+    fn dimensions(&mut self, max_size: Size, zoom: f32) -> Option<(u32, u32)> {
+        let buffer_size = self.natural_dimensions()?;
         let mut buffer_size = (buffer_size.0 as f32 * zoom, buffer_size.1 as f32 * zoom);
         if let Some(image) = self.image_data.lock().as_deref().unwrap() {
             if image.scale {
@@ -395,7 +588,7 @@ impl Image {
                 buffer_size.1 *= self.hidpi_scale;
             }
         }
-        let max_width = screen_size.0 - 2. * DEFAULT_MARGIN;
+        let max_width = max_size.0;
         let dimensions = if let Some(size) = self.size {
             let dimensions = self.dimensions_from_image_size(&size)?;
             let target_dimensions = (
@@ -421,8 +614,8 @@ impl Image {
         Some(dimensions)
     }
 
-    pub fn size(&mut self, screen_size: Size, zoom: f32) -> Option<Size> {
-        self.dimensions(screen_size, zoom)
+    pub fn size(&mut self, max_size: Size, zoom: f32) -> Option<Size> {
+        self.dimensions(max_size, zoom)
             .map(|d| (d.0 as f32, d.1 as f32))
     }
 }
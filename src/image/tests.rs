@@ -1,7 +1,11 @@
 use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
 use std::{fmt, fs};
 
-use super::{ImageData, Px};
+use super::{Image, ImageData, Px};
+use crate::interpreter::ImageCallback;
+use crate::test_utils::image::{Sample, SamplePng};
 use crate::test_utils::log;
 
 #[test]
@@ -50,6 +54,65 @@ fn source_image_variety() {
     }
 }
 
+#[test]
+fn downscale_and_redecode() {
+    log::init();
+
+    let bytes = Sample::from(SamplePng::Bun).pre_decode();
+
+    let mut image = ImageData::load_with_max_width(&bytes, true, Some(200)).unwrap();
+    assert_eq!(image.dimensions.0, 200);
+    assert_eq!(image.natural_dimensions(), (396, 347));
+    assert_eq!(image.to_bytes().len(), image.rgba_image_byte_size());
+
+    // Already wide enough
+    assert!(!image.redecode_for_width(150));
+    assert!(image.redecode_for_width(300));
+    assert_eq!(image.dimensions.0, 300);
+    // Capped at the full size
+    assert!(image.redecode_for_width(1_000));
+    assert_eq!(image.dimensions, (396, 347));
+    assert!(!image.redecode_for_width(2_000));
+
+    // Small enough images are left alone
+    let image = ImageData::load_with_max_width(&bytes, true, Some(1_000)).unwrap();
+    assert_eq!(image, ImageData::load(&bytes, true).unwrap());
+}
+
+struct SendCallback(mpsc::Sender<String>);
+
+impl ImageCallback for SendCallback {
+    fn loaded_image(&self, src: String, _: Arc<Mutex<Option<ImageData>>>) {
+        self.0.send(src).unwrap();
+    }
+}
+
+#[test]
+fn redecodes_in_the_background() {
+    log::init();
+
+    let bytes = Sample::from(SamplePng::Bun).pre_decode();
+    let data = ImageData::load_with_max_width(&bytes, true, Some(200)).unwrap();
+    let mut image = Image::from_image_data(Arc::new(Mutex::new(Some(data))), "bun".into(), 1.);
+    image.texture_dimensions = Some((200, 175));
+
+    let (sender, receiver) = mpsc::channel();
+    image.ensure_resolution(250., || Box::new(SendCallback(sender.clone())));
+    // Only one decode runs at a time
+    image.ensure_resolution(250., || panic!("Already decoding"));
+    let src = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(src, "bun");
+    let dimensions = image.buffer_dimensions().unwrap();
+    assert_eq!(dimensions.0, 375);
+    // The budget counts the texture that's still around rather than the new image data
+    assert_eq!(image.texture_byte_size(), 200 * 175 * 4);
+
+    // The old texture gets dropped once the new image data is in
+    image.ensure_resolution(250., || panic!("Wide enough"));
+    assert_eq!(image.texture_dimensions, None);
+    assert_eq!(image.texture_byte_size(), 0);
+}
+
 #[derive(PartialEq)]
 struct Rgba8Data(Vec<[u8; 4]>);
 
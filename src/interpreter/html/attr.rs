@@ -1,6 +1,11 @@
+use std::ops::RangeInclusive;
 use std::slice;
 
-use crate::{image::Px, opts::ResolvedTheme, utils::Align};
+use crate::{
+    image::Px,
+    opts::ResolvedTheme,
+    utils::{Align, Direction},
+};
 
 use html5ever::{local_name, Attribute};
 
@@ -20,6 +25,7 @@ impl Iterator for Iter<'_> {
             let Attribute { name, value } = self.0.next()?;
             let attr = match name.local {
                 local_name!("align") => Align::new(value).map(Attr::Align),
+                local_name!("dir") => Direction::new(value).map(Attr::Dir),
                 local_name!("href") => Some(Attr::Href(value.to_string())),
                 local_name!("id") => Some(Attr::Anchor(format!("#{value}"))),
                 local_name!("width") => value.parse().ok().map(Attr::Width),
@@ -31,8 +37,17 @@ impl Iterator for Iter<'_> {
                     (value.to_string() == "checkbox").then_some(Attr::IsCheckbox)
                 }
                 local_name!("checked") => Some(Attr::IsChecked),
+                local_name!("open") => Some(Attr::IsOpen),
                 local_name!("media") => PrefersColorScheme::new(value).map(Attr::Media),
                 local_name!("srcset") => Some(Attr::SrcSet(value.to_string())),
+                local_name!("title") => Some(Attr::Title(value.to_string())),
+                // Zero and out of range spans fall back to a single row or column
+                local_name!("colspan") => Some(Attr::ColSpan(parse_span(value, 1000))),
+                local_name!("rowspan") => Some(Attr::RowSpan(parse_span(value, 65534))),
+                // Set on the code of fenced code blocks from their options
+                _ if &*name.local == "data-linenos" => Some(Attr::LineNumbers),
+                _ if &*name.local == "data-hl" => Some(Attr::HighlightLines(parse_lines(value))),
+                _ if &*name.local == "data-diff" => Some(Attr::Diff),
                 _ => continue,
             };
 
@@ -45,6 +60,7 @@ impl Iterator for Iter<'_> {
 
 pub enum Attr {
     Align(Align),
+    Dir(Direction),
     Href(String),
     Anchor(String),
     Width(Px),
@@ -54,8 +70,35 @@ pub enum Attr {
     Style(String),
     IsCheckbox,
     IsChecked,
+    IsOpen,
     Media(PrefersColorScheme),
     SrcSet(String),
+    Title(String),
+    ColSpan(u16),
+    RowSpan(u16),
+    LineNumbers,
+    HighlightLines(Vec<RangeInclusive<usize>>),
+    Diff,
+}
+
+fn parse_span(value: &str, max: u16) -> u16 {
+    match value.trim().parse::<u16>() {
+        Ok(span) => span.clamp(1, max),
+        Err(_) => 1,
+    }
+}
+
+// Parses space separated line numbers and ranges of them like "3-5 8"
+fn parse_lines(value: &str) -> Vec<RangeInclusive<usize>> {
+    value
+        .split_whitespace()
+        .filter_map(|lines| {
+            let (start, end) = lines.split_once('-').unwrap_or((lines, lines));
+            let start = start.trim().parse().ok()?;
+            let end = end.trim().parse().ok()?;
+            Some(start..=end)
+        })
+        .collect()
 }
 
 impl Attr {
@@ -1,12 +1,13 @@
-use super::{picture, Header, List, ListType};
+use super::{picture, Cell, Header, List, ListType, TableRow};
 use crate::utils::Align;
-use crate::{Section, Table, TextBox};
+use crate::{Section, Table};
 
 pub enum Element {
     List(List),
     Input,
     Table(Table),
-    TableRow(Vec<TextBox>),
+    TableRow(TableRow),
+    TableCell(Cell),
     Header(Header),
     Paragraph(Option<Align>),
     Div(Option<Align>),
@@ -27,7 +28,7 @@ impl Element {
     }
 
     pub fn table_row() -> Self {
-        Self::TableRow(Vec::new())
+        Self::TableRow(TableRow::new())
     }
 
     pub fn unordered_list() -> Self {
@@ -46,6 +47,14 @@ impl Element {
         matches!(self, Self::Picture(_))
     }
 
+    pub fn is_details(&self) -> bool {
+        matches!(self, Self::Details(_))
+    }
+
+    pub fn is_table_cell(&self) -> bool {
+        matches!(self, Self::TableCell(_))
+    }
+
     pub fn as_mut_list(&mut self) -> Option<&mut List> {
         if let Self::List(list) = self {
             Some(list)
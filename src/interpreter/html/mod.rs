@@ -9,7 +9,8 @@ pub use element::Element;
 pub use picture::Picture;
 pub use tag_name::TagName;
 
-use crate::utils::Align;
+use crate::table::TableCell;
+use crate::utils::{Align, Direction};
 
 use html5ever::Attribute;
 
@@ -23,6 +24,16 @@ pub fn find_align(attrs: &[Attribute]) -> Option<Align> {
     })
 }
 
+pub fn find_dir(attrs: &[Attribute]) -> Option<Direction> {
+    attr::Iter::new(attrs).find_map(|attr| {
+        if let Attr::Dir(dir) = attr {
+            Some(dir)
+        } else {
+            None
+        }
+    })
+}
+
 pub fn find_style(attrs: &[Attribute]) -> Option<String> {
     attr::Iter::new(attrs).find_map(|attr| {
         if let Attr::Style(style) = attr {
@@ -43,15 +54,14 @@ pub enum HeaderType {
 }
 
 impl HeaderType {
-    // https://html.spec.whatwg.org/multipage/rendering.html#sections-and-headings
-    pub fn size_multiplier(&self) -> f32 {
+    pub fn level(&self) -> u8 {
         match self {
-            HeaderType::H1 => 2.0,
-            HeaderType::H2 => 1.5,
-            HeaderType::H3 => 1.17,
-            HeaderType::H4 => 1.0,
-            HeaderType::H5 => 0.83,
-            HeaderType::H6 => 0.67,
+            HeaderType::H1 => 1,
+            HeaderType::H2 => 2,
+            HeaderType::H3 => 3,
+            HeaderType::H4 => 4,
+            HeaderType::H5 => 5,
+            HeaderType::H6 => 6,
         }
     }
 }
@@ -67,6 +77,38 @@ impl Header {
     }
 }
 
+pub struct Cell {
+    pub inner: TableCell,
+    pub align: Align,
+    // Cells get indented on their own, so the surrounding indent is restored once they end
+    pub outer_indent: f32,
+}
+
+pub struct TableRow {
+    pub cells: Vec<TableCell>,
+    // Leading rows made up of only `<th>`s become the table's headers
+    pub is_header: bool,
+}
+
+impl TableRow {
+    pub fn new() -> Self {
+        Self {
+            cells: Vec::new(),
+            is_header: true,
+        }
+    }
+}
+
+impl Cell {
+    pub fn new(align: Align, outer_indent: f32) -> Self {
+        Self {
+            inner: TableCell::default(),
+            align,
+            outer_indent,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ListType {
     Ordered(usize),
@@ -88,5 +130,11 @@ pub struct TextOptions {
     pub code: usize,
     pub pre_formatted: usize,
     pub block_quote: usize,
+    pub keyboard: usize,
+    pub mark: usize,
+    pub superscript: usize,
+    pub subscript: usize,
     pub link: Vec<String>,
+    // The `title` of each enclosing `<abbr>`
+    pub abbr_title: Vec<Option<String>>,
 }
@@ -1,12 +1,13 @@
 use crate::image::ImageSize;
 use crate::opts::ResolvedTheme;
-use crate::utils::Align;
+use crate::utils::{Align, Float};
 
 use anyhow::Context;
 
 #[derive(Debug, Default)]
 pub struct Inner {
     pub align: Option<Align>,
+    pub float: Option<Float>,
     pub dark_variant: Option<String>,
     pub light_variant: Option<String>,
     pub size: Option<ImageSize>,
@@ -23,6 +24,10 @@ impl Builder {
         self.inner.align = Some(align);
     }
 
+    pub fn set_float(&mut self, float: Float) {
+        self.inner.float = Some(float);
+    }
+
     pub fn set_dark_variant(&mut self, dark: String) {
         self.inner.dark_variant = Some(dark);
     }
@@ -1,3 +1,7 @@
+use crate::utils::{Align, Float};
+
+/// Iterates over the declarations of an inline `style` attribute, skipping over any that aren't
+/// supported or fail to parse
 pub struct Iter<'style>(std::str::Split<'style, char>);
 
 impl<'style> Iter<'style> {
@@ -11,41 +15,59 @@ impl Iterator for Iter<'_> {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let part = self.0.next()?;
-
-            if let Some(bg_color) = part
-                .strip_prefix("background-color:#")
-                .and_then(|hex_str| u32::from_str_radix(hex_str, 16).ok())
-            {
-                return Some(Style::BackgroundColor(bg_color));
-            } else if let Some(color) = part
-                .strip_prefix("color:#")
-                .and_then(|hex_str| u32::from_str_radix(hex_str, 16).ok())
-            {
-                return Some(Style::Color(color));
-            } else if let Some(w) = part.strip_prefix("font-weight:").and_then(FontWeight::new) {
-                return Some(Style::FontWeight(w));
-            } else if let Some(s) = part.strip_prefix("font-style:").and_then(FontStyle::new) {
-                return Some(Style::FontStyle(s));
-            } else if let Some(d) = part
-                .strip_prefix("text-decoration:")
-                .and_then(TextDecoration::new)
-            {
-                return Some(Style::TextDecoration(d));
+            let declaration = self.0.next()?;
+            let Some((property, value)) = declaration.split_once(':') else {
+                continue;
+            };
+            let property = property.trim().to_ascii_lowercase();
+            let value = value.trim();
+            let value = value
+                .strip_suffix("!important")
+                .map_or(value, str::trim_end)
+                .to_ascii_lowercase();
+
+            let style = match property.as_str() {
+                "background-color" | "background" => {
+                    parse_color(&value).map(Style::BackgroundColor)
+                }
+                "color" => parse_color(&value).map(Style::Color),
+                "font-weight" => FontWeight::new(&value).map(Style::FontWeight),
+                "font-style" => FontStyle::new(&value).map(Style::FontStyle),
+                "font-size" => FontSize::new(&value).map(Style::FontSize),
+                "text-decoration" | "text-decoration-line" => {
+                    TextDecoration::new(&value).map(Style::TextDecoration)
+                }
+                "text-align" => parse_text_align(&value).map(Style::TextAlign),
+                "float" => Float::new(&value).map(Style::Float),
+                "margin-left" => Length::new(&value).map(Style::MarginLeft),
+                "width" => Length::new(&value).map(Style::Width),
+                "height" => Length::new(&value).map(Style::Height),
+                _ => None,
+            };
+
+            if style.is_some() {
+                break style;
             }
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Style {
     BackgroundColor(u32),
     Color(u32),
     FontWeight(FontWeight),
     FontStyle(FontStyle),
+    FontSize(FontSize),
     TextDecoration(TextDecoration),
+    TextAlign(Align),
+    Float(Float),
+    MarginLeft(Length),
+    Width(Length),
+    Height(Length),
 }
 
-#[derive(Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum FontWeight {
     #[default]
     Normal,
@@ -55,13 +77,21 @@ pub enum FontWeight {
 impl FontWeight {
     pub fn new(s: &str) -> Option<Self> {
         match s {
-            "bold" => Some(Self::Bold),
-            _ => None,
+            "bold" | "bolder" => Some(Self::Bold),
+            "normal" | "lighter" => Some(Self::Normal),
+            _ => {
+                let weight: u16 = s.parse().ok()?;
+                Some(if weight >= 600 {
+                    Self::Bold
+                } else {
+                    Self::Normal
+                })
+            }
         }
     }
 }
 
-#[derive(Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum FontStyle {
     #[default]
     Normal,
@@ -71,24 +101,406 @@ pub enum FontStyle {
 impl FontStyle {
     pub fn new(s: &str) -> Option<Self> {
         match s {
-            "italic" => Some(Self::Italic),
+            "italic" | "oblique" => Some(Self::Italic),
+            "normal" => Some(Self::Normal),
             _ => None,
         }
     }
 }
 
-#[derive(Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum TextDecoration {
     #[default]
     Normal,
     Underline,
+    LineThrough,
 }
 
 impl TextDecoration {
     pub fn new(s: &str) -> Option<Self> {
-        match s {
+        // Shorthands can also contain a color and style, so look for the line itself
+        s.split_whitespace().find_map(|part| match part {
             "underline" => Some(Self::Underline),
+            "line-through" => Some(Self::LineThrough),
+            "none" => Some(Self::Normal),
             _ => None,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FontSize {
+    Px(f32),
+    // Relative to the size of the surrounding text
+    Scale(f32),
+    // Relative to the configured font size
+    Rem(f32),
+}
+
+impl FontSize {
+    pub fn new(s: &str) -> Option<Self> {
+        let size = match s {
+            "xx-small" => Self::Px(9.),
+            "x-small" => Self::Px(10.),
+            "small" => Self::Px(13.),
+            "medium" => Self::Rem(1.),
+            "large" => Self::Px(18.),
+            "x-large" => Self::Px(24.),
+            "xx-large" => Self::Px(32.),
+            "xxx-large" => Self::Px(48.),
+            "smaller" => Self::Scale(1. / 1.2),
+            "larger" => Self::Scale(1.2),
+            _ => {
+                if let Some(percent) = s.strip_suffix('%') {
+                    Self::Scale(percent.parse::<f32>().ok()? / 100.)
+                } else {
+                    match Length::new(s)? {
+                        Length::Px(px) => Self::Px(px),
+                        Length::Em(em) => Self::Scale(em),
+                        Length::Rem(rem) => Self::Rem(rem),
+                    }
+                }
+            }
+        };
+
+        Some(size).filter(|size| match size {
+            Self::Px(n) | Self::Scale(n) | Self::Rem(n) => n.is_finite() && *n > 0.,
+        })
+    }
+
+    pub fn resolve(self, current: f32, root: f32) -> f32 {
+        match self {
+            Self::Px(px) => px,
+            Self::Scale(scale) => current * scale,
+            Self::Rem(rem) => root * rem,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Length {
+    Px(f32),
+    // Relative to the size of the surrounding text
+    Em(f32),
+    // Relative to the configured font size
+    Rem(f32),
+}
+
+impl Length {
+    pub fn new(s: &str) -> Option<Self> {
+        if s == "0" {
+            return Some(Self::Px(0.));
         }
+        // `rem` has to come before `em` since it's a suffix of it
+        let length = if let Some(px) = s.strip_suffix("px") {
+            Self::Px(px.parse().ok()?)
+        } else if let Some(pt) = s.strip_suffix("pt") {
+            Self::Px(pt.parse::<f32>().ok()? * 4. / 3.)
+        } else if let Some(rem) = s.strip_suffix("rem") {
+            Self::Rem(rem.parse().ok()?)
+        } else {
+            Self::Em(s.strip_suffix("em")?.parse().ok()?)
+        };
+
+        Some(length).filter(|length| match length {
+            Self::Px(n) | Self::Em(n) | Self::Rem(n) => n.is_finite(),
+        })
+    }
+
+    /// The length in pixels for text of size `current` where the configured size is `root`
+    pub fn resolve(self, current: f32, root: f32) -> f32 {
+        match self {
+            Self::Px(px) => px,
+            Self::Em(em) => current * em,
+            Self::Rem(rem) => root * rem,
+        }
+    }
+}
+
+fn parse_text_align(s: &str) -> Option<Align> {
+    match s {
+        "left" | "start" => Some(Align::Left),
+        "center" => Some(Align::Center),
+        "right" | "end" => Some(Align::Right),
+        "justify" => Some(Align::Justify),
+        _ => None,
+    }
+}
+
+/// Parses a CSS color into `0xRRGGBB`. Colors that are fully transparent are ignored
+pub fn parse_color(s: &str) -> Option<u32> {
+    if let Some(hex) = s.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+    if let Some((function, args)) = s.strip_suffix(')').and_then(|s| s.split_once('(')) {
+        let args: Vec<&str> = args
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .collect();
+        let (channels, alpha) = match args.as_slice() {
+            [a, b, c] => ([*a, *b, *c], None),
+            [a, b, c, alpha] => ([*a, *b, *c], Some(*alpha)),
+            _ => return None,
+        };
+        if let Some(alpha) = alpha {
+            if parse_number_or_percent(alpha, 1.)? <= 0. {
+                return None;
+            }
+        }
+        let [r, g, b] = match function.trim() {
+            "rgb" | "rgba" => {
+                let mut rgb = [0; 3];
+                for (channel, arg) in rgb.iter_mut().zip(channels) {
+                    *channel = parse_number_or_percent(arg, 255.)?.round().clamp(0., 255.) as u8;
+                }
+                rgb
+            }
+            "hsl" | "hsla" => {
+                let hue = channels[0].strip_suffix("deg").unwrap_or(channels[0]);
+                let hue: f32 = hue.parse().ok()?;
+                let saturation = channels[1].strip_suffix('%')?.parse::<f32>().ok()? / 100.;
+                let lightness = channels[2].strip_suffix('%')?.parse::<f32>().ok()? / 100.;
+                hsl_to_rgb(hue, saturation.clamp(0., 1.), lightness.clamp(0., 1.))
+            }
+            _ => return None,
+        };
+        return Some(u32::from_be_bytes([0, r, g, b]));
+    }
+
+    named_color(s)
+}
+
+fn parse_hex(hex: &str) -> Option<u32> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let expand = |hex: &str| -> String { hex.chars().flat_map(|c| [c, c]).collect() };
+    let (rgb, alpha) = match hex.len() {
+        3 => (expand(hex), None),
+        4 => (expand(&hex[..3]), Some(expand(&hex[3..]))),
+        6 => (hex.to_owned(), None),
+        8 => (hex[..6].to_owned(), Some(hex[6..].to_owned())),
+        _ => return None,
+    };
+    if alpha.is_some_and(|alpha| alpha == "00") {
+        return None;
+    }
+    u32::from_str_radix(&rgb, 16).ok()
+}
+
+// Percentages are scaled up to `max`
+fn parse_number_or_percent(s: &str, max: f32) -> Option<f32> {
+    match s.strip_suffix('%') {
+        Some(percent) => Some(percent.parse::<f32>().ok()? / 100. * max),
+        None => s.parse().ok(),
+    }
+}
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> [u8; 3] {
+    let chroma = (1. - (2. * lightness - 1.).abs()) * saturation;
+    let hue = hue.rem_euclid(360.) / 60.;
+    let x = chroma * (1. - (hue % 2. - 1.).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.),
+        1 => (x, chroma, 0.),
+        2 => (0., chroma, x),
+        3 => (0., x, chroma),
+        4 => (x, 0., chroma),
+        _ => (chroma, 0., x),
+    };
+    let m = lightness - chroma / 2.;
+    [r, g, b].map(|channel| ((channel + m) * 255.).round().clamp(0., 255.) as u8)
+}
+
+fn named_color(name: &str) -> Option<u32> {
+    let color = match name {
+        "aliceblue" => 0xF0F8FF,
+        "antiquewhite" => 0xFAEBD7,
+        "aqua" | "cyan" => 0x00FFFF,
+        "aquamarine" => 0x7FFFD4,
+        "azure" => 0xF0FFFF,
+        "beige" => 0xF5F5DC,
+        "bisque" => 0xFFE4C4,
+        "black" => 0x000000,
+        "blanchedalmond" => 0xFFEBCD,
+        "blue" => 0x0000FF,
+        "blueviolet" => 0x8A2BE2,
+        "brown" => 0xA52A2A,
+        "burlywood" => 0xDEB887,
+        "cadetblue" => 0x5F9EA0,
+        "chartreuse" => 0x7FFF00,
+        "chocolate" => 0xD2691E,
+        "coral" => 0xFF7F50,
+        "cornflowerblue" => 0x6495ED,
+        "cornsilk" => 0xFFF8DC,
+        "crimson" => 0xDC143C,
+        "darkblue" => 0x00008B,
+        "darkcyan" => 0x008B8B,
+        "darkgoldenrod" => 0xB8860B,
+        "darkgray" | "darkgrey" => 0xA9A9A9,
+        "darkgreen" => 0x006400,
+        "darkkhaki" => 0xBDB76B,
+        "darkmagenta" => 0x8B008B,
+        "darkolivegreen" => 0x556B2F,
+        "darkorange" => 0xFF8C00,
+        "darkorchid" => 0x9932CC,
+        "darkred" => 0x8B0000,
+        "darksalmon" => 0xE9967A,
+        "darkseagreen" => 0x8FBC8F,
+        "darkslateblue" => 0x483D8B,
+        "darkslategray" | "darkslategrey" => 0x2F4F4F,
+        "darkturquoise" => 0x00CED1,
+        "darkviolet" => 0x9400D3,
+        "deeppink" => 0xFF1493,
+        "deepskyblue" => 0x00BFFF,
+        "dimgray" | "dimgrey" => 0x696969,
+        "dodgerblue" => 0x1E90FF,
+        "firebrick" => 0xB22222,
+        "floralwhite" => 0xFFFAF0,
+        "forestgreen" => 0x228B22,
+        "fuchsia" | "magenta" => 0xFF00FF,
+        "gainsboro" => 0xDCDCDC,
+        "ghostwhite" => 0xF8F8FF,
+        "gold" => 0xFFD700,
+        "goldenrod" => 0xDAA520,
+        "gray" | "grey" => 0x808080,
+        "green" => 0x008000,
+        "greenyellow" => 0xADFF2F,
+        "honeydew" => 0xF0FFF0,
+        "hotpink" => 0xFF69B4,
+        "indianred" => 0xCD5C5C,
+        "indigo" => 0x4B0082,
+        "ivory" => 0xFFFFF0,
+        "khaki" => 0xF0E68C,
+        "lavender" => 0xE6E6FA,
+        "lavenderblush" => 0xFFF0F5,
+        "lawngreen" => 0x7CFC00,
+        "lemonchiffon" => 0xFFFACD,
+        "lightblue" => 0xADD8E6,
+        "lightcoral" => 0xF08080,
+        "lightcyan" => 0xE0FFFF,
+        "lightgoldenrodyellow" => 0xFAFAD2,
+        "lightgray" | "lightgrey" => 0xD3D3D3,
+        "lightgreen" => 0x90EE90,
+        "lightpink" => 0xFFB6C1,
+        "lightsalmon" => 0xFFA07A,
+        "lightseagreen" => 0x20B2AA,
+        "lightskyblue" => 0x87CEFA,
+        "lightslategray" | "lightslategrey" => 0x778899,
+        "lightsteelblue" => 0xB0C4DE,
+        "lightyellow" => 0xFFFFE0,
+        "lime" => 0x00FF00,
+        "limegreen" => 0x32CD32,
+        "linen" => 0xFAF0E6,
+        "maroon" => 0x800000,
+        "mediumaquamarine" => 0x66CDAA,
+        "mediumblue" => 0x0000CD,
+        "mediumorchid" => 0xBA55D3,
+        "mediumpurple" => 0x9370DB,
+        "mediumseagreen" => 0x3CB371,
+        "mediumslateblue" => 0x7B68EE,
+        "mediumspringgreen" => 0x00FA9A,
+        "mediumturquoise" => 0x48D1CC,
+        "mediumvioletred" => 0xC71585,
+        "midnightblue" => 0x191970,
+        "mintcream" => 0xF5FFFA,
+        "mistyrose" => 0xFFE4E1,
+        "moccasin" => 0xFFE4B5,
+        "navajowhite" => 0xFFDEAD,
+        "navy" => 0x000080,
+        "oldlace" => 0xFDF5E6,
+        "olive" => 0x808000,
+        "olivedrab" => 0x6B8E23,
+        "orange" => 0xFFA500,
+        "orangered" => 0xFF4500,
+        "orchid" => 0xDA70D6,
+        "palegoldenrod" => 0xEEE8AA,
+        "palegreen" => 0x98FB98,
+        "paleturquoise" => 0xAFEEEE,
+        "palevioletred" => 0xDB7093,
+        "papayawhip" => 0xFFEFD5,
+        "peachpuff" => 0xFFDAB9,
+        "peru" => 0xCD853F,
+        "pink" => 0xFFC0CB,
+        "plum" => 0xDDA0DD,
+        "powderblue" => 0xB0E0E6,
+        "purple" => 0x800080,
+        "rebeccapurple" => 0x663399,
+        "red" => 0xFF0000,
+        "rosybrown" => 0xBC8F8F,
+        "royalblue" => 0x4169E1,
+        "saddlebrown" => 0x8B4513,
+        "salmon" => 0xFA8072,
+        "sandybrown" => 0xF4A460,
+        "seagreen" => 0x2E8B57,
+        "seashell" => 0xFFF5EE,
+        "sienna" => 0xA0522D,
+        "silver" => 0xC0C0C0,
+        "skyblue" => 0x87CEEB,
+        "slateblue" => 0x6A5ACD,
+        "slategray" | "slategrey" => 0x708090,
+        "snow" => 0xFFFAFA,
+        "springgreen" => 0x00FF7F,
+        "steelblue" => 0x4682B4,
+        "tan" => 0xD2B48C,
+        "teal" => 0x008080,
+        "thistle" => 0xD8BFD8,
+        "tomato" => 0xFF6347,
+        "turquoise" => 0x40E0D0,
+        "violet" => 0xEE82EE,
+        "wheat" => 0xF5DEB3,
+        "white" => 0xFFFFFF,
+        "whitesmoke" => 0xF5F5F5,
+        "yellow" => 0xFFFF00,
+        "yellowgreen" => 0x9ACD32,
+        _ => return None,
+    };
+    Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_color, FontSize, FontWeight, Iter, Length, Style};
+
+    #[test]
+    fn declarations() {
+        let styles: Vec<_> = Iter::new(
+            " color : RebeccaPurple ; background:rgba(0 0 0 / 0); font-weight:500;\
+            font-size: 2em; bogus: 1; width: 12pt; background-color: #abc",
+        )
+        .collect();
+        assert_eq!(
+            styles,
+            [
+                Style::Color(0x663399),
+                Style::FontWeight(FontWeight::Normal),
+                Style::FontSize(FontSize::Scale(2.)),
+                Style::Width(Length::Px(16.)),
+                Style::BackgroundColor(0xAABBCC),
+            ]
+        );
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("hsl(120deg, 100%, 25%)"), Some(0x008000));
+        assert_eq!(parse_color("rgb(100%, 0%, 50%)"), Some(0xFF0080));
+        assert_eq!(parse_color("transparent"), None);
+        assert_eq!(parse_color("#12345"), None);
+    }
+
+    #[test]
+    fn relative_sizes() {
+        assert_eq!(Length::new("2em"), Some(Length::Em(2.)));
+        assert_eq!(Length::new("1.5rem"), Some(Length::Rem(1.5)));
+        assert_eq!(Length::new("12pt"), Some(Length::Px(16.)));
+        assert_eq!(Length::new("2em").unwrap().resolve(20., 10.), 40.);
+        assert_eq!(Length::new("2rem").unwrap().resolve(20., 10.), 20.);
+
+        assert_eq!(FontSize::new("medium").unwrap().resolve(20., 10.), 10.);
+        assert_eq!(FontSize::new("2rem").unwrap().resolve(20., 10.), 20.);
+        assert_eq!(FontSize::new("2em").unwrap().resolve(20., 10.), 40.);
+        assert_eq!(FontSize::new("50%").unwrap().resolve(20., 10.), 10.);
+        assert_eq!(FontSize::new("0em"), None);
     }
 }
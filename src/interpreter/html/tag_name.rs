@@ -5,11 +5,15 @@ use string_cache::Atom;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TagName {
+    Abbreviation,
     Anchor,
     BlockQuote,
     BoldOrStrong,
     Break,
     Code,
+    DescriptionDetails,
+    DescriptionList,
+    DescriptionTerm,
     Details,
     Div,
     EmphasisOrItalic,
@@ -19,7 +23,9 @@ pub enum TagName {
     Source,
     Image,
     Input,
+    Keyboard,
     ListItem,
+    Mark,
     OrderedList,
     Paragraph,
     PreformattedText,
@@ -27,7 +33,9 @@ pub enum TagName {
     Small,
     Span,
     Strikethrough,
+    Subscript,
     Summary,
+    Superscript,
     Table,
     TableBody,
     TableDataCell,
@@ -43,11 +51,15 @@ impl TryFrom<&Atom<LocalNameStaticSet>> for TagName {
 
     fn try_from(atom: &Atom<LocalNameStaticSet>) -> Result<Self, Self::Error> {
         let tag_name = match atom {
+            &local_name!("abbr") => Self::Abbreviation,
             &local_name!("a") => Self::Anchor,
             &local_name!("blockquote") => Self::BlockQuote,
             &local_name!("b") | &local_name!("strong") => Self::BoldOrStrong,
             &local_name!("br") => Self::Break,
-            &local_name!("code") | &local_name!("kbd") => Self::Code,
+            &local_name!("code") => Self::Code,
+            &local_name!("dd") => Self::DescriptionDetails,
+            &local_name!("dl") => Self::DescriptionList,
+            &local_name!("dt") => Self::DescriptionTerm,
             &local_name!("details") => Self::Details,
             &local_name!("div") => Self::Div,
             &local_name!("em") | &local_name!("i") => Self::EmphasisOrItalic,
@@ -62,7 +74,9 @@ impl TryFrom<&Atom<LocalNameStaticSet>> for TagName {
             &local_name!("source") => Self::Source,
             &local_name!("img") => Self::Image,
             &local_name!("input") => Self::Input,
+            &local_name!("kbd") => Self::Keyboard,
             &local_name!("li") => Self::ListItem,
+            &local_name!("mark") => Self::Mark,
             &local_name!("ol") => Self::OrderedList,
             &local_name!("p") => Self::Paragraph,
             &local_name!("pre") => Self::PreformattedText,
@@ -70,7 +84,9 @@ impl TryFrom<&Atom<LocalNameStaticSet>> for TagName {
             &local_name!("small") => Self::Small,
             &local_name!("span") => Self::Span,
             &local_name!("s") | &local_name!("del") => Self::Strikethrough,
+            &local_name!("sub") => Self::Subscript,
             &local_name!("summary") => Self::Summary,
+            &local_name!("sup") => Self::Superscript,
             &local_name!("table") => Self::Table,
             &local_name!("tbody") => Self::TableBody,
             &local_name!("td") => Self::TableDataCell,
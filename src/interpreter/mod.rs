@@ -11,10 +11,10 @@ use std::sync::{mpsc, Arc, Mutex};
 
 use crate::color::{native_color, Theme};
 use crate::image::{Image, ImageData, ImageSize};
-use crate::opts::ResolvedTheme;
-use crate::positioner::{Positioned, Row, Section, Spacer, DEFAULT_MARGIN};
-use crate::text::{Text, TextBox};
-use crate::utils::{markdown_to_html, Align};
+use crate::opts::{ResolvedTheme, SyntaxOptions, Typography};
+use crate::positioner::{Positioned, Section, Spacer, DEFAULT_MARGIN};
+use crate::text::{Script, Text, TextBox};
+use crate::utils::{markdown_to_html, Align, Direction, Float};
 use crate::{Element, ImageCache, InlyneEvent};
 use html::{
     attr::{self, PrefersColorScheme},
@@ -28,57 +28,62 @@ use html5ever::tendril::*;
 use html5ever::tokenizer::{
     BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
 };
+use html5ever::Attribute;
 use wgpu::TextureFormat;
 use winit::event_loop::EventLoopProxy;
 use winit::window::Window;
 
 use self::html::{picture, HeaderType, Picture};
 
+#[derive(Default)]
 struct State {
     global_indent: f32,
     element_stack: Vec<InterpreterElement>,
     text_options: html::TextOptions,
-    span: Span,
-    // Stores the row and a counter of newlines after each image
-    inline_images: Option<(Row, usize)>,
+    inline_styles: Vec<InlineStyle>,
     pending_anchor: Option<String>,
     pending_list_prefix: Option<String>,
     anchorizer: Anchorizer,
 }
 
 impl State {
-    fn with_span_color(span_color: [f32; 4]) -> Self {
-        Self {
-            global_indent: 0.0,
-            element_stack: Vec::new(),
-            text_options: Default::default(),
-            span: Span::with_color(span_color),
-            inline_images: None,
-            pending_anchor: None,
-            pending_list_prefix: None,
-            anchorizer: Default::default(),
-        }
-    }
-
     fn element_iter_mut(&mut self) -> slice::IterMut<'_, InterpreterElement> {
         self.element_stack.iter_mut()
     }
+
+    // Finds the value set by the innermost element that styles this property
+    fn inline_style<T>(&self, f: impl Fn(&InlineStyle) -> Option<T>) -> Option<T> {
+        self.inline_styles.iter().rev().find_map(f)
+    }
 }
 
-struct Span {
-    color: [f32; 4],
-    weight: FontWeight,
-    style: FontStyle,
-    decor: TextDecoration,
+// Declarations from the `style` attribute of an open element
+struct InlineStyle {
+    tag_name: TagName,
+    color: Option<[f32; 4]>,
+    background: Option<[f32; 4]>,
+    weight: Option<FontWeight>,
+    style: Option<FontStyle>,
+    decor: Option<TextDecoration>,
+    font_size: Option<f32>,
+    align: Option<Align>,
+    dir: Option<Direction>,
+    margin_left: f32,
 }
 
-impl Span {
-    fn with_color(color: [f32; 4]) -> Self {
+impl InlineStyle {
+    fn new(tag_name: TagName) -> Self {
         Self {
-            color,
-            weight: Default::default(),
-            style: Default::default(),
-            decor: Default::default(),
+            tag_name,
+            color: None,
+            background: None,
+            weight: None,
+            style: None,
+            decor: None,
+            font_size: None,
+            align: None,
+            dir: None,
+            margin_left: 0.,
         }
     }
 }
@@ -95,7 +100,7 @@ trait WindowInteractor {
     fn image_callback(&self) -> Box<dyn ImageCallback + Send>;
 }
 
-struct EventLoopCallback(EventLoopProxy<InlyneEvent>);
+pub struct EventLoopCallback(pub EventLoopProxy<InlyneEvent>);
 
 impl ImageCallback for EventLoopCallback {
     fn loaded_image(&self, src: String, image_data: Arc<Mutex<Option<ImageData>>>) {
@@ -126,6 +131,13 @@ impl WindowInteractor for LiveWindow {
     }
 }
 
+pub enum InterpreterMsg {
+    Markdown(String),
+    // Used for everything interpreted after it. Re-send the markdown to apply it to the current
+    // document
+    Theme(Box<Theme>, Option<ResolvedTheme>),
+}
+
 pub struct HtmlInterpreter {
     element_queue: Arc<Mutex<VecDeque<Element>>>,
     current_textbox: TextBox,
@@ -142,6 +154,12 @@ pub struct HtmlInterpreter {
     image_cache: ImageCache,
     window: Box<dyn WindowInteractor + Send>,
     color_scheme: Option<ResolvedTheme>,
+    // Images wider than this get downscaled while decoding
+    image_max_width: Option<u32>,
+    // Shows line numbers on every code block instead of only those that ask for them
+    line_numbers: bool,
+    syntax_opts: SyntaxOptions,
+    typography: Typography,
 }
 
 impl HtmlInterpreter {
@@ -158,6 +176,10 @@ impl HtmlInterpreter {
         image_cache: ImageCache,
         event_proxy: EventLoopProxy<InlyneEvent>,
         color_scheme: Option<ResolvedTheme>,
+        image_max_width: Option<u32>,
+        line_numbers: bool,
+        syntax_opts: SyntaxOptions,
+        typography: Typography,
     ) -> Self {
         let live_window = LiveWindow {
             window,
@@ -172,6 +194,10 @@ impl HtmlInterpreter {
             image_cache,
             Box::new(live_window),
             color_scheme,
+            image_max_width,
+            line_numbers,
+            syntax_opts,
+            typography,
         )
     }
 
@@ -186,14 +212,18 @@ impl HtmlInterpreter {
         image_cache: ImageCache,
         window: Box<dyn WindowInteractor + Send>,
         color_scheme: Option<ResolvedTheme>,
+        image_max_width: Option<u32>,
+        line_numbers: bool,
+        syntax_opts: SyntaxOptions,
+        typography: Typography,
     ) -> Self {
         Self {
             window,
             element_queue,
-            current_textbox: TextBox::new(Vec::new(), hidpi_scale),
+            current_textbox: new_textbox(&typography, hidpi_scale),
             hidpi_scale,
             surface_format,
-            state: State::with_span_color(native_color(theme.code_color, &surface_format)),
+            state: State::default(),
             theme,
             file_path,
             should_queue: Arc::new(AtomicBool::new(true)),
@@ -201,27 +231,39 @@ impl HtmlInterpreter {
             first_pass: true,
             image_cache,
             color_scheme,
+            image_max_width,
+            line_numbers,
+            syntax_opts,
+            typography,
         }
     }
 
-    pub fn interpret_md(self, receiver: mpsc::Receiver<String>) {
+    pub fn interpret_md(self, receiver: mpsc::Receiver<InterpreterMsg>) {
         let mut input = BufferQueue::default();
 
-        let span_color = self.native_color(self.theme.text_color);
-        let code_highlighter = self.theme.code_highlighter.clone();
+        let syntax_opts = self.syntax_opts.clone();
         let mut tok = Tokenizer::new(self, TokenizerOpts::default());
 
-        for md_string in receiver {
+        for msg in receiver {
+            let md_string = match msg {
+                InterpreterMsg::Markdown(md_string) => md_string,
+                InterpreterMsg::Theme(theme, color_scheme) => {
+                    tok.sink.theme = *theme;
+                    tok.sink.color_scheme = color_scheme;
+                    continue;
+                }
+            };
             tracing::debug!(
                 "Received markdown for interpretation: {} bytes",
                 md_string.len()
             );
 
             if tok.sink.should_queue.load(AtomicOrdering::Relaxed) {
-                tok.sink.state = State::with_span_color(span_color);
-                tok.sink.current_textbox = TextBox::new(Vec::new(), tok.sink.hidpi_scale);
+                tok.sink.state = State::default();
+                tok.sink.current_textbox = new_textbox(&tok.sink.typography, tok.sink.hidpi_scale);
                 tok.sink.stopped = false;
-                let htmlified = markdown_to_html(&md_string, code_highlighter.clone());
+                let code_highlighter = tok.sink.theme.code_highlighter.clone();
+                let htmlified = markdown_to_html(&md_string, code_highlighter, &syntax_opts);
 
                 input.push_back(
                     Tendril::from_str(&htmlified)
@@ -249,6 +291,9 @@ impl HtmlInterpreter {
             | InterpreterElement::Header(html::Header {
                 align: Some(elem_align),
                 ..
+            })
+            | InterpreterElement::TableCell(html::Cell {
+                align: elem_align, ..
             }) = element
             {
                 return Some(*elem_align);
@@ -257,55 +302,138 @@ impl HtmlInterpreter {
         None
     }
 
+    // Parses the `style` attribute of an element and pushes it onto the inline style stack. Only
+    // block level elements get to shift the indent with `margin-left` or size the font with
+    // `font-size` since a whole text box shares a single size, and spans can't set the text
+    // direction with `dir`. Returns any `text-align`
+    fn push_inline_style(&mut self, tag_name: TagName, attrs: &[Attribute]) -> Option<Align> {
+        let parent_size = self.state.inline_style(|s| s.font_size);
+        let is_block = matches!(
+            tag_name,
+            TagName::Div | TagName::Paragraph | TagName::Header(_)
+        );
+
+        let mut inline = InlineStyle::new(tag_name);
+        let mut margin_left = None;
+        if tag_name != TagName::Span {
+            inline.dir = html::find_dir(attrs);
+        }
+        let style_str = html::find_style(attrs).unwrap_or_default();
+        for style in style::Iter::new(&style_str) {
+            match style {
+                Style::Color(color) => inline.color = Some(self.native_color(color)),
+                Style::BackgroundColor(color) => inline.background = Some(self.native_color(color)),
+                Style::FontWeight(weight) => inline.weight = Some(weight),
+                Style::FontStyle(style) => inline.style = Some(style),
+                Style::TextDecoration(decor) => inline.decor = Some(decor),
+                Style::FontSize(size) if is_block => {
+                    let parent_size = parent_size.unwrap_or(self.typography.font_size);
+                    inline.font_size = Some(size.resolve(parent_size, self.typography.font_size));
+                }
+                Style::TextAlign(align) => inline.align = Some(align),
+                Style::MarginLeft(margin) if is_block => margin_left = Some(margin),
+                Style::FontSize(_)
+                | Style::MarginLeft(_)
+                | Style::Width(_)
+                | Style::Height(_)
+                | Style::Float(_) => {}
+            }
+        }
+        // Headers scale relative to an explicitly sized parent
+        if let TagName::Header(header_type) = tag_name {
+            if inline.font_size.is_none() {
+                let scale = self.typography.heading_scale[usize::from(header_type.level() - 1)];
+                inline.font_size = parent_size.map(|size| size * scale);
+            }
+        }
+        // `em` margins are relative to the element's own font size
+        if let Some(margin) = margin_left {
+            let font_size = match (inline.font_size, tag_name) {
+                (Some(size), _) => size,
+                (None, TagName::Header(header_type)) => {
+                    self.typography.heading_size(header_type.level())
+                }
+                (None, _) => parent_size.unwrap_or(self.typography.font_size),
+            };
+            inline.margin_left = margin.resolve(font_size, self.typography.font_size);
+        }
+
+        self.state.global_indent += inline.margin_left;
+        self.current_textbox.indent = self.state.global_indent;
+        let align = inline.align;
+        self.state.inline_styles.push(inline);
+        align
+    }
+
+    // Pops the style of the innermost open `tag_name` along with any elements inside of it that
+    // were never closed
+    fn pop_inline_style(&mut self, tag_name: TagName) {
+        let Some(index) = self
+            .state
+            .inline_styles
+            .iter()
+            .rposition(|inline| inline.tag_name == tag_name)
+        else {
+            tracing::warn!("Closed a {tag_name:?} that has no inline style");
+            return;
+        };
+
+        for inline in self.state.inline_styles.drain(index..) {
+            self.state.global_indent -= inline.margin_left;
+        }
+        self.current_textbox.indent = self.state.global_indent;
+    }
+
     #[must_use]
     fn native_color(&self, color: u32) -> [f32; 4] {
         native_color(color, &self.surface_format)
     }
 
     fn push_current_textbox(&mut self) {
-        // Push any inline images
-        if let Some((row, count)) = self.state.inline_images.take() {
-            if count == 0 {
-                self.push_element(row);
-                self.push_spacer();
-            } else {
-                self.state.inline_images = Some((row, count))
-            }
-        }
-
-        if !self.current_textbox.texts.is_empty() {
-            let mut empty = true;
-            for text in &self.current_textbox.texts {
-                if !text.text.trim().is_empty() {
-                    empty = false;
-                    break;
-                }
-            }
-            if !empty {
-                self.current_textbox.indent = self.state.global_indent;
-                let section = self.state.element_iter_mut().rev().find_map(|e| {
-                    if let InterpreterElement::Details(section) = e {
-                        Some(section)
-                    } else {
-                        None
-                    }
-                });
-                if let Some(section) = section {
-                    section
-                        .elements
-                        .push(Positioned::new(self.current_textbox.clone()));
-                } else {
-                    self.push_element(self.current_textbox.clone());
+        let has_text = self
+            .current_textbox
+            .texts
+            .iter()
+            .any(|text| text.image.is_none() && !text.text.trim().is_empty());
+        let has_images = !self.current_textbox.images.is_empty();
+        if has_text {
+            // Images that sit within text and don't have a size match the size of the font
+            let font_size = self.current_textbox.font_size.round() as u32;
+            for image in &mut self.current_textbox.images {
+                if image.inner.size.is_none() {
+                    image.inner.size = Some(ImageSize::height(font_size));
                 }
             }
         }
-        self.current_textbox = TextBox::new(Vec::new(), self.hidpi_scale);
+        if has_text || has_images {
+            self.current_textbox.indent = self.state.global_indent;
+            self.push_element(self.current_textbox.clone());
+        }
+        self.current_textbox = new_textbox(&self.typography, self.hidpi_scale);
         self.current_textbox.indent = self.state.global_indent;
     }
     fn push_spacer(&mut self) {
-        self.push_element(Spacer::invisible());
+        self.push_element(Spacer::new(self.typography.paragraph_spacing, false));
     }
     fn push_element<I: Into<Element>>(&mut self, element: I) {
+        // Anything within a `<details>` or table cell belongs to the innermost one that's open
+        let container = self.state.element_iter_mut().rev().find(|e| {
+            matches!(
+                e,
+                InterpreterElement::Details(_) | InterpreterElement::TableCell(_)
+            )
+        });
+        match container {
+            Some(InterpreterElement::Details(section)) => {
+                section.elements.push(Positioned::new(element.into()));
+                return;
+            }
+            Some(InterpreterElement::TableCell(cell)) => {
+                cell.inner.push(element.into());
+                return;
+            }
+            _ => {}
+        }
         self.element_queue.lock().unwrap().push_back(element.into());
         if self.first_pass {
             self.window.request_redraw()
@@ -319,12 +447,13 @@ impl HtmlInterpreter {
         let is_url = src.starts_with("http://") || src.starts_with("https://");
         let mut image = match self.image_cache.lock().unwrap().get(&src) {
             Some(image_data) if is_url => {
-                Image::from_image_data(image_data.clone(), self.hidpi_scale)
+                Image::from_image_data(image_data.clone(), src.clone(), self.hidpi_scale)
             }
             _ => Image::from_src(
                 src.clone(),
                 self.file_path.clone(),
                 self.hidpi_scale,
+                self.image_max_width,
                 self.window.image_callback(),
             )
             .unwrap(),
@@ -338,20 +467,59 @@ impl HtmlInterpreter {
             image = image.with_size(size);
         }
 
-        if align == Align::Left {
-            if let Some((row, count)) = &mut self.state.inline_images {
-                row.elements.push(Positioned::new(image));
-                // Restart newline count
-                *count = 1;
-            } else {
-                self.state.inline_images = Some((Row::with_image(image, self.hidpi_scale), 1));
-            }
+        if let Some(float) = pic.inner.float {
+            // Goes ahead of the text that's still being collected, so that it wraps around
+            self.push_element(image.with_float(float));
+        } else if matches!(align, Align::Left | Align::Justify) {
+            self.push_inline_image(image);
         } else {
             self.push_element(image);
             self.push_spacer();
         }
     }
 
+    fn push_inline_image(&mut self, image: Image) {
+        let text_native_color = self.native_color(self.theme.text_color);
+        let mut text = Text::new(String::new(), self.hidpi_scale, text_native_color);
+        if let Some(link) = self.state.text_options.link.last() {
+            text = text.with_link((*link).clone());
+        }
+        self.push_pending_list_prefix(text_native_color);
+        if self.state.text_options.block_quote >= 1 {
+            self.current_textbox
+                .set_quote_block(self.state.text_options.block_quote);
+        }
+        self.current_textbox.push_image(image, text);
+    }
+
+    fn push_pending_list_prefix(&mut self, color: [f32; 4]) {
+        if let Some(prefix) = self.state.pending_list_prefix.take() {
+            if self.current_textbox.texts.is_empty() {
+                self.current_textbox
+                    .texts
+                    .push(Text::new(prefix, self.hidpi_scale, color).make_bold(true));
+            }
+        }
+    }
+
+    // Separates the upcoming text from what came before unless there's whitespace already
+    fn push_separating_space(&mut self, color: [f32; 4]) {
+        let Some(last_text) = self.current_textbox.texts.last() else {
+            return;
+        };
+        let needs_space = last_text.image.is_some()
+            || last_text
+                .text
+                .chars()
+                .last()
+                .is_some_and(|last_char| !last_char.is_whitespace());
+        if needs_space {
+            self.current_textbox
+                .texts
+                .push(Text::new(" ".to_string(), self.hidpi_scale, color));
+        }
+    }
+
     fn process_start_tag(&mut self, tag: Tag) {
         let tag_name = match TagName::try_from(&tag.name) {
             Ok(name) => name,
@@ -369,24 +537,48 @@ impl HtmlInterpreter {
                 self.current_textbox
                     .set_quote_block(self.state.text_options.block_quote);
             }
+            TagName::DescriptionList => self.push_current_textbox(),
+            TagName::DescriptionTerm => {
+                self.push_current_textbox();
+                self.state.text_options.bold += 1;
+            }
+            TagName::DescriptionDetails => {
+                self.push_current_textbox();
+                self.state.global_indent += DEFAULT_MARGIN / 2.;
+                self.current_textbox.indent = self.state.global_indent;
+            }
             TagName::TableHead | TagName::TableBody => {}
             TagName::Table => {
                 self.push_spacer();
                 self.state.element_stack.push(InterpreterElement::table());
             }
-            TagName::TableHeader => {
-                self.state.text_options.bold += 1;
-                let align = html::find_align(&tag.attrs);
-                self.current_textbox.set_align_or_default(align);
+            TagName::TableHeader | TagName::TableDataCell => {
+                if tag_name == TagName::TableHeader {
+                    self.state.text_options.bold += 1;
+                }
+                self.push_current_textbox();
+                let outer_indent = std::mem::take(&mut self.state.global_indent);
+                let style_align = self.push_inline_style(tag_name, &tag.attrs);
+                let align = style_align
+                    .or_else(|| html::find_align(&tag.attrs))
+                    .unwrap_or_default();
+                self.current_textbox.set_align(align);
+                let mut cell = html::Cell::new(align, outer_indent);
+                for attr in attr::Iter::new(&tag.attrs) {
+                    match attr {
+                        Attr::ColSpan(span) => cell.inner.colspan = span,
+                        Attr::RowSpan(span) => cell.inner.rowspan = span,
+                        _ => {}
+                    }
+                }
+                self.state
+                    .element_stack
+                    .push(InterpreterElement::TableCell(cell));
             }
             TagName::TableRow => self
                 .state
                 .element_stack
                 .push(InterpreterElement::table_row()),
-            TagName::TableDataCell => {
-                let align = html::find_align(&tag.attrs);
-                self.current_textbox.set_align_or_default(align);
-            }
             TagName::Anchor => {
                 for attr in attr::Iter::new(&tag.attrs) {
                     match attr {
@@ -397,7 +589,12 @@ impl HtmlInterpreter {
                 }
             }
             TagName::Small => self.state.text_options.small += 1,
-            TagName::Break => self.push_current_textbox(),
+            TagName::Break => {
+                self.push_current_textbox();
+                if let Some(align) = self.find_current_align() {
+                    self.current_textbox.set_align(align);
+                }
+            }
             TagName::Underline => self.state.text_options.underline += 1,
             TagName::Strikethrough => self.state.text_options.strike_through += 1,
             TagName::Picture => {
@@ -435,9 +632,17 @@ impl HtmlInterpreter {
                 }
             }
             TagName::Image => {
-                let apply_attrs = |builder: &mut picture::Builder, attr_iter: attr::Iter<'_>| {
-                    for attr in attr_iter {
+                let font_size = self
+                    .state
+                    .inline_style(|s| s.font_size)
+                    .unwrap_or(self.typography.font_size);
+                let root_font_size = self.typography.font_size;
+                let apply_attrs = |builder: &mut picture::Builder, attrs: &[Attribute]| {
+                    for attr in attr::Iter::new(attrs) {
                         match attr {
+                            // Images on either side get floated with the text wrapping around
+                            Attr::Align(Align::Left) => builder.set_float(Float::Left),
+                            Attr::Align(Align::Right) => builder.set_float(Float::Right),
                             Attr::Align(a) => builder.set_align(a),
                             Attr::Width(w) => builder.set_size(ImageSize::width(w)),
                             Attr::Height(h) => builder.set_size(ImageSize::height(h)),
@@ -445,19 +650,35 @@ impl HtmlInterpreter {
                             _ => {}
                         }
                     }
+                    // Sizes from the `style` take precedence over the attributes
+                    let style_str = html::find_style(attrs).unwrap_or_default();
+                    for style in style::Iter::new(&style_str) {
+                        match style {
+                            Style::Width(w) => {
+                                let w = w.resolve(font_size, root_font_size);
+                                builder.set_size(ImageSize::width(w.round() as u32))
+                            }
+                            Style::Height(h) => {
+                                let h = h.resolve(font_size, root_font_size);
+                                builder.set_size(ImageSize::height(h.round() as u32))
+                            }
+                            Style::Float(float) => builder.set_float(float),
+                            _ => {}
+                        }
+                    }
                 };
 
                 if let Some(InterpreterElement::Picture(builder)) =
                     self.state.element_stack.last_mut()
                 {
                     // Builder already inherited align
-                    apply_attrs(builder, attr::Iter::new(&tag.attrs));
+                    apply_attrs(builder, &tag.attrs);
                 } else {
                     let mut builder = Picture::builder();
                     if let Some(align) = self.align_or_inherit(None) {
                         builder.set_align(align);
                     }
-                    apply_attrs(&mut builder, attr::Iter::new(&tag.attrs));
+                    apply_attrs(&mut builder, &tag.attrs);
                     match builder.try_finish() {
                         Ok(pic) => self.push_image_from_picture(pic),
                         Err(err) => tracing::warn!("Invalid <img>: {err}"),
@@ -474,7 +695,8 @@ impl HtmlInterpreter {
                     self.current_textbox.set_anchor(format!("#{anchorized}"));
                 }
 
-                let align = html::find_align(&tag.attrs);
+                let style_align = self.push_inline_style(tag_name, &tag.attrs);
+                let align = style_align.or_else(|| html::find_align(&tag.attrs));
                 if let Some(align) = self.align_or_inherit(align) {
                     self.current_textbox.set_align(align);
                 }
@@ -486,7 +708,32 @@ impl HtmlInterpreter {
             }
             TagName::EmphasisOrItalic => self.state.text_options.italic += 1,
             TagName::BoldOrStrong => self.state.text_options.bold += 1,
-            TagName::Code => self.state.text_options.code += 1,
+            TagName::Code => {
+                self.state.text_options.code += 1;
+                if self.state.text_options.pre_formatted > 0 {
+                    for attr in attr::Iter::new(&tag.attrs) {
+                        match attr {
+                            Attr::LineNumbers => self.current_textbox.set_line_numbers(true),
+                            Attr::HighlightLines(lines) => {
+                                self.current_textbox.set_highlighted_lines(lines)
+                            }
+                            Attr::Diff => self.current_textbox.set_diff(true),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            TagName::Keyboard => self.state.text_options.keyboard += 1,
+            TagName::Mark => self.state.text_options.mark += 1,
+            TagName::Superscript => self.state.text_options.superscript += 1,
+            TagName::Subscript => self.state.text_options.subscript += 1,
+            TagName::Abbreviation => {
+                let title = attr::Iter::new(&tag.attrs).find_map(|attr| match attr {
+                    Attr::Title(title) => Some(title),
+                    _ => None,
+                });
+                self.state.text_options.abbr_title.push(title);
+            }
             TagName::ListItem => {
                 for attr in attr::Iter::new(&tag.attrs) {
                     self.state.pending_anchor = attr.to_anchor();
@@ -528,10 +775,11 @@ impl HtmlInterpreter {
                     .push(InterpreterElement::ordered_list(start_index));
             }
             TagName::Header(header_type) => {
-                let mut align = html::find_align(&tag.attrs);
-                align = self.align_or_inherit(align);
                 self.push_current_textbox();
                 self.push_spacer();
+                let style_align = self.push_inline_style(tag_name, &tag.attrs);
+                let mut align = style_align.or_else(|| html::find_align(&tag.attrs));
+                align = self.align_or_inherit(align);
                 if let html::HeaderType::H1 = header_type {
                     self.state.text_options.underline += 1;
                 }
@@ -554,22 +802,10 @@ impl HtmlInterpreter {
                 }
                 self.state.text_options.pre_formatted += 1;
                 self.current_textbox.set_code_block(true);
+                self.current_textbox.set_line_numbers(self.line_numbers);
             }
-            // HACK: spans are only supported enough to get syntax highlighting in code
-            // blocks working
             TagName::Span => {
-                let style_str = html::find_style(&tag.attrs).unwrap_or_default();
-                for style in style::Iter::new(&style_str) {
-                    match style {
-                        Style::Color(color) => {
-                            self.state.span.color = native_color(color, &self.surface_format)
-                        }
-                        Style::FontWeight(weight) => self.state.span.weight = weight,
-                        Style::FontStyle(style) => self.state.span.style = style,
-                        Style::TextDecoration(decor) => self.state.span.decor = decor,
-                        _ => {}
-                    }
-                }
+                self.push_inline_style(tag_name, &tag.attrs);
             }
             TagName::Input => {
                 let mut is_checkbox = false;
@@ -591,8 +827,13 @@ impl HtmlInterpreter {
             TagName::Details => {
                 self.push_current_textbox();
                 self.push_spacer();
+                let is_open = attr::Iter::new(&tag.attrs).any(|attr| matches!(attr, Attr::IsOpen));
                 let section = Section::bare(self.hidpi_scale);
-                *section.hidden.borrow_mut() = true;
+                *section.hidden.borrow_mut() = !is_open;
+                if self.state.element_stack.iter().any(|e| e.is_details()) {
+                    self.state.global_indent += DEFAULT_MARGIN / 2.;
+                    self.current_textbox.indent = self.state.global_indent;
+                }
                 self.state
                     .element_stack
                     .push(InterpreterElement::Details(section));
@@ -621,29 +862,45 @@ impl HtmlInterpreter {
             TagName::Strikethrough => self.state.text_options.strike_through -= 1,
             TagName::Small => self.state.text_options.small -= 1,
             TagName::TableHead | TagName::TableBody => {}
-            TagName::TableHeader => {
-                let iter = self.state.element_iter_mut();
-                let table = iter.rev().find_map(|elem| elem.as_mut_table()).unwrap();
-                table.push_header(self.current_textbox.clone());
-                self.current_textbox.texts.clear();
-                self.state.text_options.bold -= 1;
-            }
-            TagName::TableDataCell => {
-                let table_row = self.state.element_stack.last_mut();
-                if let Some(InterpreterElement::TableRow(ref mut row)) = table_row {
-                    row.push(self.current_textbox.clone());
+            TagName::TableHeader | TagName::TableDataCell => {
+                if !self.state.element_stack.iter().any(|e| e.is_table_cell()) {
+                    tracing::warn!("Element stack is muddled");
+                    return;
+                }
+
+                self.push_current_textbox();
+                // Implicitly close anything that was left open within the cell
+                let cell = loop {
+                    if let Some(InterpreterElement::TableCell(cell)) =
+                        self.state.element_stack.pop()
+                    {
+                        break cell;
+                    }
+                };
+                self.state.global_indent = cell.outer_indent;
+                self.current_textbox.indent = self.state.global_indent;
+                self.pop_inline_style(tag_name);
+
+                let is_header = tag_name == TagName::TableHeader;
+                if is_header {
+                    self.state.text_options.bold -= 1;
+                }
+                if let Some(InterpreterElement::TableRow(row)) = self.state.element_stack.last_mut()
+                {
+                    row.is_header &= is_header;
+                    row.cells.push(cell.inner);
                 }
-                self.current_textbox.texts.clear();
             }
             TagName::TableRow => {
                 let table_row = self.state.element_stack.pop();
-                for mut element in self.state.element_iter_mut().rev() {
-                    if let InterpreterElement::Table(table) = &mut element {
-                        if let Some(InterpreterElement::TableRow(row)) = table_row {
-                            if !row.is_empty() {
-                                table.push_row(row);
-                            }
-                            break;
+                let iter = self.state.element_iter_mut();
+                let table = iter.rev().find_map(|elem| elem.as_mut_table());
+                if let (Some(table), Some(InterpreterElement::TableRow(row))) = (table, table_row) {
+                    if !row.cells.is_empty() {
+                        if row.is_header && table.rows.is_empty() {
+                            table.push_header(row.cells);
+                        } else {
+                            table.push_row(row.cells);
                         }
                     }
                 }
@@ -665,9 +922,17 @@ impl HtmlInterpreter {
                     self.push_spacer();
                 }
                 self.state.element_stack.pop();
+                self.pop_inline_style(tag_name);
             }
             TagName::EmphasisOrItalic => self.state.text_options.italic -= 1,
             TagName::BoldOrStrong => self.state.text_options.bold -= 1,
+            TagName::Keyboard => self.state.text_options.keyboard -= 1,
+            TagName::Mark => self.state.text_options.mark -= 1,
+            TagName::Superscript => self.state.text_options.superscript -= 1,
+            TagName::Subscript => self.state.text_options.subscript -= 1,
+            TagName::Abbreviation => {
+                self.state.text_options.abbr_title.pop();
+            }
             TagName::Header(header_type) => {
                 if header_type == HeaderType::H1 {
                     self.state.text_options.underline -= 1;
@@ -680,9 +945,11 @@ impl HtmlInterpreter {
                     .collect();
                 let anchorized = self.state.anchorizer.anchorize(anchor_name);
                 self.current_textbox.set_anchor(format!("#{anchorized}"));
+                self.current_textbox.set_heading_level(header_type.level());
                 self.push_current_textbox();
                 self.push_spacer();
                 self.state.element_stack.pop();
+                self.pop_inline_style(tag_name);
             }
             TagName::ListItem => {
                 // Pop pending anchor if nothing consumed it
@@ -708,6 +975,9 @@ impl HtmlInterpreter {
                 self.push_spacer();
                 self.state.text_options.pre_formatted -= 1;
                 self.current_textbox.set_code_block(false);
+                self.current_textbox.set_line_numbers(false);
+                self.current_textbox.set_highlighted_lines(Vec::new());
+                self.current_textbox.set_diff(false);
             }
             TagName::BlockQuote => {
                 self.push_current_textbox();
@@ -718,13 +988,27 @@ impl HtmlInterpreter {
                     self.push_spacer();
                 }
             }
-            TagName::Span => {
-                let color = self.native_color(self.theme.code_color);
-                self.state.span = Span::with_color(color);
+            TagName::DescriptionList => {
+                self.push_current_textbox();
+                self.push_spacer();
+            }
+            TagName::DescriptionTerm => {
+                self.push_current_textbox();
+                self.state.text_options.bold -= 1;
             }
+            TagName::DescriptionDetails => {
+                self.push_current_textbox();
+                self.state.global_indent -= DEFAULT_MARGIN / 2.;
+                self.current_textbox.indent = self.state.global_indent;
+            }
+            TagName::Span => self.pop_inline_style(tag_name),
             TagName::Details => {
                 self.push_current_textbox();
                 if let Some(InterpreterElement::Details(section)) = self.state.element_stack.pop() {
+                    if self.state.element_stack.iter().any(|e| e.is_details()) {
+                        self.state.global_indent -= DEFAULT_MARGIN / 2.;
+                        self.current_textbox.indent = self.state.global_indent;
+                    }
                     self.push_element(section);
                 }
                 self.push_spacer();
@@ -769,6 +1053,9 @@ impl HtmlInterpreter {
     }
 
     fn process_character_tokens(&mut self, mut str: String) {
+        if self.current_textbox.is_code_block {
+            self.current_textbox.code.push_str(&str);
+        }
         let text_native_color = self.native_color(self.theme.text_color);
         if str == "\n" {
             if self.state.text_options.pre_formatted >= 1 {
@@ -778,37 +1065,9 @@ impl HtmlInterpreter {
                     text_native_color,
                 ));
             }
-            if let Some(last_text) = self.current_textbox.texts.last() {
-                if let Some(last_char) = last_text.text.chars().last() {
-                    if !last_char.is_whitespace() {
-                        self.current_textbox.texts.push(Text::new(
-                            " ".to_string(),
-                            self.hidpi_scale,
-                            text_native_color,
-                        ));
-                    }
-                }
-            }
-            if let Some((row, newline_counter)) = self.state.inline_images.take() {
-                if newline_counter == 0 {
-                    self.push_element(row);
-                    self.push_spacer();
-                } else {
-                    self.state.inline_images = Some((row, newline_counter - 1));
-                }
-            }
+            self.push_separating_space(text_native_color);
         } else if str.trim().is_empty() && self.state.text_options.pre_formatted == 0 {
-            if let Some(last_text) = self.current_textbox.texts.last() {
-                if let Some(last_char) = last_text.text.chars().last() {
-                    if !last_char.is_whitespace() {
-                        self.current_textbox.texts.push(Text::new(
-                            " ".to_string(),
-                            self.hidpi_scale,
-                            text_native_color,
-                        ));
-                    }
-                }
-            }
+            self.push_separating_space(text_native_color);
         } else {
             if self.current_textbox.texts.is_empty() && self.state.text_options.pre_formatted == 0 {
                 #[allow(
@@ -820,39 +1079,66 @@ impl HtmlInterpreter {
                 }
             }
 
+            let color = self.state.inline_style(|s| s.color);
             let mut text = Text::new(str, self.hidpi_scale, text_native_color);
-            if let Some(prefix) = self.state.pending_list_prefix.take() {
-                if self.current_textbox.texts.is_empty() {
-                    self.current_textbox.texts.push(
-                        Text::new(prefix, self.hidpi_scale, text_native_color).make_bold(true),
-                    );
-                }
+            if let Some(color) = color {
+                text = text.with_color(color);
             }
+            self.push_pending_list_prefix(text_native_color);
             if self.state.text_options.block_quote >= 1 {
                 self.current_textbox
                     .set_quote_block(self.state.text_options.block_quote);
             }
+            if let Some(dir) = self.state.inline_style(|s| s.dir) {
+                self.current_textbox.set_direction(dir);
+            }
             if self.state.text_options.code >= 1 {
+                let code_color = color.unwrap_or_else(|| self.native_color(self.theme.code_color));
                 text = text
-                    .with_color(self.state.span.color)
+                    .with_color(code_color)
                     .with_family(FamilyOwned::Monospace);
-                if self.state.span.weight == FontWeight::Bold {
-                    text = text.make_bold(true);
-                }
-                if self.state.span.style == FontStyle::Italic {
-                    text = text.make_italic(true);
-                }
-                if self.state.span.decor == TextDecoration::Underline {
-                    text = text.make_underlined(true);
+                if self.state.text_options.pre_formatted == 0 {
+                    if let Some(background) = self.theme.code_background_color {
+                        text = text.with_highlight(self.native_color(background));
+                    }
                 }
             }
+            if self.state.inline_style(|s| s.weight) == Some(FontWeight::Bold) {
+                text = text.make_bold(true);
+            }
+            if self.state.inline_style(|s| s.style) == Some(FontStyle::Italic) {
+                text = text.make_italic(true);
+            }
+            match self.state.inline_style(|s| s.decor) {
+                Some(TextDecoration::Underline) => text = text.make_underlined(true),
+                Some(TextDecoration::LineThrough) => text = text.make_striked(true),
+                Some(TextDecoration::Normal) | None => {}
+            }
+            if let Some(background) = self.state.inline_style(|s| s.background) {
+                text = text.with_highlight(background);
+            }
+            if self.state.text_options.superscript >= 1 {
+                text = text.with_script(Script::Super);
+            } else if self.state.text_options.subscript >= 1 {
+                text = text.with_script(Script::Sub);
+            }
             for elem in self.state.element_stack.iter().rev() {
                 if let InterpreterElement::Header(header) = elem {
-                    self.current_textbox.font_size *= header.ty.size_multiplier();
+                    self.current_textbox.font_size =
+                        self.typography.heading_size(header.ty.level());
                     text = text.make_bold(true);
+                    let heading_color =
+                        self.theme.heading_colors[usize::from(header.ty.level() - 1)];
+                    if let (None, Some(heading_color)) = (text.color, heading_color) {
+                        text = text.with_color(self.native_color(heading_color));
+                    }
                     break;
                 }
             }
+            // Explicit sizes win over the header multiplier
+            if let Some(font_size) = self.state.inline_style(|s| s.font_size) {
+                self.current_textbox.font_size = font_size;
+            }
             if let Some(link) = self.state.text_options.link.last() {
                 text = text.with_link((*link).clone());
                 text = text.with_color(self.native_color(self.theme.link_color));
@@ -870,13 +1156,30 @@ impl HtmlInterpreter {
                 text = text.make_striked(true);
             }
             if self.state.text_options.small >= 1 {
-                self.current_textbox.font_size = 12.;
+                self.current_textbox.font_size = self.typography.font_size * 0.75;
+            }
+            if self.state.text_options.keyboard >= 1 {
+                text = text.with_family(FamilyOwned::Monospace).make_key(true);
+            }
+            if self.state.text_options.mark >= 1 {
+                text = text.with_highlight(self.native_color(self.theme.mark_color));
+            }
+            if let Some(Some(title)) = self.state.text_options.abbr_title.last() {
+                text = text.with_title(title.clone());
             }
             self.current_textbox.texts.push(text);
         }
     }
 }
 
+fn new_textbox(typography: &Typography, hidpi_scale: f32) -> TextBox {
+    TextBox {
+        font_size: typography.font_size,
+        line_height: typography.line_height,
+        ..TextBox::new(Vec::new(), hidpi_scale)
+    }
+}
+
 impl TokenSink for HtmlInterpreter {
     type Handle = ();
 
@@ -7,15 +7,15 @@ use std::sync::{
 use std::time::{Duration, Instant};
 use std::{env, thread};
 
-use super::{HtmlInterpreter, ImageCallback, WindowInteractor};
+use super::{HtmlInterpreter, ImageCallback, InterpreterMsg, WindowInteractor};
 use crate::color::{Theme, ThemeDefaults};
-use crate::image::{Image, ImageData};
-use crate::opts::ResolvedTheme;
-use crate::positioner::Spacer;
+use crate::image::{Image, ImageData, ImageSize};
+use crate::opts::{ResolvedTheme, SyntaxOptions, Typography};
+use crate::positioner::{Positioned, Spacer};
 use crate::test_utils::image::{Sample, SamplePng};
 use crate::test_utils::{log, server};
-use crate::text::{Text, TextBox};
-use crate::utils::Align;
+use crate::text::{DiffLine, Text, TextBox};
+use crate::utils::{Align, Direction, Float};
 use crate::{Element, ImageCache};
 
 use base64::prelude::*;
@@ -78,6 +78,9 @@ struct InterpreterOpts {
     theme: Theme,
     fail_after: Duration,
     color_scheme: Option<ResolvedTheme>,
+    line_numbers: bool,
+    syntax_opts: SyntaxOptions,
+    typography: Typography,
 }
 
 impl Default for InterpreterOpts {
@@ -86,6 +89,9 @@ impl Default for InterpreterOpts {
             theme: Theme::light_default(),
             fail_after: Duration::from_secs(8),
             color_scheme: None,
+            line_numbers: false,
+            syntax_opts: SyntaxOptions::default(),
+            typography: Typography::default(),
         }
     }
 }
@@ -110,11 +116,29 @@ impl InterpreterOpts {
         self.color_scheme = Some(color_scheme);
     }
 
+    fn line_numbers(mut self, line_numbers: bool) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    fn syntax_opts(mut self, syntax_opts: SyntaxOptions) -> Self {
+        self.syntax_opts = syntax_opts;
+        self
+    }
+
+    fn typography(mut self, typography: Typography) -> Self {
+        self.typography = typography;
+        self
+    }
+
     fn finish(self, counter: AtomicCounter) -> (HtmlInterpreter, Arc<Mutex<VecDeque<Element>>>) {
         let Self {
             theme,
             fail_after: _,
             color_scheme,
+            line_numbers,
+            syntax_opts,
+            typography,
         } = self;
         let element_queue = Arc::default();
         let surface_format = TextureFormat::Bgra8UnormSrgb;
@@ -131,6 +155,10 @@ impl InterpreterOpts {
             image_cache,
             window,
             color_scheme,
+            None,
+            line_numbers,
+            syntax_opts,
+            typography,
         );
 
         (interpreter, element_queue)
@@ -177,7 +205,9 @@ fn interpret_md_with_opts(text: &str, opts: InterpreterOpts) -> VecDeque<Element
     let counter = AtomicCounter::new();
     let (interpreter, element_queue) = opts.finish(counter.clone());
     let (md_tx, md_rx) = mpsc::channel();
-    md_tx.send(text.to_owned()).unwrap();
+    md_tx
+        .send(InterpreterMsg::Markdown(text.to_owned()))
+        .unwrap();
     let interpreter_handle = std::thread::spawn(|| {
         interpreter.interpret_md(md_rx);
     });
@@ -218,6 +248,7 @@ macro_rules! snapshot_interpreted_elements {
                 let htmlified = $crate::utils::markdown_to_html(
                     text,
                     opts.theme.code_highlighter.clone(),
+                    &opts.syntax_opts,
                 );
                 let description = format!(" --- md\n\n{text}\n\n --- html\n\n{htmlified}");
 
@@ -297,6 +328,85 @@ collapsed text
 </details>
 ";
 
+const NESTED_SECTIONS: &str = "\
+<details open>
+<summary>outer</summary>
+
+outer text
+
+<details>
+<summary>inner</summary>
+
+inner text
+</details>
+</details>
+";
+
+const DESCRIPTION_LIST: &str = "\
+Term
+
+: Definition of the term
+
+<dl>
+<dt>HTML term</dt>
+<dd>Its definition</dd>
+</dl>
+";
+
+const INLINE_STYLING: &str = "\
+x<sup>2</sup> H<sub>2</sub>O <mark>marked</mark> <kbd>Ctrl</kbd>
+<abbr title=\"HyperText Markup Language\">HTML</abbr>
+";
+
+const INLINE_CSS: &str = r#"
+<p style="text-align: center; color: rgb(255, 0, 0) !important">centered red</p>
+
+<div style="margin-left:2em;font-size:125%">
+<span style="COLOR: #0f0; font-weight: 700; text-decoration: line-through">styled span</span>
+<span style="background-color: hsl(60, 100%, 50%); font-style: oblique">highlighted</span>
+</div>
+
+<h2 style="font-size: 20px; text-align: right">Heading</h2>
+
+<p>normal <span style="font-size: 40px">sized span</span></p>
+
+<div style="color: red"><span style="color: blue">unclosed span</div>
+<p>unstyled</p>
+
+<table><tr><td style="text-align: right; color: navy">cell</td></tr></table>
+"#;
+
+const NESTED_IN_TABLE_CELL: &str = r#"
+<table><tr><td>
+<table><tr><th>inner head</th></tr><tr><td>inner cell</td></tr></table>
+<details><summary>folded</summary>
+
+hidden text
+</details>
+</td></tr></table>
+"#;
+
+const TABLE_SPANS: &str = r#"
+<table>
+<tr><th colspan="2">Grouped</th><th rowspan="2">Tall</th></tr>
+<tr><th>a</th><th>b</th></tr>
+<tr><td>1</td><td colspan="0">2</td><td rowspan="nope">3</td></tr>
+</table>
+"#;
+
+const RICH_TABLE_CELLS: &str = r#"
+| Option | Notes |
+| :----: | ----- |
+| `--theme` | first line<br>second line |
+
+<table><tr><td>
+<ul>
+<li>one</li>
+<li>two</li>
+</ul>
+</td></tr></table>
+"#;
+
 snapshot_interpreted_elements!(
     // (footnotes_list_prefix, FOOTNOTES_LIST_PREFIX),
     (checklist_has_no_text_prefix, CHECKLIST_HAS_NO_TEXT_PREFIX),
@@ -306,6 +416,13 @@ snapshot_interpreted_elements!(
     (aligned_table, ALIGNED_TABLE),
     (header_inherit_align, HEADER_INHERIT_ALIGN),
     (collapsed_section, COLLAPSED_SECTION),
+    (nested_sections, NESTED_SECTIONS),
+    (description_list, DESCRIPTION_LIST),
+    (inline_styling, INLINE_STYLING),
+    (inline_css, INLINE_CSS),
+    (rich_table_cells, RICH_TABLE_CELLS),
+    (nested_in_table_cell, NESTED_IN_TABLE_CELL),
+    (table_spans, TABLE_SPANS),
 );
 
 fn elem_as_text_box(elem: &Element) -> Option<&TextBox> {
@@ -806,14 +923,13 @@ fn toml_gets_highlighted() {
 fn find_image(elements: &VecDeque<Element>) -> Option<&Image> {
     elements.iter().find_map(|element| match element {
         crate::Element::Image(image) => Some(image),
+        crate::Element::TextBox(text_box) => text_box.images.first().map(|image| &image.inner),
         _ => None,
     })
 }
 
-#[test]
-fn centered_image_with_size_align_and_link() {
-    log::init();
-
+// Serves the bun logo, returning the server that needs to be kept alive along with the logo's url
+fn serve_logo() -> (server::MiniServerHandle, String) {
     let logo: Sample = SamplePng::Bun.into();
     let logo_path = "/bun_logo.png";
     let files = vec![server::File::new(
@@ -821,8 +937,25 @@ fn centered_image_with_size_align_and_link() {
         logo.content_type(),
         &logo.pre_decode(),
     )];
-    let (_server, server_url) = server::mock_file_server(files);
-    let logo_url = server_url + logo_path;
+    let (server, server_url) = server::mock_file_server(files);
+    (server, server_url + logo_path)
+}
+
+fn find_code_block(elements: VecDeque<Element>) -> TextBox {
+    elements
+        .into_iter()
+        .find_map(|element| match element {
+            Element::TextBox(text_box) if text_box.is_code_block => Some(text_box),
+            _ => None,
+        })
+        .expect("Should have a code block")
+}
+
+#[test]
+fn centered_image_with_size_align_and_link() {
+    log::init();
+
+    let (_server, logo_url) = serve_logo();
 
     let text = format!(
         r#"
@@ -841,6 +974,7 @@ fn centered_image_with_size_align_and_link() {
                     lz4_blob: { len: 21244, data: [4, 34, 77, ..] },
                     scale: true,
                     dimensions: (396, 347),
+                    ..
                 },
             ),
             poisoned: false,
@@ -854,6 +988,103 @@ fn centered_image_with_size_align_and_link() {
     "###);
 }
 
+#[test]
+fn floated_images() {
+    log::init();
+
+    let (_server, logo_url) = serve_logo();
+
+    let text = format!(
+        r#"
+<img align="right" src="{logo_url}" height=64>
+<p>Wraps <img style="float: left" src="{logo_url}"> around</p>
+<p align="right"><img src="{logo_url}"></p>"#,
+    );
+    let elems = interpret_md(&text);
+    let images: Vec<_> = elems
+        .iter()
+        .filter_map(|elem| match elem {
+            Element::Image(image) => Some((image.float, image.is_aligned)),
+            _ => None,
+        })
+        .collect();
+    // Only the alignment set on an image itself floats it
+    assert_eq!(
+        images,
+        [
+            (Some(Float::Right), Some(Align::Left)),
+            (Some(Float::Left), Some(Align::Left)),
+            (None, Some(Align::Right)),
+        ]
+    );
+    // The paragraph's text stays together with the float going ahead of it
+    let text_boxes = elems
+        .iter()
+        .filter(|elem| matches!(elem, Element::TextBox(_)))
+        .count();
+    assert_eq!(text_boxes, 1);
+}
+
+#[test]
+fn images_stay_in_table_cells() {
+    log::init();
+
+    let (_server, logo_url) = serve_logo();
+
+    let text = format!("| Badges |\n| --- |\n| ![a]({logo_url}) ![b]({logo_url}) |");
+    let elems = interpret_md(&text);
+    assert!(find_image(&elems).is_none(), "Image escaped the table");
+    let table = elems
+        .iter()
+        .find_map(|elem| match elem {
+            Element::Table(table) => Some(table),
+            _ => None,
+        })
+        .unwrap();
+    let [cell] = table.rows[0].as_slice() else {
+        panic!("Expected a single cell: {table:#?}");
+    };
+    let [Positioned {
+        inner: Element::TextBox(text_box),
+        ..
+    }] = cell.elements.as_slice()
+    else {
+        panic!("Expected a single line of images: {cell:#?}");
+    };
+    assert_eq!(text_box.images.len(), 2);
+}
+
+#[test]
+fn images_flow_with_text() {
+    log::init();
+
+    let (_server, logo_url) = serve_logo();
+
+    let text = format!("Built with ![bun]({logo_url}) and sized to fit");
+    let elems: Vec<_> = interpret_md(&text).into();
+    let [Element::TextBox(text_box), Element::Spacer(_)] = elems.as_slice() else {
+        panic!("Expected the image within a single text box: {elems:#?}");
+    };
+    let texts: Vec<_> = text_box
+        .texts
+        .iter()
+        .map(|text| (text.text.as_str(), text.image))
+        .collect();
+    assert_eq!(
+        texts,
+        [
+            ("Built with ", None),
+            ("", Some(0)),
+            (" and sized to fit", None)
+        ]
+    );
+    // Images without a size of their own match the font
+    let [image] = text_box.images.as_slice() else {
+        panic!("Expected a single image: {text_box:#?}");
+    };
+    assert_eq!(image.inner.size, Some(ImageSize::height(16)));
+}
+
 // TODO: change this to test against the image cache so that we can inspect the error?
 #[test]
 fn image_loading_fails_gracefully() {
@@ -991,3 +1222,276 @@ fn custom_user_agent() {
     };
     insta::assert_snapshot!(user_agent, @"inlyne 0.5.0-dev https://github.com/Inlyne-Project/inlyne");
 }
+
+#[test]
+fn code_fence_options() {
+    log::init();
+
+    let code_box = |text: &str, opts| find_code_block(interpret_md_with_opts(text, opts));
+
+    let text_box = code_box(
+        "```rust {linenos, hl=3-5,8}\nfn main() {}\n```",
+        InterpreterOpts::new(),
+    );
+    assert!(text_box.line_numbers);
+    assert_eq!(text_box.highlighted_lines, [3..=5, 8..=8]);
+    assert!(text_box.is_highlighted(4));
+    assert!(!text_box.is_highlighted(6));
+
+    // Alongside the comma separated options
+    let text_box = code_box(
+        "```rust,ignore,hl=2\nfn main() {}\n```",
+        InterpreterOpts::new(),
+    );
+    assert!(!text_box.line_numbers);
+    assert_eq!(text_box.highlighted_lines, [2..=2]);
+
+    let text_box = code_box("```\nplain\n```", InterpreterOpts::new());
+    assert!(!text_box.line_numbers);
+    let text_box = code_box("```\nplain\n```", InterpreterOpts::new().line_numbers(true));
+    assert!(text_box.line_numbers);
+}
+
+#[test]
+fn code_block_source() {
+    log::init();
+
+    let code_box = |text: &str| find_code_block(interpret_md(text));
+
+    // Highlighted code gets split into many texts, but copies exactly as it was written
+    let source = "fn main() {\n\tlet s = \"<&>\";  \n\n    println!(\"{s}\");\n}\n";
+    let text_box = code_box(&format!("```rust\n{source}```"));
+    assert!(text_box.texts.len() > 1);
+    assert_eq!(text_box.code, source);
+
+    let source = "plain <b>text</b> & more\n";
+    assert_eq!(code_box(&format!("```\n{source}```")).code, source);
+}
+
+#[test]
+fn diff_code_blocks() {
+    log::init();
+
+    let code_box = |text: &str| find_code_block(interpret_md(text));
+
+    let diff = "\
+--- a/main.rs
++++ b/main.rs
+@@ -1,2 +1,2 @@
+ fn main() {
+-    println!(\"old\");
++    println!(\"new\");
+";
+    let text_box = code_box(&format!("```diff\n{diff}```"));
+    assert!(text_box.is_diff);
+    assert_eq!(text_box.code, diff);
+    let diff_lines: Vec<_> = text_box.diff_lines().collect();
+    assert_eq!(
+        diff_lines,
+        [
+            None,
+            None,
+            Some(DiffLine::Hunk),
+            None,
+            Some(DiffLine::Removed),
+            Some(DiffLine::Added),
+        ]
+    );
+
+    assert!(code_box("```patch\n+added\n```").is_diff);
+    // Keeps highlighting the code as the language after `diff-`
+    let text_box = code_box("```diff-rust\n+fn main() {}\n```");
+    assert!(text_box.is_diff);
+    let mut colors = text_box.texts.iter().filter_map(|text| text.color);
+    let first = colors.next();
+    assert!(colors.any(|color| Some(color) != first));
+    assert!(!code_box("```rust\n+fn main() {}\n```").is_diff);
+}
+
+#[test]
+fn syntax_aliases() {
+    log::init();
+
+    let is_highlighted = |text: &str, syntax_opts: SyntaxOptions| {
+        find_code_block(interpret_md_with_opts(
+            text,
+            InterpreterOpts::new().syntax_opts(syntax_opts),
+        ))
+        .texts
+        .iter()
+        .filter_map(|text| text.color)
+        .collect::<Vec<_>>()
+        .windows(2)
+        .any(|colors| colors[0] != colors[1])
+    };
+
+    let jsonc = "```jsonc\n{ \"key\": true }\n```";
+    assert!(!is_highlighted(jsonc, SyntaxOptions::default()));
+    let aliases = [("jsonc".to_owned(), "json".to_owned())].into();
+    let syntax_opts = SyntaxOptions {
+        aliases,
+        default: None,
+    };
+    assert!(is_highlighted(jsonc, syntax_opts));
+
+    // Recognized languages get swapped out too
+    let rust = "```rust\nfn main() {}\n```";
+    assert!(is_highlighted(rust, SyntaxOptions::default()));
+    let syntax_opts = SyntaxOptions {
+        aliases: [("rust".to_owned(), "txt".to_owned())].into(),
+        default: None,
+    };
+    assert!(!is_highlighted(rust, syntax_opts));
+
+    let no_lang = "```\nfn main() {}\n```";
+    assert!(!is_highlighted(no_lang, SyntaxOptions::default()));
+    let syntax_opts = SyntaxOptions {
+        default: Some("rust".to_owned()),
+        ..Default::default()
+    };
+    assert!(is_highlighted(no_lang, syntax_opts));
+}
+
+#[test]
+fn theme_element_colors() {
+    log::init();
+
+    let native_color = |color| crate::color::native_color(color, &TextureFormat::Bgra8UnormSrgb);
+    let theme = Theme {
+        heading_colors: [None, Some(0xFF0000), None, None, None, None],
+        code_background_color: Some(0x00FF00),
+        ..Theme::light_default()
+    };
+    let elements = interpret_md_with_opts(
+        "# One\n\n## Two `code`\n\n`inline`\n\n```\nblock\n```",
+        InterpreterOpts::new().theme(theme.clone()),
+    );
+    let text_boxes: Vec<_> = elements
+        .into_iter()
+        .filter_map(|element| match element {
+            Element::TextBox(text_box) => Some(text_box),
+            _ => None,
+        })
+        .collect();
+    let [h1, h2, inline, block] = text_boxes.as_slice() else {
+        panic!("Unexpected elements: {text_boxes:#?}");
+    };
+
+    assert_eq!(h1.texts[0].color, None);
+    assert_eq!(h2.texts[0].color, Some(native_color(0xFF0000)));
+    // Inline code keeps its own color inside of headings
+    assert_eq!(h2.texts[1].color, Some(native_color(theme.code_color)));
+    assert_eq!(h2.texts[1].highlight, Some(native_color(0x00FF00)));
+    assert_eq!(inline.texts[0].highlight, Some(native_color(0x00FF00)));
+    assert!(block.texts.iter().all(|text| text.highlight.is_none()));
+}
+
+#[test]
+fn typography() {
+    log::init();
+
+    let typography = Typography {
+        font_size: 20.,
+        line_height: 1.5,
+        heading_scale: [3., 2., 1., 1., 1., 1.],
+        paragraph_spacing: 12.,
+        ..Default::default()
+    };
+    let elements = interpret_md_with_opts(
+        "# One `code`\n\nText <small>small</small>",
+        InterpreterOpts::new().typography(typography.clone()),
+    );
+    let elements: Vec<_> = elements.into_iter().collect();
+    let [_, Element::TextBox(heading), Element::Spacer(spacer), Element::TextBox(text), ..] =
+        elements.as_slice()
+    else {
+        panic!("Unexpected elements: {elements:#?}");
+    };
+
+    // Every piece of the heading stays at the same size
+    assert_eq!(heading.font_size, 60.);
+    assert_eq!(heading.line_height, 1.5);
+    assert_eq!(spacer.space, 12.);
+    assert_eq!(text.font_size, 15.);
+    assert_eq!(text.line_height, 1.5);
+
+    // CSS sizes are relative to the configured font size instead of a fixed 16px
+    let elements = interpret_md_with_opts(
+        "<p style=\"font-size: medium; margin-left: 1rem\">medium</p>\n\n\
+        <p style=\"margin-left: 2em; font-size: 2rem\">doubled</p>",
+        InterpreterOpts::new().typography(typography),
+    );
+    let text_boxes: Vec<_> = elements
+        .into_iter()
+        .filter_map(|element| match element {
+            Element::TextBox(text_box) => Some(text_box),
+            _ => None,
+        })
+        .collect();
+    let [medium, doubled] = text_boxes.as_slice() else {
+        panic!("Unexpected text boxes: {text_boxes:#?}");
+    };
+    assert_eq!((medium.font_size, medium.indent), (20., 20.));
+    assert_eq!((doubled.font_size, doubled.indent), (40., 80.));
+}
+
+#[test]
+fn text_direction() {
+    log::init();
+
+    let text = "\
+مرحبا بالعالم
+
+- عنصر
+
+Plain text
+
+<div dir=\"rtl\">Starts in English</div>
+
+<p dir=\"ltr\">שלום</p>
+
+```
+// تعليق
+```";
+    let elements = interpret_md(text);
+    let text_boxes: Vec<_> = elements
+        .iter()
+        .filter_map(|element| match element {
+            Element::TextBox(text_box) => Some(text_box),
+            _ => None,
+        })
+        .collect();
+    let [arabic, list_item, plain, forced_rtl, forced_ltr, code] = text_boxes.as_slice() else {
+        panic!("Unexpected elements: {elements:#?}");
+    };
+
+    assert!(arabic.is_rtl());
+    // The list prefix doesn't have a direction of its own
+    assert!(list_item.is_rtl());
+    assert!(!plain.is_rtl());
+    assert_eq!(forced_rtl.direction, Direction::Rtl);
+    assert!(forced_rtl.is_rtl());
+    assert_eq!(forced_ltr.direction, Direction::Ltr);
+    assert!(!forced_ltr.is_rtl());
+    assert!(!code.is_rtl());
+}
+
+#[test]
+fn justify() {
+    log::init();
+
+    let text = r#"
+<p align="justify">From the attribute</p>
+
+<p style="text-align: justify">From the style</p>
+"#;
+    let elements = interpret_md(text);
+    let aligns: Vec<_> = elements
+        .iter()
+        .filter_map(|element| match element {
+            Element::TextBox(text_box) => Some(text_box.align),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(aligns, [Align::Justify, Align::Justify]);
+}
@@ -5,7 +5,11 @@ pub enum Action {
     Scroll(VertDirection),
     Page(VertDirection),
     Zoom(Zoom),
+    ExpandAll,
+    CollapseAll,
     Copy,
+    CopyCodeBlock,
+    ToggleTheme,
     Quit,
 }
 
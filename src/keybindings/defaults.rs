@@ -20,6 +20,14 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
             Action::Copy,
             KeyCombo(vec![ModifiedKey(Key::from(VirtKey::C), ctrl_or_command)]),
         ),
+        // Copy the code block under the cursor: Ctrl+Shift+C / Command+Shift+C
+        (
+            Action::CopyCodeBlock,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::C),
+                ctrl_or_command | ModifiersState::SHIFT,
+            )]),
+        ),
         // Zoom in: Ctrl+= / Command+=
         (
             Action::Zoom(Zoom::In),
@@ -36,6 +44,11 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
                 ctrl_or_command,
             )]),
         ),
+        // Switch between the light and dark themes: Ctrl+T / Command+T
+        (
+            Action::ToggleTheme,
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::T), ctrl_or_command)]),
+        ),
         // Navigate to next file: Alt+Right
         (
             Action::History(HistDirection::Next),
@@ -129,6 +142,22 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
                 ModifiedKey(Key::from(VirtKey::Q), ModifiersState::SHIFT),
             ]),
         ),
+        // Expand all sections: zR
+        (
+            Action::ExpandAll,
+            KeyCombo(vec![
+                ModifiedKey::from(VirtKey::Z),
+                ModifiedKey(Key::from(VirtKey::R), ModifiersState::SHIFT),
+            ]),
+        ),
+        // Collapse all sections: zM
+        (
+            Action::CollapseAll,
+            KeyCombo(vec![
+                ModifiedKey::from(VirtKey::Z),
+                ModifiedKey(Key::from(VirtKey::M), ModifiersState::SHIFT),
+            ]),
+        ),
         // Navigate to next file: bn
         (
             Action::History(HistDirection::Next),
@@ -26,7 +26,11 @@ impl<'de> Deserialize<'de> for Action {
             ZoomIn,
             ZoomOut,
             ZoomReset,
+            ExpandAll,
+            CollapseAll,
             Copy,
+            CopyCodeBlock,
+            ToggleTheme,
             Quit,
         }
 
@@ -42,7 +46,11 @@ impl<'de> Deserialize<'de> for Action {
             FlatAction::ZoomIn => Action::Zoom(Zoom::In),
             FlatAction::ZoomOut => Action::Zoom(Zoom::Out),
             FlatAction::ZoomReset => Action::Zoom(Zoom::Reset),
+            FlatAction::ExpandAll => Action::ExpandAll,
+            FlatAction::CollapseAll => Action::CollapseAll,
             FlatAction::Copy => Action::Copy,
+            FlatAction::CopyCodeBlock => Action::CopyCodeBlock,
+            FlatAction::ToggleTheme => Action::ToggleTheme,
             FlatAction::Quit => Action::Quit,
         };
 
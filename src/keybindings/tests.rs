@@ -54,8 +54,10 @@ const DEFAULTS_TEMPLATE: &str = r#"
 base = [
     # Regular
     ["Copy", { key = "c", mod = "CTRL_OR_CMD" }],
+    ["CopyCodeBlock", { key = "c", mod = ["CTRL_OR_CMD", "Shift"] }],
     ["ZoomIn", { key = "=", mod = "CTRL_OR_CMD" }],
     ["ZoomOut", { key = "-", mod = "CTRL_OR_CMD" }],
+    ["ToggleTheme", { key = "t", mod = "CTRL_OR_CMD" }],
     ["HistoryNext", { key = "Right", mod = "Alt" }],
     ["HistoryPrevious", { key = "Left", mod = "Alt" }],
     ["ScrollUp", "Up"],
@@ -74,6 +76,8 @@ base = [
     ["Quit", "q"],
     ["Quit", ["Z", "Z"]],
     ["Quit", ["Z", "Q"]],
+    ["ExpandAll", ["z", "R"]],
+    ["CollapseAll", ["z", "M"]],
     ["HistoryNext", ["b", "n"]],
     ["HistoryPrevious", ["b", "p"]],
 ]
@@ -24,13 +24,14 @@ mod panic_hook;
 pub mod positioner;
 pub mod renderer;
 pub mod selection;
+mod system_theme;
 pub mod table;
 #[cfg(test)]
 pub mod test_utils;
 pub mod text;
 pub mod utils;
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::Debug;
 use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
@@ -43,12 +44,12 @@ use std::time::Instant;
 
 use file_watcher::Watcher;
 use image::{Image, ImageData};
-use interpreter::HtmlInterpreter;
+use interpreter::{HtmlInterpreter, InterpreterMsg};
 use keybindings::action::{Action, HistDirection, VertDirection, Zoom};
 use keybindings::{Key, KeyCombos, ModifiedKey};
-use metrics::{histogram, HistTag};
-use opts::{Cli, Config, Opts};
-use positioner::{Positioned, Row, Section, Spacer, DEFAULT_MARGIN, DEFAULT_PADDING};
+use metrics::{histogram, GaugeTag, HistTag};
+use opts::{Cli, Config, Opts, ResolvedTheme, Typography};
+use positioner::{Positioned, Section, Spacer, DEFAULT_MARGIN};
 use raw_window_handle::HasRawDisplayHandle;
 use renderer::Renderer;
 use table::Table;
@@ -61,7 +62,7 @@ use crate::opts::{Commands, ConfigCmd, MetricsExporter};
 use crate::selection::Selection;
 use anyhow::Context;
 use clap::Parser;
-use taffy::Taffy;
+use glyphon::{fontdb, Family};
 use winit::event::{
     ElementState, Event, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, WindowEvent,
 };
@@ -74,6 +75,7 @@ pub enum InlyneEvent {
     FileChange { contents: String },
     Reposition,
     PositionQueue,
+    SystemThemeChange(ResolvedTheme),
 }
 
 impl Debug for InlyneEvent {
@@ -94,7 +96,6 @@ pub enum Element {
     Spacer(Spacer),
     Image(Image),
     Table(Table),
-    Row(Row),
     Section(Section),
 }
 
@@ -104,12 +105,6 @@ impl From<Section> for Element {
     }
 }
 
-impl From<Row> for Element {
-    fn from(row: Row) -> Self {
-        Element::Row(row)
-    }
-}
-
 impl From<Image> for Element {
     fn from(image: Image) -> Self {
         Element::Image(image)
@@ -145,12 +140,17 @@ pub struct Inlyne {
     elements: Vec<Positioned<Element>>,
     lines_to_scroll: f32,
     image_cache: ImageCache,
-    interpreter_sender: mpsc::Sender<String>,
+    interpreter_sender: mpsc::Sender<InterpreterMsg>,
     interpreter_should_queue: Arc<AtomicBool>,
     keycombos: KeyCombos,
     need_repositioning: bool,
     watcher: Watcher,
     selection: Selection,
+    folded_headings: HashSet<String>,
+    // The file that `folded_headings` belongs to
+    folded_path: PathBuf,
+    // Where to scroll back to once the current document is re-interpreted
+    restore_scroll_y: Option<f32>,
 }
 
 impl Inlyne {
@@ -179,13 +179,31 @@ impl Inlyne {
             Arc::new(wb.build(&event_loop).unwrap())
         };
 
-        let renderer = pollster::block_on(Renderer::new(
+        let mut renderer = pollster::block_on(Renderer::new(
             &window,
             opts.theme.clone(),
             opts.scale.unwrap_or(window.scale_factor() as f32),
             opts.page_width.unwrap_or(f32::MAX),
             opts.font_opts.clone(),
+            event_loop.create_proxy(),
         ))?;
+        renderer.positioner.horizontal_scroll = opts.horizontal_scroll;
+        renderer.positioner.margin = opts.typography.margin;
+        renderer.positioner.padding = opts.typography.paragraph_spacing;
+
+        // Downscaled images keep enough resolution to fill the widest content area that the window
+        // could have on its monitor, with some extra headroom for zooming in
+        let image_max_width = if opts.downscale_images {
+            window.current_monitor().map(|monitor| {
+                let monitor_width = monitor.size().width as f32 / monitor.scale_factor() as f32;
+                let content_width = opts.page_width.map_or(monitor_width, |page_width| {
+                    monitor_width.min(page_width / renderer.hidpi_scale)
+                });
+                (content_width * renderer.hidpi_scale * image::ZOOM_HEADROOM) as u32
+            })
+        } else {
+            None
+        };
 
         let element_queue = Arc::new(Mutex::new(VecDeque::new()));
         let image_cache = Arc::new(Mutex::new(HashMap::new()));
@@ -202,17 +220,24 @@ impl Inlyne {
             image_cache.clone(),
             event_loop.create_proxy(),
             opts.color_scheme,
+            image_max_width,
+            opts.line_numbers,
+            opts.syntax.clone(),
+            opts.typography.clone(),
         );
 
         let (interpreter_sender, interpreter_receiver) = channel();
         let interpreter_should_queue = interpreter.should_queue.clone();
         std::thread::spawn(move || interpreter.interpret_md(interpreter_receiver));
 
-        interpreter_sender.send(md_string)?;
+        interpreter_sender.send(InterpreterMsg::Markdown(md_string))?;
 
         let lines_to_scroll = opts.lines_to_scroll;
 
         let watcher = Watcher::spawn(event_loop.create_proxy(), file_path.clone());
+        if opts.follow_system_theme {
+            system_theme::watch(event_loop.create_proxy(), opts.color_scheme);
+        }
 
         let _ = file_path.parent().map(std::env::set_current_dir);
 
@@ -231,6 +256,9 @@ impl Inlyne {
             need_repositioning: false,
             watcher,
             selection: Selection::new(),
+            folded_headings: HashSet::new(),
+            folded_path: file_path,
+            restore_scroll_y: None,
         })
     }
 
@@ -238,6 +266,7 @@ impl Inlyne {
         element_queue: &Arc<Mutex<VecDeque<Element>>>,
         renderer: &mut Renderer,
         elements: &mut Vec<Positioned<Element>>,
+        folded_headings: &HashSet<String>,
     ) {
         let queue = {
             element_queue
@@ -247,21 +276,25 @@ impl Inlyne {
         if let Ok(queue) = queue {
             let positioning_start = Instant::now();
 
+            // Elements that land inside of the trailing heading section grow it, so it gets
+            // positioned again from where the new elements start along with everything after it
+            let mut first_unpositioned = elements.len();
             for element in queue {
-                // Position element and add it to elements
-                let mut positioned_element = Positioned::new(element);
+                let len = elements.len();
+                if !positioner::push_outlined(elements, element, folded_headings) {
+                    first_unpositioned = first_unpositioned.min(len - 1);
+                }
+            }
+
+            for positioned_element in &mut elements[first_unpositioned..] {
                 renderer
                     .positioner
-                    .position(
-                        &mut renderer.text_system,
-                        &mut positioned_element,
-                        renderer.zoom,
-                    )
+                    .position_appended(&mut renderer.text_system, positioned_element, renderer.zoom)
+                    .unwrap();
+                renderer
+                    .positioner
+                    .advance(positioned_element, renderer.zoom)
                     .unwrap();
-                renderer.positioner.reserved_height +=
-                    DEFAULT_PADDING * renderer.hidpi_scale * renderer.zoom
-                        + positioned_element.bounds.as_ref().unwrap().size.1;
-                elements.push(positioned_element);
             }
 
             histogram!(HistTag::Positioner).record(positioning_start.elapsed());
@@ -272,11 +305,55 @@ impl Inlyne {
         self.interpreter_should_queue
             .store(false, Ordering::Relaxed);
         self.element_queue.lock().unwrap().clear();
+        // Keep headings folded across reloads of the same file
+        self.folded_headings.clear();
+        let path = self.opts.history.get_path();
+        if self.folded_path == path {
+            positioner::folded_headings(&self.elements, &mut self.folded_headings);
+        } else {
+            path.clone_into(&mut self.folded_path);
+        }
         self.elements.clear();
-        self.renderer.positioner.reserved_height = DEFAULT_PADDING * self.renderer.hidpi_scale;
+        let padding = self.renderer.positioner.padding;
+        self.renderer
+            .positioner
+            .rewind(padding * self.renderer.hidpi_scale);
         self.renderer.positioner.anchors.clear();
         self.interpreter_should_queue.store(true, Ordering::Relaxed);
-        self.interpreter_sender.send(contents).unwrap();
+        self.interpreter_sender
+            .send(InterpreterMsg::Markdown(contents))
+            .unwrap();
+    }
+
+    /// Re-interprets the current document with the theme for `color_scheme`
+    fn set_theme(&mut self, color_scheme: ResolvedTheme) {
+        if self.opts.color_scheme == Some(color_scheme) {
+            return;
+        }
+        let theme = self.opts.theme_for(color_scheme).clone();
+        self.opts.color_scheme = Some(color_scheme);
+        self.opts.theme = theme.clone();
+        self.renderer.theme = theme.clone();
+        self.interpreter_sender
+            .send(InterpreterMsg::Theme(Box::new(theme), Some(color_scheme)))
+            .unwrap();
+
+        let path = self.opts.history.get_path();
+        match read_to_string(path) {
+            Ok(contents) => {
+                // Text keeps the same layout, so we can return to the same spot once it's all back
+                self.restore_scroll_y = Some(self.renderer.scroll_y);
+                self.load_file(contents);
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Failed reloading file at {}\nError: {}",
+                    path.display(),
+                    err
+                );
+            }
+        }
+        self.window.request_redraw();
     }
 
     fn update_file(&mut self, path: &Path, contents: String) {
@@ -325,9 +402,16 @@ impl Inlyne {
                             &self.element_queue,
                             &mut self.renderer,
                             &mut self.elements,
+                            &self.folded_headings,
                         );
+                        // The whole document is in, so make room for any floats at the end
+                        self.renderer.positioner.clear_floats(self.renderer.zoom);
+                        if let Some(scroll_y) = self.restore_scroll_y.take() {
+                            self.renderer.set_scroll_y(scroll_y);
+                        }
                         self.window.request_redraw()
                     }
+                    InlyneEvent::SystemThemeChange(color_scheme) => self.set_theme(color_scheme),
                 },
                 Event::RedrawRequested(_) => {
                     let redraw_start = Instant::now();
@@ -335,6 +419,7 @@ impl Inlyne {
                         &self.element_queue,
                         &mut self.renderer,
                         &mut self.elements,
+                        &self.folded_headings,
                     );
                     self.renderer.set_scroll_y(self.renderer.scroll_y);
                     self.renderer
@@ -347,17 +432,43 @@ impl Inlyne {
                 Event::WindowEvent { event, .. } => match event {
                     WindowEvent::Resized(size) => pending_resize = Some(size),
                     WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::MouseWheel { delta, .. } => match delta {
-                        MouseScrollDelta::PixelDelta(pos) => {
-                            Self::scroll_pixels(&mut self.renderer, &self.window, pos.y as f32)
+                    WindowEvent::ThemeChanged(theme) if self.opts.follow_system_theme => {
+                        self.set_theme(match theme {
+                            winit::window::Theme::Dark => ResolvedTheme::Dark,
+                            winit::window::Theme::Light => ResolvedTheme::Light,
+                        });
+                    }
+                    WindowEvent::MouseWheel { delta, .. } => {
+                        let (x_pixels, y_pixels) = match delta {
+                            MouseScrollDelta::PixelDelta(pos) => (pos.x as f32, pos.y as f32),
+                            MouseScrollDelta::LineDelta(x_lines, y_lines) => {
+                                let line_pixels = Self::line_pixels(
+                                    &self.renderer,
+                                    &self.opts.typography,
+                                    self.lines_to_scroll,
+                                );
+                                (x_lines * line_pixels, y_lines * line_pixels)
+                            }
+                        };
+                        // Holding shift turns regular scrolling sideways
+                        let (x_pixels, y_pixels) = if modifiers.shift() {
+                            (x_pixels + y_pixels, 0.)
+                        } else {
+                            (x_pixels, y_pixels)
+                        };
+                        if x_pixels != 0.
+                            && positioner::scroll_horizontally(
+                                &mut self.elements,
+                                mouse_position,
+                                x_pixels,
+                            )
+                        {
+                            self.window.request_redraw();
                         }
-                        MouseScrollDelta::LineDelta(_, y_delta) => Self::scroll_lines(
-                            &mut self.renderer,
-                            &self.window,
-                            self.lines_to_scroll,
-                            y_delta,
-                        ),
-                    },
+                        if y_pixels != 0. {
+                            Self::scroll_pixels(&mut self.renderer, &self.window, y_pixels);
+                        }
+                    }
                     WindowEvent::CursorMoved { position, .. } => {
                         let screen_size = self.renderer.screen_size();
                         let loc = (
@@ -365,14 +476,44 @@ impl Inlyne {
                             position.y as f32 + self.renderer.scroll_y,
                         );
 
-                        let cursor_icon = if let Some(hoverable) = Self::find_hoverable(
+                        let hoverable = Self::find_hoverable(
                             &mut self.renderer.text_system,
-                            &mut self.renderer.positioner.taffy,
                             &self.elements,
                             loc,
                             screen_size,
+                            self.renderer.positioner.margin,
                             self.renderer.zoom,
-                        ) {
+                        );
+                        let hovered_code_block = positioner::find_code_block(&self.elements, loc)
+                            .map(|(_, bounds)| bounds);
+                        if self.renderer.hovered_code_block != hovered_code_block {
+                            self.renderer.hovered_code_block = hovered_code_block;
+                            self.window.request_redraw();
+                        }
+                        let over_copy_button = self
+                            .renderer
+                            .copy_button
+                            .as_ref()
+                            .is_some_and(|button| button.contains(loc));
+
+                        let tooltip = match &hoverable {
+                            _ if over_copy_button => Some((
+                                (position.x as f32, position.y as f32),
+                                "Copy code".to_owned(),
+                            )),
+                            Some(Hoverable::Text(Text {
+                                title: Some(title), ..
+                            })) => Some(((position.x as f32, position.y as f32), title.clone())),
+                            _ => None,
+                        };
+                        if self.renderer.tooltip != tooltip {
+                            self.renderer.tooltip = tooltip;
+                            self.window.request_redraw();
+                        }
+
+                        let cursor_icon = if over_copy_button {
+                            CursorIcon::Hand
+                        } else if let Some(hoverable) = hoverable {
                             match hoverable {
                                 Hoverable::Image(Image { is_link: None, .. }) => {
                                     CursorIcon::Default
@@ -457,12 +598,26 @@ impl Inlyne {
                                 self.window.request_redraw();
                             }
 
-                            if let Some(hoverable) = Self::find_hoverable(
+                            let on_copy_button = self
+                                .renderer
+                                .copy_button
+                                .as_ref()
+                                .is_some_and(|button| button.contains(mouse_position));
+                            if on_copy_button {
+                                if let Some((code_block, _)) =
+                                    positioner::find_code_block(&self.elements, mouse_position)
+                                {
+                                    clipboard.set_contents(code_block.code.clone());
+                                    let cursor = (mouse_position.0, y);
+                                    self.renderer.tooltip = Some((cursor, "Copied".to_owned()));
+                                    self.window.request_redraw();
+                                }
+                            } else if let Some(hoverable) = Self::find_hoverable(
                                 &mut self.renderer.text_system,
-                                &mut self.renderer.positioner.taffy,
                                 &self.elements,
                                 mouse_position,
                                 screen_size,
+                                self.renderer.positioner.margin,
                                 self.renderer.zoom,
                             ) {
                                 match hoverable {
@@ -566,6 +721,7 @@ impl Inlyne {
                                     Self::scroll_lines(
                                         &mut self.renderer,
                                         &self.window,
+                                        &self.opts.typography,
                                         self.lines_to_scroll,
                                         lines,
                                     )
@@ -600,8 +756,23 @@ impl Inlyne {
                                     );
                                     self.window.request_redraw();
                                 }
+                                Action::ExpandAll | Action::CollapseAll => {
+                                    let hidden = action == Action::CollapseAll;
+                                    positioner::set_all_hidden(&self.elements, hidden);
+                                    self.need_repositioning = true;
+                                }
                                 Action::Copy => clipboard
                                     .set_contents(self.selection.text.trim().to_owned()),
+                                Action::CopyCodeBlock => {
+                                    if let Some((code_block, _)) =
+                                        positioner::find_code_block(&self.elements, mouse_position)
+                                    {
+                                        clipboard.set_contents(code_block.code.clone());
+                                    }
+                                }
+                                Action::ToggleTheme => self.set_theme(
+                                    self.opts.color_scheme.unwrap_or_default().toggled(),
+                                ),
                                 Action::Quit => *control_flow = ControlFlow::Exit,
                                 Action::History(hist_dir) => {
                                     let changed_path = match hist_dir {
@@ -667,13 +838,20 @@ impl Inlyne {
     fn scroll_lines(
         renderer: &mut Renderer,
         window: &Window,
+        typography: &Typography,
         lines_to_scroll: f32,
         num_lines: f32,
     ) {
-        let num_pixels = num_lines * 16.0 * lines_to_scroll * renderer.hidpi_scale * renderer.zoom;
+        let num_pixels = num_lines * Self::line_pixels(renderer, typography, lines_to_scroll);
         Self::scroll_pixels(renderer, window, num_pixels);
     }
 
+    // How far scrolling by `lines_to_scroll` lines of regular text moves the page
+    fn line_pixels(renderer: &Renderer, typography: &Typography, lines_to_scroll: f32) -> f32 {
+        let line_height = typography.font_size * typography.line_height;
+        line_height * lines_to_scroll * renderer.hidpi_scale * renderer.zoom
+    }
+
     fn scroll_pixels(renderer: &mut Renderer, window: &Window, num_pixels: f32) {
         renderer.set_scroll_y(renderer.scroll_y - num_pixels);
         window.request_redraw();
@@ -681,17 +859,14 @@ impl Inlyne {
 
     fn find_hoverable<'a>(
         text_system: &mut TextSystem,
-        taffy: &mut Taffy,
         elements: &'a [Positioned<Element>],
         loc: Point,
         screen_size: Size,
+        margin: f32,
         zoom: f32,
     ) -> Option<Hoverable<'a>> {
         let screen_pos = |screen_size: Size, bounds_offset: f32| {
-            (
-                screen_size.0 - bounds_offset - DEFAULT_MARGIN,
-                screen_size.1,
-            )
+            (screen_size.0 - bounds_offset - margin, screen_size.1)
         };
 
         elements
@@ -699,50 +874,63 @@ impl Inlyne {
             .find(|&e| e.contains(loc) && !matches!(e.inner, Element::Spacer(_)))
             .and_then(|element| match &element.inner {
                 Element::TextBox(text_box) => {
+                    if let Some(image) = text_box.find_image(loc) {
+                        return Some(Hoverable::Image(image));
+                    }
                     let bounds = element.bounds.as_ref().unwrap();
+                    // Right-to-left text is indented from the right, so it wraps within its bounds
+                    let text_bounds = if text_box.is_rtl() {
+                        (bounds.size.0, screen_size.1)
+                    } else {
+                        screen_pos(screen_size, bounds.pos.0 + text_box.right_float)
+                    };
                     text_box
                         .find_hoverable(
                             text_system,
-                            loc,
-                            bounds.pos,
-                            screen_pos(screen_size, bounds.pos.0),
-                            zoom,
-                        )
-                        .map(Hoverable::Text)
-                }
-                Element::Table(table) => {
-                    let bounds = element.bounds.as_ref().unwrap();
-                    table
-                        .find_hoverable(
-                            text_system,
-                            taffy,
-                            loc,
+                            (loc.0 + text_box.scroll_offset(), loc.1),
                             bounds.pos,
-                            screen_pos(screen_size, bounds.pos.0),
+                            text_bounds,
                             zoom,
                         )
                         .map(Hoverable::Text)
                 }
+                Element::Table(table) => table.find_hoverable(text_system, loc, zoom),
                 Element::Image(image) => Some(Hoverable::Image(image)),
                 Element::Spacer(_) => unreachable!("Spacers are filtered"),
-                Element::Row(row) => {
-                    Self::find_hoverable(text_system, taffy, &row.elements, loc, screen_size, zoom)
-                }
                 Element::Section(section) => {
                     if let Some(ref summary) = *section.summary {
                         if let Some(ref bounds) = summary.bounds {
-                            if bounds.contains(loc) {
-                                return Some(Hoverable::Summary(section));
+                            let section_left = element.bounds.as_ref().unwrap().pos.0;
+                            let in_gutter = loc.0 < bounds.pos.0;
+                            let in_summary = Rect::new(
+                                (section_left, bounds.pos.1),
+                                (bounds.max().0 - section_left, bounds.size.1),
+                            )
+                            .contains(loc);
+                            if in_summary {
+                                // Only the marker toggles a heading, so that its text stays
+                                // selectable
+                                if in_gutter || section.heading_level().is_none() {
+                                    return Some(Hoverable::Summary(section));
+                                }
+                                return Self::find_hoverable(
+                                    text_system,
+                                    std::slice::from_ref(summary),
+                                    loc,
+                                    screen_size,
+                                    margin,
+                                    zoom,
+                                );
                             }
                         }
                     }
                     if !*section.hidden.borrow() {
                         Self::find_hoverable(
                             text_system,
-                            taffy,
                             &section.elements,
                             loc,
                             screen_size,
+                            margin,
                             zoom,
                         )
                     } else {
@@ -769,16 +957,7 @@ fn main() -> anyhow::Result<()> {
 
     match command {
         Commands::View(view) => {
-            let config = match &view.config {
-                Some(config_path) => Config::load_from_file(config_path)?,
-                None => Config::load_from_system().unwrap_or_else(|err| {
-                    tracing::warn!(
-                        "Failed reading config file. Falling back to defaults. Error: {}",
-                        err
-                    );
-                    Config::default()
-                }),
-            };
+            let config = load_config(view.config.as_deref())?;
             let opts = Opts::parse_and_load_from(view, config)?;
 
             if let Some(exporter) = &opts.metrics {
@@ -798,6 +977,9 @@ fn main() -> anyhow::Result<()> {
             for tag in HistTag::iter() {
                 tag.set_global_description();
             }
+            for tag in GaugeTag::iter() {
+                tag.set_global_description();
+            }
 
             let inlyne = Inlyne::new(opts)?;
             inlyne.run();
@@ -818,7 +1000,72 @@ fn main() -> anyhow::Result<()> {
 
             edit::edit_file(config_path)?;
         }
+        Commands::Config(ConfigCmd::Themes) => {
+            for theme in opts::themes::list() {
+                let color_scheme = theme
+                    .load()
+                    .map_or("invalid", |theme_file| theme_file.color_scheme.as_str());
+                #[allow(clippy::print_stdout)]
+                {
+                    println!("{:<20} {:<8} {}", theme.name, color_scheme, theme.source);
+                }
+            }
+        }
+        Commands::Fonts => {
+            let font_opts = load_config(None)?.font_options.unwrap_or_default();
+            let font_system = fonts::get_fonts(&font_opts);
+            let db = font_system.db();
+            let families = fonts::FontFamilies::new(&font_opts);
+            #[allow(clippy::print_stdout)]
+            {
+                for (role, family) in families.roles() {
+                    let resolved = match fonts::resolve(db, family) {
+                        Some(face) => {
+                            let name = face.families.first().map_or("", |(name, _)| name.as_str());
+                            let path = match &face.source {
+                                fontdb::Source::File(path)
+                                | fontdb::Source::SharedFile(path, _) => {
+                                    format!(" ({})", path.display())
+                                }
+                                fontdb::Source::Binary(_) => String::new(),
+                            };
+                            format!("{name}{path}")
+                        }
+                        None => "not found, uses the system's fallbacks".to_owned(),
+                    };
+                    // Generic families get mapped to a specific one
+                    let requested = match family {
+                        Family::Name(name) => name.to_owned(),
+                        generic => format!(
+                            "{} ({})",
+                            fonts::family_label(&generic),
+                            db.family_name(&generic)
+                        ),
+                    };
+                    println!("{role:<12} {requested:<32} {resolved}");
+                }
+                println!();
+                for name in fonts::family_names(db) {
+                    println!("{name}");
+                }
+            }
+        }
     }
 
     Ok(())
 }
+
+// Reads the config at `config_path` or the one in the config directory when there isn't one
+fn load_config(config_path: Option<&Path>) -> anyhow::Result<Config> {
+    let config = match config_path {
+        Some(config_path) => Config::load_from_file(config_path)?,
+        None => Config::load_from_system().unwrap_or_else(|err| {
+            tracing::warn!(
+                "Failed reading config file. Falling back to defaults. Error: {}",
+                err
+            );
+            Config::default()
+        }),
+    };
+    Ok(config)
+}
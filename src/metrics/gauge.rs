@@ -1,9 +1,62 @@
-use super::{Metric, Unit, SPAN_LEVEL};
+use super::{describe_gauge, Metric, Unit, SPAN_LEVEL};
 
-use metrics::{GaugeFn, Key};
+use metrics::{GaugeFn, Key, KeyName};
 use parking_lot::Mutex;
 use tracing::{debug, span};
 
+#[derive(Clone, Copy)]
+pub enum Tag {
+    ImageTextureBytes,
+}
+
+impl Tag {
+    pub fn set_global_description(self) {
+        describe_gauge!(self.as_str(), self.unit(), self.desc_text());
+    }
+
+    pub fn iter() -> TagIter {
+        TagIter(Some(Tag::ImageTextureBytes))
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Tag::ImageTextureBytes => "image.texture_bytes",
+        }
+    }
+
+    pub fn desc_text(self) -> &'static str {
+        match self {
+            Self::ImageTextureBytes => "Bytes of image textures currently uploaded to the GPU",
+        }
+    }
+
+    pub fn unit(self) -> Unit {
+        match self {
+            Self::ImageTextureBytes => Unit::Bytes,
+        }
+    }
+}
+
+impl From<Tag> for KeyName {
+    fn from(tag: Tag) -> Self {
+        tag.as_str().into()
+    }
+}
+
+pub struct TagIter(Option<Tag>);
+
+impl Iterator for TagIter {
+    type Item = Tag;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = std::mem::take(&mut self.0)?;
+        self.0 = match next {
+            Tag::ImageTextureBytes => None,
+        };
+        Some(next)
+    }
+}
+
 pub struct Handle(pub Mutex<Metric<f64>>);
 
 impl Handle {
@@ -36,7 +36,7 @@ impl Default for LogRecorder {
 impl metrics::Recorder for LogRecorder {
     fn describe_gauge(&self, key: KeyName, unit: Option<Unit>, _desc: SharedString) {
         let key = Key::from_name(key);
-        let gauge = self.0.get_or_create_histogram(&key, Arc::clone);
+        let gauge = self.0.get_or_create_gauge(&key, Arc::clone);
         gauge.0.lock().unit = unit.unwrap_or(Unit::Count);
     }
 
@@ -5,13 +5,16 @@ use parking_lot::Mutex;
 use tracing::Level;
 
 // Re-exports from the actual `metrics` crate
-pub use metrics::{describe_histogram, histogram, set_global_recorder, Unit};
+pub use metrics::{
+    describe_gauge, describe_histogram, gauge, histogram, set_global_recorder, Unit,
+};
 
 mod counter;
 mod gauge;
 mod hist;
 mod log_recorder;
 
+pub use gauge::Tag as GaugeTag;
 pub use hist::Tag as HistTag;
 pub use log_recorder::LogRecorder;
 
@@ -1,36 +1,91 @@
-use clap::{
-    builder::PossibleValue, command, value_parser, Args as ClapArgs, Parser, Subcommand, ValueEnum,
-};
-use serde::Deserialize;
+use clap::builder::{PossibleValue, StringValueParser, TypedValueParser};
+use clap::{command, value_parser, Args as ClapArgs, Parser, Subcommand};
+use serde::{Deserialize, Deserializer};
 use std::array;
+use std::convert::Infallible;
+use std::ffi::OsStr;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// The names of the themes that are bundled with inlyne
+pub const BUNDLED_THEMES: &[&str] = &["dracula", "gruvbox-dark", "nord", "solarized-light"];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub enum ThemeType {
     #[default]
     Auto,
     Dark,
     Light,
+    /// A bundled theme or one from a file in the themes directory
+    Named(String),
 }
 
 impl ThemeType {
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
         match self {
             Self::Auto => "auto",
             Self::Dark => "dark",
             Self::Light => "light",
+            Self::Named(name) => name,
         }
     }
 }
 
-impl ValueEnum for ThemeType {
-    fn value_variants<'a>() -> &'a [Self] {
-        &[Self::Auto, Self::Dark, Self::Light]
+impl From<&str> for ThemeType {
+    fn from(s: &str) -> Self {
+        if s.eq_ignore_ascii_case("auto") {
+            Self::Auto
+        } else if s.eq_ignore_ascii_case("dark") {
+            Self::Dark
+        } else if s.eq_ignore_ascii_case("light") {
+            Self::Light
+        } else {
+            Self::Named(s.to_owned())
+        }
+    }
+}
+
+impl FromStr for ThemeType {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.into())
     }
+}
+
+/// Accepts any theme name while still listing the built-in ones in `--help` and completions
+#[derive(Clone)]
+struct ThemeTypeParser;
+
+impl TypedValueParser for ThemeTypeParser {
+    type Value = ThemeType;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        let s = StringValueParser::new().parse_ref(cmd, arg, value)?;
+        Ok(s.as_str().into())
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        let names = ["auto", "dark", "light"]
+            .iter()
+            .chain(BUNDLED_THEMES)
+            .map(PossibleValue::new);
+        Some(Box::new(names))
+    }
+}
 
-    fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
-        Some(PossibleValue::new(self.as_str()))
+impl<'de> Deserialize<'de> for ThemeType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.as_str().into())
     }
 }
 
@@ -115,6 +170,8 @@ pub enum Commands {
     View(View),
     #[command(subcommand)]
     Config(ConfigCmd),
+    /// Lists the installed font families and the font used for each kind of text
+    Fonts,
 }
 
 /// View a markdown file with inlyne
@@ -125,8 +182,9 @@ pub struct View {
     #[arg(value_name = "FILE", required = true)]
     pub file_path: PathBuf,
 
-    /// Theme to use when rendering
-    #[arg(short = 't', long = "theme", value_parser = value_parser!(ThemeType))]
+    /// Theme to use when rendering. Themes from the themes directory can be selected by name too
+    /// (see `inlyne config themes`)
+    #[arg(short = 't', long = "theme", value_parser = ThemeTypeParser)]
     pub theme: Option<ThemeType>,
 
     /// Factor to scale rendered file by [default: OS defined window scale factor]
@@ -155,4 +213,6 @@ pub struct View {
 pub enum ConfigCmd {
     /// Opens the configuration file in the default text editor
     Open,
+    /// Lists the themes that can be selected by name
+    Themes,
 }
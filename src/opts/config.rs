@@ -1,3 +1,4 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{create_dir_all, read_to_string};
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -5,6 +6,7 @@ use std::path::{Path, PathBuf};
 use super::{Position, Size, ThemeType};
 use crate::color;
 use crate::keybindings::Keybindings;
+use crate::positioner::{DEFAULT_MARGIN, DEFAULT_PADDING};
 
 use anyhow::Context;
 use serde::Deserialize;
@@ -17,6 +19,98 @@ pub struct FontOptions {
     pub regular_font: Option<String>,
     #[serde(default)]
     pub monospace_font: Option<String>,
+    /// Font family for headings. Falls back to the body font
+    #[serde(default)]
+    pub heading_font: Option<String>,
+    /// Font family for all other regular text
+    #[serde(default)]
+    pub body_font: Option<String>,
+    /// Families tried in order for characters that the main font doesn't have
+    #[serde(default)]
+    pub fallback_fonts: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SyntaxOptions {
+    /// Maps fence languages to the syntax used to highlight them, even when the language is
+    /// recognized on its own
+    pub aliases: HashMap<String, String>,
+    /// The syntax used for fences without a language
+    pub default: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Typography {
+    /// Font size of regular text in pixels
+    pub font_size: f32,
+    /// Height of each line as a multiple of the font size
+    pub line_height: f32,
+    /// Font sizes of `h1` through `h6` as multiples of the regular font size
+    pub heading_scale: [f32; 6],
+    /// Space left between paragraphs, headings and other blocks
+    pub paragraph_spacing: f32,
+    /// Space on either side of the page
+    pub margin: f32,
+}
+
+impl Default for Typography {
+    fn default() -> Self {
+        Self {
+            font_size: 16.,
+            line_height: 1.1,
+            // https://html.spec.whatwg.org/multipage/rendering.html#sections-and-headings
+            heading_scale: [2.0, 1.5, 1.17, 1.0, 0.83, 0.67],
+            paragraph_spacing: DEFAULT_PADDING,
+            margin: DEFAULT_MARGIN,
+        }
+    }
+}
+
+impl Typography {
+    pub fn heading_size(&self, level: u8) -> f32 {
+        self.font_size * self.heading_scale[usize::from(level - 1)]
+    }
+
+    /// Sizes that aren't positive can't lay out any text at all, and neither can a margin that
+    /// takes up the whole `page_width`
+    pub fn validate(&self, page_width: Option<f32>) -> anyhow::Result<()> {
+        let sizes = [
+            ("font-size", self.font_size),
+            ("line-height", self.line_height),
+        ]
+        .into_iter()
+        .chain(
+            self.heading_scale
+                .iter()
+                .map(|&scale| ("heading-scale", scale)),
+        );
+        for (key, value) in sizes {
+            anyhow::ensure!(
+                value.is_finite() && value > 0.,
+                "`typography.{key}` must be a positive number, but got {value}"
+            );
+        }
+        let spacings = [
+            ("paragraph-spacing", self.paragraph_spacing),
+            ("margin", self.margin),
+        ];
+        for (key, value) in spacings {
+            anyhow::ensure!(
+                value.is_finite() && value >= 0.,
+                "`typography.{key}` must be zero or a positive number, but got {value}"
+            );
+        }
+        if let Some(page_width) = page_width {
+            anyhow::ensure!(
+                page_width - 2. * self.margin > 0.,
+                "`typography.margin` of {} leaves no room on a page that's {page_width} wide",
+                self.margin
+            );
+        }
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, Debug, Default, PartialEq)]
@@ -29,16 +123,48 @@ pub struct OptionalTheme {
     pub link_color: Option<u32>,
     pub select_color: Option<u32>,
     pub checkbox_color: Option<u32>,
+    pub mark_color: Option<u32>,
+    pub diff_added_color: Option<u32>,
+    pub diff_removed_color: Option<u32>,
+    pub diff_hunk_color: Option<u32>,
+    pub h1_color: Option<u32>,
+    pub h2_color: Option<u32>,
+    pub h3_color: Option<u32>,
+    pub h4_color: Option<u32>,
+    pub h5_color: Option<u32>,
+    pub h6_color: Option<u32>,
+    pub table_border_color: Option<u32>,
+    pub table_header_color: Option<u32>,
+    pub table_stripe_color: Option<u32>,
+    pub hr_color: Option<u32>,
+    pub scrollbar_track_color: Option<u32>,
+    pub scrollbar_thumb_color: Option<u32>,
+    pub code_background_color: Option<u32>,
+    pub select_text_color: Option<u32>,
     pub code_highlighter: Option<color::SyntaxTheme>,
 }
 
 impl OptionalTheme {
-    pub fn merge(self, other: color::Theme) -> anyhow::Result<color::Theme> {
+    pub fn merge(
+        self,
+        other: color::Theme,
+        user_syntax_themes: &BTreeMap<String, SyntectTheme>,
+    ) -> anyhow::Result<color::Theme> {
         let code_highlighter = match self.code_highlighter {
-            Some(theme) => SyntectTheme::try_from(theme)?,
+            Some(theme) => theme.load(user_syntax_themes)?,
             None => other.code_highlighter,
         };
 
+        let heading_colors = [
+            self.h1_color,
+            self.h2_color,
+            self.h3_color,
+            self.h4_color,
+            self.h5_color,
+            self.h6_color,
+        ];
+        let heading_colors = std::array::from_fn(|i| heading_colors[i].or(other.heading_colors[i]));
+
         Ok(color::Theme {
             text_color: self.text_color.unwrap_or(other.text_color),
             background_color: self.background_color.unwrap_or(other.background_color),
@@ -47,6 +173,21 @@ impl OptionalTheme {
             link_color: self.link_color.unwrap_or(other.link_color),
             select_color: self.select_color.unwrap_or(other.select_color),
             checkbox_color: self.checkbox_color.unwrap_or(other.checkbox_color),
+            mark_color: self.mark_color.unwrap_or(other.mark_color),
+            diff_added_color: self.diff_added_color.unwrap_or(other.diff_added_color),
+            diff_removed_color: self.diff_removed_color.unwrap_or(other.diff_removed_color),
+            diff_hunk_color: self.diff_hunk_color.unwrap_or(other.diff_hunk_color),
+            heading_colors,
+            table_border_color: self.table_border_color.or(other.table_border_color),
+            table_header_color: self.table_header_color.or(other.table_header_color),
+            table_stripe_color: self.table_stripe_color.or(other.table_stripe_color),
+            hr_color: self.hr_color.or(other.hr_color),
+            scrollbar_track_color: self.scrollbar_track_color.or(other.scrollbar_track_color),
+            scrollbar_thumb_color: self
+                .scrollbar_thumb_color
+                .unwrap_or(other.scrollbar_thumb_color),
+            code_background_color: self.code_background_color.or(other.code_background_color),
+            select_text_color: self.select_text_color.or(other.select_text_color),
             code_highlighter,
         })
     }
@@ -101,10 +242,15 @@ pub struct Config {
     pub theme: Option<ThemeType>,
     pub scale: Option<f32>,
     pub page_width: Option<f32>,
+    pub downscale_images: Option<bool>,
+    pub horizontal_scroll: Option<bool>,
+    pub line_numbers: Option<bool>,
     pub lines_to_scroll: LinesToScroll,
     pub light_theme: Option<OptionalTheme>,
     pub dark_theme: Option<OptionalTheme>,
     pub font_options: Option<FontOptions>,
+    pub syntax: SyntaxOptions,
+    pub typography: Typography,
     pub keybindings: KeybindingsSection,
     pub debug: DebugSection,
     pub window: Option<Window>,
@@ -112,7 +258,8 @@ pub struct Config {
 
 impl Config {
     pub fn load_from_str(s: &str) -> anyhow::Result<Self> {
-        let config = toml::from_str(s)?;
+        let config: Self = toml::from_str(s)?;
+        config.typography.validate(config.page_width)?;
         Ok(config)
     }
 
@@ -169,11 +316,15 @@ mod tests {
         assert_eq!(config, Config::default());
         assert_eq!(theme, ThemeType::Auto);
         assert_eq!(
-            dark_theme.merge(color::Theme::dark_default()).unwrap(),
+            dark_theme
+                .merge(color::Theme::dark_default(), &BTreeMap::new())
+                .unwrap(),
             color::Theme::dark_default()
         );
         assert_eq!(
-            light_theme.merge(color::Theme::light_default()).unwrap(),
+            light_theme
+                .merge(color::Theme::light_default(), &BTreeMap::new())
+                .unwrap(),
             color::Theme::light_default()
         );
     }
@@ -2,6 +2,7 @@ mod cli;
 mod config;
 #[cfg(test)]
 mod tests;
+pub mod themes;
 
 use std::{
     path::Path,
@@ -10,13 +11,17 @@ use std::{
 
 use crate::color;
 pub use cli::{Cli, Commands, ConfigCmd, Position, Size, ThemeType, View};
-pub use config::{Config, DebugSection, FontOptions, KeybindingsSection, MetricsExporter};
+pub use config::{
+    Config, DebugSection, FontOptions, KeybindingsSection, MetricsExporter, SyntaxOptions,
+    Typography,
+};
 
 use crate::history::History;
 use anyhow::Result;
 use clap::Parser;
 use serde::Deserialize;
 use smart_debug::SmartDebug;
+use themes::ThemeFile;
 
 static RENDER_ELEMENT_BOUNDS: AtomicBool = AtomicBool::new(false);
 
@@ -30,6 +35,7 @@ pub fn set_render_element_bounds(b: bool) {
 }
 
 #[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
 pub enum ResolvedTheme {
     Dark,
     #[default]
@@ -37,15 +43,21 @@ pub enum ResolvedTheme {
 }
 
 impl ResolvedTheme {
-    fn new(theme_ty: ThemeType) -> Option<Self> {
-        match theme_ty {
-            ThemeType::Auto => Self::try_detect(),
-            ThemeType::Dark => Some(Self::Dark),
-            ThemeType::Light => Some(Self::Light),
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dark => "dark",
+            Self::Light => "light",
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            Self::Dark => Self::Light,
+            Self::Light => Self::Dark,
         }
     }
 
-    fn try_detect() -> Option<Self> {
+    pub fn try_detect() -> Option<Self> {
         match dark_light::detect() {
             dark_light::Mode::Default => None,
             dark_light::Mode::Dark => Some(Self::Dark),
@@ -59,10 +71,21 @@ pub struct Opts {
     pub history: History,
     #[debug(skip)]
     pub theme: color::Theme,
+    #[debug(skip)]
+    pub light_theme: color::Theme,
+    #[debug(skip)]
+    pub dark_theme: color::Theme,
+    // Switch between the light and dark themes when the system's color scheme changes
+    pub follow_system_theme: bool,
     pub scale: Option<f32>,
     pub page_width: Option<f32>,
+    pub downscale_images: bool,
+    pub horizontal_scroll: bool,
+    pub line_numbers: bool,
     pub lines_to_scroll: f32,
     pub font_opts: FontOptions,
+    pub syntax: SyntaxOptions,
+    pub typography: Typography,
     pub keybindings: KeybindingsSection,
     pub color_scheme: Option<ResolvedTheme>,
     pub metrics: Option<MetricsExporter>,
@@ -81,7 +104,15 @@ impl Opts {
         #[cfg(not(test))]
         {
             let system_color_scheme = ResolvedTheme::try_detect();
-            Self::parse_and_load_inner(args, config, system_color_scheme)
+            let themes_dir = crate::utils::user_config_dir("themes");
+            let syntax_themes_dir = crate::utils::user_config_dir("syntax-themes");
+            Self::parse_and_load_inner(
+                args,
+                config,
+                system_color_scheme,
+                themes_dir.as_deref(),
+                syntax_themes_dir.as_deref(),
+            )
         }
     }
 
@@ -91,22 +122,46 @@ impl Opts {
         config: Config,
         theme: Option<ResolvedTheme>,
     ) -> Result<Self> {
-        Self::parse_and_load_inner(args, config, theme)
+        Self::parse_and_load_inner(args, config, theme, None, None)
     }
 
+    #[cfg(test)]
+    pub fn parse_and_load_with_theme_dirs(
+        args: View,
+        config: Config,
+        themes_dir: &Path,
+        syntax_themes_dir: &Path,
+    ) -> Result<Self> {
+        Self::parse_and_load_inner(
+            args,
+            config,
+            None,
+            Some(themes_dir),
+            Some(syntax_themes_dir),
+        )
+    }
+
+    /// `themes_dir` and `syntax_themes_dir` hold the user's `.toml` and `.tmTheme` themes
     fn parse_and_load_inner(
         args: View,
         config: Config,
         fallback_theme: Option<ResolvedTheme>,
+        themes_dir: Option<&Path>,
+        syntax_themes_dir: Option<&Path>,
     ) -> Result<Self> {
         let Config {
             theme: config_theme,
             scale: config_scale,
             page_width: config_page_width,
+            downscale_images,
+            horizontal_scroll,
+            line_numbers,
             lines_to_scroll,
             light_theme,
             dark_theme,
             font_options,
+            syntax,
+            typography,
             keybindings,
             debug,
             window,
@@ -130,25 +185,49 @@ impl Opts {
         set_render_element_bounds(render_element_bounds);
 
         let history = History::new(&file_path)?;
-        let resolved_theme = args_theme
-            .or(config_theme)
-            .and_then(ResolvedTheme::new)
-            .or(fallback_theme);
-        let theme = {
-            let (maybe_theme, fallback_values) = match resolved_theme {
-                Some(ResolvedTheme::Dark) => (dark_theme, color::Theme::dark_default()),
-                None | Some(ResolvedTheme::Light) => (light_theme, color::Theme::light_default()),
-            };
-
-            match maybe_theme {
-                Some(theme) => theme.merge(fallback_values)?,
-                None => fallback_values,
+        let theme_type = args_theme.or(config_theme);
+        let follow_system_theme = matches!(theme_type, None | Some(ThemeType::Auto));
+        let user_syntax_themes = syntax_themes_dir
+            .map(color::load_user_themes)
+            .unwrap_or_default();
+        let mut light_theme = match light_theme {
+            Some(theme) => theme.merge(color::Theme::light_default(), &user_syntax_themes)?,
+            None => color::Theme::light_default(),
+        };
+        let mut dark_theme = match dark_theme {
+            Some(theme) => theme.merge(color::Theme::dark_default(), &user_syntax_themes)?,
+            None => color::Theme::dark_default(),
+        };
+        let resolved_theme = match theme_type {
+            None | Some(ThemeType::Auto) => fallback_theme,
+            Some(ThemeType::Dark) => Some(ResolvedTheme::Dark),
+            Some(ThemeType::Light) => Some(ResolvedTheme::Light),
+            // A named theme takes the place of the light or dark theme that it declares itself as
+            Some(ThemeType::Named(name)) => {
+                let (color_scheme, theme) =
+                    ThemeFile::load_by_name(&name, themes_dir)?.into_theme(&user_syntax_themes)?;
+                match color_scheme {
+                    ResolvedTheme::Dark => dark_theme = theme,
+                    ResolvedTheme::Light => light_theme = theme,
+                }
+                Some(color_scheme)
             }
         };
+        let theme = match resolved_theme {
+            Some(ResolvedTheme::Dark) => dark_theme.clone(),
+            None | Some(ResolvedTheme::Light) => light_theme.clone(),
+        };
 
         let scale = args_scale.or(config_scale);
         let font_opts = font_options.unwrap_or_default();
         let page_width = args_page_width.or(config_page_width);
+        // The config was only checked against its own page width
+        if args_page_width.is_some() {
+            typography.validate(page_width)?;
+        }
+        let downscale_images = downscale_images.unwrap_or(true);
+        let horizontal_scroll = horizontal_scroll.unwrap_or(false);
+        let line_numbers = line_numbers.unwrap_or(false);
         let lines_to_scroll = lines_to_scroll.into();
 
         let (position, size) = if let Some(window) = window {
@@ -160,10 +239,18 @@ impl Opts {
         Ok(Self {
             history,
             theme,
+            light_theme,
+            dark_theme,
+            follow_system_theme,
             scale,
             page_width,
+            downscale_images,
+            horizontal_scroll,
+            line_numbers,
             lines_to_scroll,
             font_opts,
+            syntax,
+            typography,
             keybindings,
             color_scheme: resolved_theme,
             metrics,
@@ -172,6 +259,13 @@ impl Opts {
         })
     }
 
+    pub fn theme_for(&self, color_scheme: ResolvedTheme) -> &color::Theme {
+        match color_scheme {
+            ResolvedTheme::Dark => &self.dark_theme,
+            ResolvedTheme::Light => &self.light_theme,
+        }
+    }
+
     /// Arguments to supply to program that are opened externally.
     pub fn program_args(file_path: &Path) -> Vec<String> {
         let current_args = Cli::parse().into_view().expect("Should contain an view!");
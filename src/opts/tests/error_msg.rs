@@ -1,3 +1,6 @@
+use std::collections::BTreeMap;
+
+use crate::color::Theme;
 use crate::keybindings::KeyCombos;
 use crate::opts::Config;
 
@@ -31,13 +34,44 @@ base = [
 ]
 "#;
 
+const ZERO_FONT_SIZE: &str = "typography.font-size = 0";
+const NEGATIVE_LINE_HEIGHT: &str = "typography.line-height = -1.5";
+const NAN_HEADING_SCALE: &str = "typography.heading-scale = [2.0, 1.5, nan, 1.0, 0.83, 0.67]";
+const NEGATIVE_PARAGRAPH_SPACING: &str = "typography.paragraph-spacing = -5";
+const INFINITE_MARGIN: &str = "typography.margin = inf";
+const MARGIN_WIDER_THAN_PAGE: &str = "page-width = 300\ntypography.margin = 150";
+
 snapshot_config_parse_error!(
-    (unknown_theme, UNKNOWN_THEME),
     (invalid_theme_ty, INVALID_THEME_TY),
+    (zero_font_size, ZERO_FONT_SIZE),
+    (negative_line_height, NEGATIVE_LINE_HEIGHT),
+    (nan_heading_scale, NAN_HEADING_SCALE),
+    (negative_paragraph_spacing, NEGATIVE_PARAGRAPH_SPACING),
+    (infinite_margin, INFINITE_MARGIN),
+    (margin_wider_than_page, MARGIN_WIDER_THAN_PAGE),
     // FIXME: vv
     (fix_this_sucky_error_message, FIX_THIS_SUCKY_ERROR_MESSAGE),
 );
 
+// Syntax theme names are only checked against the user's themes when loading the options
+#[test]
+fn unknown_theme() {
+    crate::test_utils::log::init();
+
+    let config = Config::load_from_str(UNKNOWN_THEME).unwrap();
+    let err = config
+        .light_theme
+        .unwrap()
+        .merge(Theme::light_default(), &BTreeMap::new())
+        .unwrap_err();
+
+    insta::with_settings!({
+        description => UNKNOWN_THEME,
+    }, {
+        insta::assert_snapshot!(err);
+    });
+}
+
 fn keycombo_conflict_from_config(s: &str) -> anyhow::Result<anyhow::Error> {
     let Config { keybindings, .. } = Config::load_from_str(s)?;
     let err = KeyCombos::new(keybindings).unwrap_err();
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 
@@ -8,7 +9,7 @@ use tempfile::NamedTempFile;
 use crate::color::{SyntaxTheme, Theme, ThemeDefaults};
 use crate::history::History;
 use crate::opts::config::{self, FontOptions, LinesToScroll};
-use crate::opts::{Cli, Opts, Position, ResolvedTheme, Size, ThemeType};
+use crate::opts::{themes, Cli, Opts, Position, ResolvedTheme, Size, ThemeType};
 use crate::test_utils::log;
 
 fn gen_args(args: Vec<&str>) -> Vec<OsString> {
@@ -33,9 +34,17 @@ impl Opts {
         Self {
             history: History::new(file_path.as_ref()).unwrap(),
             theme: ResolvedTheme::Light.as_theme(),
+            light_theme: ResolvedTheme::Light.as_theme(),
+            dark_theme: ResolvedTheme::Dark.as_theme(),
+            follow_system_theme: true,
             scale: None,
             page_width: None,
+            downscale_images: true,
+            horizontal_scroll: false,
+            line_numbers: false,
             font_opts: FontOptions::default(),
+            syntax: Default::default(),
+            typography: Default::default(),
             lines_to_scroll: LinesToScroll::default().0,
             keybindings: Default::default(),
             color_scheme: None,
@@ -106,6 +115,7 @@ fn config_overrides_default() {
         Opts {
             theme: ResolvedTheme::Dark.as_theme(),
             color_scheme: Some(ResolvedTheme::Dark),
+            follow_system_theme: false,
             ..Opts::mostly_default(&md_file)
         }
     );
@@ -128,6 +138,7 @@ fn config_overrides_default() {
         Opts {
             theme: ResolvedTheme::Light.as_theme(),
             color_scheme: Some(ResolvedTheme::Light),
+            follow_system_theme: false,
             ..Opts::mostly_default(&md_file)
         }
     );
@@ -172,6 +183,7 @@ fn from_cli() {
         Opts {
             theme: ResolvedTheme::Dark.as_theme(),
             color_scheme: Some(ResolvedTheme::Dark),
+            follow_system_theme: false,
             ..Opts::mostly_default(&md_file)
         }
     );
@@ -196,6 +208,7 @@ fn from_cli() {
             theme: ResolvedTheme::Dark.as_theme(),
             scale: Some(1.5),
             color_scheme: Some(ResolvedTheme::Dark),
+            follow_system_theme: false,
             ..Opts::mostly_default(&md_file)
         }
     );
@@ -227,11 +240,33 @@ fn cli_kitchen_sink() {
             scale: Some(1.5),
             theme: ResolvedTheme::Dark.as_theme(),
             color_scheme: Some(ResolvedTheme::Dark),
+            follow_system_theme: false,
             ..Opts::mostly_default(&md_file)
         }
     );
 }
 
+#[test]
+fn page_width_fits_margin() {
+    log::init();
+
+    let (_tmp, md_file) = temp_md_file();
+
+    // The default margin takes up 200px of the page
+    let load = |page_width: &str| {
+        Opts::parse_and_load_with_system_theme(
+            Cli::try_parse_from(gen_args(vec!["--page-width", page_width, &md_file]))
+                .unwrap()
+                .into_view()
+                .unwrap(),
+            config::Config::default(),
+            Some(ResolvedTheme::Light),
+        )
+    };
+    assert!(load("200").is_err());
+    assert_eq!(load("250").unwrap().page_width, Some(250.));
+}
+
 #[test]
 fn builtin_syntax_theme() {
     log::init();
@@ -260,6 +295,161 @@ fn builtin_syntax_theme() {
     );
 }
 
+#[test]
+fn both_themes_resolved() {
+    log::init();
+
+    let (_tmp, md_file) = temp_md_file();
+
+    let mut config = config::Config::default();
+    config.dark_theme = Some(config::OptionalTheme {
+        code_highlighter: Some(SyntaxTheme::Defaults(ThemeDefaults::Nord)),
+        ..Default::default()
+    });
+
+    let opts = Opts::parse_and_load_with_system_theme(
+        Cli::try_parse_from(gen_args(vec![&md_file]))
+            .unwrap()
+            .into_view()
+            .unwrap(),
+        config,
+        Some(ResolvedTheme::Light),
+    )
+    .unwrap();
+
+    // The dark theme is kept around to switch to at runtime
+    assert!(opts.follow_system_theme);
+    assert_eq!(opts.theme, Theme::light_default());
+    assert_eq!(
+        opts.theme_for(ResolvedTheme::Dark)
+            .code_highlighter
+            .name
+            .as_deref(),
+        Some("Nord")
+    );
+}
+
+#[test]
+fn named_theme() {
+    log::init();
+
+    let (_tmp, md_file) = temp_md_file();
+
+    let opts = Opts::parse_and_load_with_system_theme(
+        Cli::try_parse_from(gen_args(vec!["--theme", "nord", &md_file]))
+            .unwrap()
+            .into_view()
+            .unwrap(),
+        config::Config::default(),
+        Some(ResolvedTheme::Light),
+    )
+    .unwrap();
+    // Nord is a dark theme, so it takes the dark theme's place
+    assert_eq!(opts.color_scheme, Some(ResolvedTheme::Dark));
+    assert!(!opts.follow_system_theme);
+    assert_eq!(opts.theme, opts.dark_theme);
+    assert_eq!(opts.theme.background_color, 0x2E3440);
+    assert_eq!(opts.theme.heading_colors[0], Some(0x88C0D0));
+    assert_eq!(opts.light_theme, Theme::light_default());
+
+    let res = Opts::parse_and_load_with_system_theme(
+        Cli::try_parse_from(gen_args(vec!["--theme", "doesnt-exist", &md_file]))
+            .unwrap()
+            .into_view()
+            .unwrap(),
+        config::Config::default(),
+        Some(ResolvedTheme::Light),
+    );
+    assert!(res.is_err());
+
+    // Themes from the themes directory can be selected too
+    let themes_dir = tempfile::tempdir().unwrap();
+    let syntax_dir = tempfile::tempdir().unwrap();
+    let theme = "color-scheme = \"light\"\ntext-color = 0x123456\n";
+    std::fs::write(themes_dir.path().join("mine.toml"), theme).unwrap();
+    let opts = Opts::parse_and_load_with_theme_dirs(
+        Cli::try_parse_from(gen_args(vec!["--theme", "mine", &md_file]))
+            .unwrap()
+            .into_view()
+            .unwrap(),
+        config::Config::default(),
+        themes_dir.path(),
+        syntax_dir.path(),
+    )
+    .unwrap();
+    assert_eq!(opts.color_scheme, Some(ResolvedTheme::Light));
+    assert_eq!(opts.theme.text_color, 0x123456);
+
+    // Selecting themes by name in the config too
+    let config = config::Config::load_from_str(r#"theme = "nord""#).unwrap();
+    assert_eq!(config.theme, Some(ThemeType::Named("nord".to_owned())));
+    let config = config::Config::load_from_str(r#"theme = "Dark""#).unwrap();
+    assert_eq!(config.theme, Some(ThemeType::Dark));
+}
+
+#[test]
+fn theme_possible_values() {
+    let cmd = Cli::command();
+    let theme_arg = cmd
+        .get_arguments()
+        .find(|arg| arg.get_id() == "theme")
+        .unwrap();
+    let values: Vec<_> = theme_arg
+        .get_possible_values()
+        .into_iter()
+        .map(|value| value.get_name().to_owned())
+        .collect();
+    let bundled = themes::list_in(None).into_iter().map(|theme| theme.name);
+    let expected: Vec<_> = ["auto", "dark", "light"]
+        .map(String::from)
+        .into_iter()
+        .chain(bundled)
+        .collect();
+    assert_eq!(values, expected);
+
+    // Listing them doesn't stop other names from being accepted
+    let args = Cli::try_parse_from(gen_args(vec!["--theme", "mine", "file.md"])).unwrap();
+    assert_eq!(
+        args.into_view().unwrap().theme,
+        Some(ThemeType::Named("mine".to_owned()))
+    );
+}
+
+#[test]
+fn bundled_themes() {
+    for theme in themes::list_in(None) {
+        let themes::ThemeSource::Bundled(_) = theme.source else {
+            panic!("{} isn't bundled", theme.name);
+        };
+        let theme_file = theme.load().unwrap();
+        theme_file.into_theme(&BTreeMap::new()).unwrap();
+    }
+}
+
+#[test]
+fn user_themes() {
+    let dir = tempfile::tempdir().unwrap();
+    let theme = "color-scheme = \"dark\"\ntext-color = 0x123456\n";
+    std::fs::write(dir.path().join("nord.toml"), theme).unwrap();
+    std::fs::write(dir.path().join("mine.toml"), theme).unwrap();
+    std::fs::write(dir.path().join("notes.txt"), "not a theme").unwrap();
+
+    let themes = themes::list_in(Some(dir.path()));
+    let names: Vec<_> = themes.iter().map(|theme| theme.name.as_str()).collect();
+    assert_eq!(
+        names,
+        ["dracula", "gruvbox-dark", "mine", "nord", "solarized-light"]
+    );
+    // Themes in the directory replace the bundled ones with the same name
+    for theme in &themes {
+        let is_file = matches!(theme.source, themes::ThemeSource::File(_));
+        assert_eq!(is_file, ["mine", "nord"].contains(&theme.name.as_str()));
+    }
+    let nord = themes.iter().find(|theme| theme.name == "nord").unwrap();
+    let (_, nord) = nord.load().unwrap().into_theme(&BTreeMap::new()).unwrap();
+    assert_eq!(nord.text_color, 0x123456);
+}
+
 #[test]
 fn custom_syntax_theme() {
     fn config_with_theme_at(path: PathBuf) -> config::Config {
@@ -304,6 +494,45 @@ fn custom_syntax_theme() {
     );
 }
 
+#[test]
+fn user_syntax_themes() {
+    log::init();
+
+    let (_tmp, md_file) = temp_md_file();
+    let args = Cli::try_parse_from(gen_args(vec![&md_file]))
+        .unwrap()
+        .into_view()
+        .unwrap();
+    let config =
+        || config::Config::load_from_str(r#"light-theme.code-highlighter = "mine""#).unwrap();
+    assert_eq!(
+        config().light_theme.unwrap().code_highlighter,
+        Some(SyntaxTheme::User("mine".to_owned()))
+    );
+
+    // Only themes from the syntax themes directory get picked up
+    let themes_dir = tempfile::tempdir().unwrap();
+    let syntax_dir = tempfile::tempdir().unwrap();
+    let sample = Path::new("assets").join("test_data").join("sample.tmTheme");
+    std::fs::copy(&sample, themes_dir.path().join("mine.tmTheme")).unwrap();
+    let res = Opts::parse_and_load_with_theme_dirs(
+        args.clone(),
+        config(),
+        themes_dir.path(),
+        syntax_dir.path(),
+    );
+    assert!(res.is_err());
+
+    std::fs::copy(&sample, syntax_dir.path().join("mine.tmTheme")).unwrap();
+    let opts =
+        Opts::parse_and_load_with_theme_dirs(args, config(), themes_dir.path(), syntax_dir.path())
+            .unwrap();
+    assert_eq!(
+        opts.light_theme.code_highlighter.name.unwrap(),
+        "Example Color Scheme"
+    );
+}
+
 #[test]
 fn missing_file_arg() {
     log::init();
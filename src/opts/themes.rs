@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use super::config::OptionalTheme;
+use super::ResolvedTheme;
+use crate::color;
+use crate::utils::user_config_dir;
+
+use anyhow::Context;
+use serde::Deserialize;
+use syntect::highlighting::Theme as SyntectTheme;
+
+const BUNDLED: &[(&str, &str)] = &[
+    ("dracula", include_str!("../../assets/themes/dracula.toml")),
+    (
+        "gruvbox-dark",
+        include_str!("../../assets/themes/gruvbox-dark.toml"),
+    ),
+    ("nord", include_str!("../../assets/themes/nord.toml")),
+    (
+        "solarized-light",
+        include_str!("../../assets/themes/solarized-light.toml"),
+    ),
+];
+
+/// A complete theme that lives in its own file
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct ThemeFile {
+    /// Whether this is a dark or light theme. Colors that aren't set fall back to the default
+    /// theme of the same kind
+    pub color_scheme: ResolvedTheme,
+    #[serde(flatten)]
+    pub colors: OptionalTheme,
+}
+
+impl ThemeFile {
+    pub fn load_from_str(s: &str) -> anyhow::Result<Self> {
+        let theme = toml::from_str(s)?;
+        Ok(theme)
+    }
+
+    /// Finds the theme called `name` with themes in `user_dir` taking precedence over the bundled
+    /// ones
+    pub fn load_by_name(name: &str, user_dir: Option<&Path>) -> anyhow::Result<Self> {
+        let themes = list_in(user_dir);
+        let theme = themes
+            .iter()
+            .find(|theme| theme.name == name)
+            .with_context(|| {
+                let names: Vec<_> = themes.iter().map(|theme| theme.name.as_str()).collect();
+                format!(
+                    "Couldn't find a theme named \"{name}\". Available themes: [{}]",
+                    names.join(", ")
+                )
+            })?;
+        theme.load()
+    }
+
+    pub fn into_theme(
+        self,
+        user_syntax_themes: &BTreeMap<String, SyntectTheme>,
+    ) -> anyhow::Result<(ResolvedTheme, color::Theme)> {
+        let defaults = match self.color_scheme {
+            ResolvedTheme::Dark => color::Theme::dark_default(),
+            ResolvedTheme::Light => color::Theme::light_default(),
+        };
+        let theme = self.colors.merge(defaults, user_syntax_themes)?;
+        Ok((self.color_scheme, theme))
+    }
+}
+
+pub enum ThemeSource {
+    Bundled(&'static str),
+    File(PathBuf),
+}
+
+impl std::fmt::Display for ThemeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bundled(_) => f.write_str("bundled"),
+            Self::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+pub struct NamedTheme {
+    pub name: String,
+    pub source: ThemeSource,
+}
+
+impl NamedTheme {
+    pub fn load(&self) -> anyhow::Result<ThemeFile> {
+        let contents = match &self.source {
+            ThemeSource::Bundled(contents) => (*contents).to_owned(),
+            ThemeSource::File(path) => read_to_string(path)
+                .with_context(|| format!("Failed reading theme at {}", path.display()))?,
+        };
+        ThemeFile::load_from_str(&contents).with_context(|| {
+            format!(
+                "Failed loading theme \"{}\" from {}",
+                self.name, self.source
+            )
+        })
+    }
+}
+
+/// All of the themes that can be selected by name sorted by their names
+pub fn list() -> Vec<NamedTheme> {
+    list_in(user_config_dir("themes").as_deref())
+}
+
+/// The bundled themes along with the ones in `user_dir`, which take precedence over bundled themes
+/// with the same name
+pub fn list_in(user_dir: Option<&Path>) -> Vec<NamedTheme> {
+    let mut themes: Vec<_> = BUNDLED
+        .iter()
+        .map(|&(name, contents)| NamedTheme {
+            name: name.to_owned(),
+            source: ThemeSource::Bundled(contents),
+        })
+        .collect();
+
+    let user_files = user_dir
+        .and_then(|dir| dir.read_dir().ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"));
+    for path in user_files {
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let name = name.to_owned();
+        themes.retain(|theme| theme.name != name);
+        themes.push(NamedTheme {
+            name,
+            source: ThemeSource::File(path),
+        });
+    }
+
+    themes.sort_by(|a, b| a.name.cmp(&b.name));
+    themes
+}
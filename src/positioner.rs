@@ -1,19 +1,22 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 use anyhow::Context;
 use taffy::Taffy;
 
-use crate::image::Image;
-use crate::text::TextSystem;
-use crate::utils::{Align, Point, Rect, Size};
+use crate::text::{TextBox, TextSystem};
+use crate::utils::{Align, Float, Point, Rect, Size};
 use crate::{debug_impls, Element};
 
 pub const DEFAULT_PADDING: f32 = 5.;
 pub const DEFAULT_MARGIN: f32 = 100.;
+// Room to the left of a section's summary for its fold marker
+pub const SECTION_GUTTER: f32 = 25.;
+// Room left below elements that scroll horizontally for their scrollbar
+pub const HORIZONTAL_SCROLLBAR_HEIGHT: f32 = 8.;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Positioned<T> {
     pub inner: T,
     pub bounds: Option<Rect>,
@@ -38,6 +41,45 @@ impl<T> Positioned<T> {
     }
 }
 
+/// How far an element that's wider than the page is scrolled to the side
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HorizontalScroll {
+    pub offset: f32,
+    pub content_width: f32,
+}
+
+impl HorizontalScroll {
+    // Keeps the current offset when an element gets positioned again, but only if it still
+    // overflows
+    fn update(scroll: &mut Option<Self>, content_width: f32, visible_width: f32) {
+        *scroll = (content_width > visible_width).then(|| {
+            let offset = scroll.map_or(0., |scroll| scroll.offset);
+            Self {
+                offset: offset.min(content_width - visible_width),
+                content_width,
+            }
+        });
+    }
+
+    /// Scrolls by `delta` pixels where positive values scroll back towards the start. Returns
+    /// whether the offset changed
+    pub fn scroll_by(&mut self, delta: f32, visible_width: f32) -> bool {
+        let max_offset = (self.content_width - visible_width).max(0.);
+        let offset = (self.offset - delta).clamp(0., max_offset);
+        let changed = offset != self.offset;
+        self.offset = offset;
+        changed
+    }
+
+    /// The part of the scrollbar's track at the bottom of `bounds` that the thumb covers
+    pub fn thumb(&self, bounds: &Rect, height: f32) -> Rect {
+        let visible_width = bounds.size.0;
+        let width = visible_width * visible_width / self.content_width;
+        let x = bounds.pos.0 + self.offset / self.content_width * visible_width;
+        Rect::new((x, bounds.max().1 - height), (width, height))
+    }
+}
+
 #[derive(Default)]
 pub struct Positioner {
     pub screen_size: Size,
@@ -46,6 +88,14 @@ pub struct Positioner {
     pub page_width: f32,
     pub anchors: HashMap<String, f32>,
     pub taffy: Taffy,
+    // Code blocks and tables keep their natural width and scroll horizontally instead of wrapping
+    pub horizontal_scroll: bool,
+    // Space on either side of the page
+    pub margin: f32,
+    // Space left between elements
+    pub padding: f32,
+    // Regions taken up by floated images that text still has to wrap around
+    floats: Vec<(Float, Rect)>,
 }
 
 impl Positioner {
@@ -59,10 +109,61 @@ impl Positioner {
             screen_size,
             anchors: HashMap::new(),
             taffy,
+            horizontal_scroll: false,
+            margin: DEFAULT_MARGIN,
+            padding: DEFAULT_PADDING,
+            floats: Vec::new(),
+        }
+    }
+
+    /// Moves back up to `height` to position elements from there again
+    pub fn rewind(&mut self, height: f32) {
+        self.reserved_height = height;
+        self.floats.retain(|(_, bounds)| bounds.pos.1 < height);
+    }
+
+    /// Adds the room that a positioned element takes up to the reserved height and returns it.
+    /// Floated images only take up room beside the elements that follow them
+    pub fn advance(&mut self, element: &Positioned<Element>, zoom: f32) -> anyhow::Result<f32> {
+        if matches!(&element.inner, Element::Image(image) if image.float.is_some()) {
+            return Ok(0.);
+        }
+        let bounds = element
+            .bounds
+            .as_ref()
+            .context("Element didn't have bounds")?;
+        let height = bounds.size.1 + self.padding * self.hidpi_scale * zoom;
+        self.reserved_height += height;
+        Ok(height)
+    }
+
+    /// Moves below all of the floated images, so that nothing wraps around them anymore
+    pub fn clear_floats(&mut self, zoom: f32) {
+        let padding = self.padding * self.hidpi_scale * zoom;
+        for (_, bounds) in self.floats.drain(..) {
+            self.reserved_height = self.reserved_height.max(bounds.max().1 + padding);
         }
     }
 
-    // Positions the element but does not update reserved_height
+    // How far the floats beside the current height reach into the page from the left and right
+    fn float_insets(&self, zoom: f32) -> (f32, f32) {
+        let centering = (self.screen_size.0 - self.page_width).max(0.) / 2.;
+        let (left_edge, right_edge) = (
+            self.margin + centering,
+            self.screen_size.0 - self.margin - centering,
+        );
+        let padding = self.padding * self.hidpi_scale * zoom;
+        let y = self.reserved_height;
+        self.floats
+            .iter()
+            .filter(|(_, bounds)| bounds.pos.1 <= y && y < bounds.max().1)
+            .fold((0., 0.), |(left, right), (float, bounds)| match float {
+                Float::Left => (left.max(bounds.max().0 + padding - left_edge), right),
+                Float::Right => (left, right.max(right_edge - bounds.pos.0 + padding)),
+            })
+    }
+
+    // Positions the element but does not update reserved_height apart from clearing floats
     pub fn position(
         &mut self,
         text_system: &mut TextSystem,
@@ -73,17 +174,47 @@ impl Positioner {
 
         let bounds = match &mut element.inner {
             Element::TextBox(text_box) => {
-                let indent = text_box.indent;
-                let pos = (DEFAULT_MARGIN + indent + centering, self.reserved_height);
+                // Line numbers go in a gutter to the left of the text
+                let indent = text_box.indent + text_box.gutter_width(text_system, zoom);
+                // Right-to-left text gets indented from the right instead
+                let is_rtl = text_box.is_rtl();
+                let (mut left_indent, mut right_indent) =
+                    if is_rtl { (0., indent) } else { (indent, 0.) };
+                // Text wraps around any floats beside it unless that leaves hardly any room
+                let page_width = (self.screen_size.0 - 2. * (self.margin + centering)).max(0.);
+                let (left_float, right_float) = self.float_insets(zoom);
+                if page_width - left_float - right_float < page_width / 4. {
+                    self.clear_floats(zoom);
+                    text_box.right_float = 0.;
+                } else {
+                    left_indent += left_float;
+                    right_indent += right_float;
+                    text_box.right_float = right_float;
+                }
+                let pos = (self.margin + left_indent + centering, self.reserved_height);
+                let width =
+                    (self.screen_size.0 - pos.0 - self.margin - centering - right_indent).max(0.);
+                text_box.size_images(width, zoom);
 
-                let size = text_box.size(
-                    text_system,
-                    (
-                        (self.screen_size.0 - pos.0 - DEFAULT_MARGIN - centering).max(0.),
-                        f32::INFINITY,
-                    ),
-                    zoom,
-                );
+                let mut size = if self.horizontal_scroll && text_box.is_code_block {
+                    let (content_width, height) =
+                        text_box.size(text_system, (f32::INFINITY, f32::INFINITY), zoom);
+                    HorizontalScroll::update(&mut text_box.scroll, content_width, width);
+                    if text_box.scroll.is_some() {
+                        let scrollbar = HORIZONTAL_SCROLLBAR_HEIGHT * self.hidpi_scale * zoom;
+                        (width, height + scrollbar)
+                    } else {
+                        (content_width, height)
+                    }
+                } else {
+                    text_box.size(text_system, (width, f32::INFINITY), zoom)
+                };
+                // Code blocks fill out the page, so that hovering anywhere over their background
+                // reveals the copy button. Right-to-left text sits against the right edge
+                if text_box.is_code_block || is_rtl {
+                    size.0 = size.0.max(width);
+                }
+                text_box.position_images(text_system, pos, (width, f32::INFINITY), zoom);
 
                 if let Some(ref anchor_name) = text_box.is_anchor {
                     let _ = self.anchors.insert(anchor_name.clone(), pos.1);
@@ -96,118 +227,144 @@ impl Positioner {
                 (0., spacer.space * self.hidpi_scale * zoom),
             ),
             Element::Image(image) => {
+                let max_width =
+                    (self.screen_size.0.min(self.page_width) - 2. * self.margin).max(0.);
                 let size = image
-                    .size(
-                        (self.screen_size.0.min(self.page_width), self.screen_size.1),
-                        zoom,
-                    )
+                    .size((max_width, self.screen_size.1), zoom)
                     .unwrap_or_default();
-                match image.is_aligned {
-                    Some(Align::Center) => Rect::new(
-                        (self.screen_size.0 / 2. - size.0 / 2., self.reserved_height),
-                        size,
-                    ),
-                    _ => Rect::new((DEFAULT_MARGIN + centering, self.reserved_height), size),
+                let right_edge = self.screen_size.0 - self.margin - centering;
+                if let Some(float) = image.float {
+                    // Floats line up next to any others that are already on the same side
+                    let (left_float, right_float) = self.float_insets(zoom);
+                    let x = match float {
+                        Float::Left => self.margin + centering + left_float,
+                        Float::Right => right_edge - right_float - size.0,
+                    };
+                    let bounds = Rect::new((x, self.reserved_height), size);
+                    self.floats.push((float, bounds.clone()));
+                    bounds
+                } else {
+                    self.clear_floats(zoom);
+                    let x = match image.is_aligned {
+                        Some(Align::Center) => self.screen_size.0 / 2. - size.0 / 2.,
+                        Some(Align::Right) => right_edge - size.0,
+                        _ => self.margin + centering,
+                    };
+                    Rect::new((x, self.reserved_height), size)
                 }
             }
             Element::Table(table) => {
-                let pos = (DEFAULT_MARGIN + centering, self.reserved_height);
-                let layout = table.layout(
+                self.clear_floats(zoom);
+                let pos = (self.margin + centering, self.reserved_height);
+                let width = self.screen_size.0 - pos.0 - self.margin - centering;
+                let size = table.layout(
                     text_system,
                     &mut self.taffy,
-                    (
-                        self.screen_size.0 - pos.0 - DEFAULT_MARGIN - centering,
-                        f32::INFINITY,
-                    ),
+                    pos,
+                    (width, f32::INFINITY),
+                    self.hidpi_scale,
                     zoom,
+                    self.horizontal_scroll,
                 )?;
-                Rect::new(
-                    (DEFAULT_MARGIN + centering, self.reserved_height),
-                    layout.size,
-                )
-            }
-            Element::Row(row) => {
-                let mut reserved_width = DEFAULT_MARGIN + centering;
-                let mut inner_reserved_height: f32 = 0.;
-                let mut max_height: f32 = 0.;
-                let mut max_width: f32 = 0.;
-                for element in &mut row.elements {
-                    self.position(text_system, element, zoom)?;
-                    let element_bounds = element
-                        .bounds
-                        .as_mut()
-                        .context("Element didn't have bounds")?;
-
-                    let target_width = reserved_width
-                        + DEFAULT_PADDING * self.hidpi_scale * zoom
-                        + element_bounds.size.0;
-                    // Row would be too long with this element so add another line
-                    if target_width > self.screen_size.0 - DEFAULT_MARGIN - centering {
-                        max_width = max_width.max(reserved_width);
-                        reserved_width = DEFAULT_MARGIN
-                            + centering
-                            + DEFAULT_PADDING * self.hidpi_scale * zoom
-                            + element_bounds.size.0;
-                        inner_reserved_height +=
-                            max_height + DEFAULT_PADDING * self.hidpi_scale * zoom;
-                        max_height = element_bounds.size.1;
-                        element_bounds.pos.0 = DEFAULT_MARGIN + centering;
-                    } else {
-                        max_height = max_height.max(element_bounds.size.1);
-                        element_bounds.pos.0 = reserved_width;
-                        reserved_width = target_width;
-                    }
-                    element_bounds.pos.1 = self.reserved_height + inner_reserved_height;
+                HorizontalScroll::update(&mut table.scroll, size.0, width);
+                if table.scroll.is_some() {
+                    let scrollbar = HORIZONTAL_SCROLLBAR_HEIGHT * self.hidpi_scale * zoom;
+                    Rect::new(pos, (width, size.1 + scrollbar))
+                } else {
+                    Rect::new(pos, size)
                 }
-                max_width = max_width.max(reserved_width);
-                inner_reserved_height += max_height + DEFAULT_PADDING * self.hidpi_scale * zoom;
-                Rect::new(
-                    (DEFAULT_MARGIN + centering, self.reserved_height),
-                    (
-                        max_width - DEFAULT_MARGIN - centering,
-                        inner_reserved_height,
-                    ),
-                )
             }
             Element::Section(section) => {
-                let mut section_bounds =
-                    Rect::new((DEFAULT_MARGIN + centering, self.reserved_height), (0., 0.));
+                let gutter = SECTION_GUTTER * self.hidpi_scale * zoom;
+                let pos = (self.margin + centering - gutter, self.reserved_height);
                 if let Some(ref mut summary) = *section.summary {
                     self.position(text_system, summary, zoom)?;
-                    let element_size = summary
-                        .bounds
-                        .as_mut()
-                        .context("Element didn't have bounds")?
-                        .size;
-                    self.reserved_height +=
-                        element_size.1 + DEFAULT_PADDING * self.hidpi_scale * zoom;
-                    section_bounds.size.1 +=
-                        element_size.1 + DEFAULT_PADDING * self.hidpi_scale * zoom;
-                    section_bounds.size.0 = section_bounds.size.0.max(element_size.0)
+                    self.advance(summary, zoom)?;
                 }
-                for element in &mut section.elements {
-                    self.position(text_system, element, zoom)?;
-                    let element_size = element
-                        .bounds
-                        .as_mut()
-                        .context("Element didn't have bounds")?
-                        .size;
-                    self.reserved_height +=
-                        element_size.1 + DEFAULT_PADDING * self.hidpi_scale * zoom;
-                    if !*section.hidden.borrow() {
-                        section_bounds.size.1 +=
-                            element_size.1 + DEFAULT_PADDING * self.hidpi_scale * zoom;
-                        section_bounds.size.0 = section_bounds.size.0.max(element_size.0)
-                    }
-                }
-                self.reserved_height = section_bounds.pos.1;
-                section_bounds
+                self.position_section_elements(text_system, section, 0, false, zoom)?;
+                self.reserved_height = pos.1;
+                section_bounds(section, pos)?
             }
         };
         element.bounds = Some(bounds);
         Ok(())
     }
 
+    // Positions a section's elements starting from `start` with the reserved height already at
+    // where that element goes. When `resume` is set, the element at `start` has already been
+    // positioned and only what was appended to it gets positioned
+    fn position_section_elements(
+        &mut self,
+        text_system: &mut TextSystem,
+        section: &mut Section,
+        start: usize,
+        resume: bool,
+        zoom: f32,
+    ) -> anyhow::Result<()> {
+        // Floats within a folded section have nothing to wrap around
+        let floats = self.floats.len();
+        for (i, element) in section.elements.iter_mut().enumerate().skip(start) {
+            if i == start && resume {
+                self.position_appended(text_system, element, zoom)?;
+            } else {
+                self.position(text_system, element, zoom)?;
+            }
+            self.advance(element, zoom)?;
+        }
+        if *section.hidden.borrow() {
+            self.floats.truncate(floats);
+        }
+        Ok(())
+    }
+
+    /// Positions elements that were added to the end of an already positioned section (or to the
+    /// sections nested at its end) without positioning everything before them again. Leaves the
+    /// reserved height at the top of the element like `position()`
+    pub fn position_appended(
+        &mut self,
+        text_system: &mut TextSystem,
+        element: &mut Positioned<Element>,
+        zoom: f32,
+    ) -> anyhow::Result<()> {
+        let Some(bounds) = element.bounds.clone() else {
+            return self.position(text_system, element, zoom);
+        };
+        let Element::Section(section) = &mut element.inner else {
+            self.reserved_height = bounds.pos.1;
+            return Ok(());
+        };
+
+        // Picks back up at the first new element or at the nested section that it went into
+        let mut start = section
+            .elements
+            .iter()
+            .position(|element| element.bounds.is_none())
+            .unwrap_or(section.elements.len());
+        let resume = start > 0 && matches!(section.elements[start - 1].inner, Element::Section(_));
+        if resume {
+            start -= 1;
+        }
+        let previous = match start.checked_sub(1) {
+            Some(i) => section.elements.get(i),
+            None => section.summary.as_ref().as_ref(),
+        };
+        self.reserved_height = bounds.pos.1;
+        if let Some(previous) = previous {
+            self.reserved_height = previous
+                .bounds
+                .as_ref()
+                .context("Element didn't have bounds")?
+                .pos
+                .1;
+            self.advance(previous, zoom)?;
+        }
+        self.position_section_elements(text_system, section, start, resume, zoom)?;
+
+        self.reserved_height = bounds.pos.1;
+        element.bounds = Some(section_bounds(section, bounds.pos)?);
+        Ok(())
+    }
+
     // Resets reserved height and positions every element again
     pub fn reposition(
         &mut self,
@@ -215,18 +372,13 @@ impl Positioner {
         elements: &mut [Positioned<Element>],
         zoom: f32,
     ) -> anyhow::Result<()> {
-        self.reserved_height = DEFAULT_PADDING * self.hidpi_scale * zoom;
+        self.rewind(self.padding * self.hidpi_scale * zoom);
 
         for element in elements {
             self.position(text_system, element, zoom)?;
-            self.reserved_height += DEFAULT_PADDING * self.hidpi_scale * zoom
-                + element
-                    .bounds
-                    .as_ref()
-                    .context("Element didn't have bounds")?
-                    .size
-                    .1;
+            self.advance(element, zoom)?;
         }
+        self.clear_floats(zoom);
         Ok(())
     }
 }
@@ -257,21 +409,6 @@ impl fmt::Debug for Spacer {
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Row {
-    pub elements: Vec<Positioned<Element>>,
-    pub hidpi_scale: f32,
-}
-
-impl Row {
-    pub fn with_image(image: Image, hidpi_scale: f32) -> Self {
-        Self {
-            elements: vec![Positioned::new(image)],
-            hidpi_scale,
-        }
-    }
-}
-
 #[derive(Debug, PartialEq)]
 pub struct Section {
     pub elements: Vec<Positioned<Element>>,
@@ -289,4 +426,721 @@ impl Section {
             summary: Default::default(),
         }
     }
+
+    /// A section that folds everything after `heading` up to the next heading of equal or higher
+    /// level
+    pub fn heading(heading: TextBox, hidden: bool) -> Self {
+        let mut section = Self::bare(heading.hidpi_scale);
+        *section.hidden.borrow_mut() = hidden;
+        *section.summary = Some(Positioned::new(heading));
+        section
+    }
+
+    fn heading_box(&self) -> Option<&TextBox> {
+        match &*self.summary {
+            Some(Positioned {
+                inner: Element::TextBox(text_box),
+                ..
+            }) if text_box.heading_level.is_some() => Some(text_box),
+            _ => None,
+        }
+    }
+
+    pub fn heading_level(&self) -> Option<u8> {
+        self.heading_box()?.heading_level
+    }
+
+    pub fn heading_anchor(&self) -> Option<&str> {
+        self.heading_box()?.is_anchor.as_deref()
+    }
+}
+
+// Covers the summary and everything in the section unless it's folded up, including floats that
+// reach past the rest of it
+fn section_bounds(section: &Section, pos: Point) -> anyhow::Result<Rect> {
+    let hidden = *section.hidden.borrow();
+    let elements = section
+        .summary
+        .iter()
+        .chain(section.elements.iter().filter(|_| !hidden));
+    let mut max = pos;
+    for element in elements {
+        let bounds = element
+            .bounds
+            .as_ref()
+            .context("Element didn't have bounds")?;
+        // Nested elements can be indented, so measure up to their far edge
+        max = (max.0.max(bounds.max().0), max.1.max(bounds.max().1));
+    }
+    Ok(Rect::from_min_max(pos, max))
+}
+
+/// Pushes an element onto the document outline, nesting everything after a heading inside of that
+/// heading's section
+///
+/// Returns `true` when the element was pushed as a new top-level element and `false` when it ended
+/// up inside of the trailing section instead
+pub fn push_outlined(
+    elements: &mut Vec<Positioned<Element>>,
+    element: Element,
+    folded_headings: &HashSet<String>,
+) -> bool {
+    let level = match &element {
+        Element::TextBox(text_box) => text_box.heading_level,
+        _ => None,
+    };
+    let mut target = elements;
+    let mut top_level = true;
+    loop {
+        let descend = match target.last() {
+            Some(Positioned {
+                inner: Element::Section(section),
+                ..
+            }) => section
+                .heading_level()
+                .is_some_and(|open| level.map_or(true, |level| open < level)),
+            _ => false,
+        };
+        if !descend {
+            break;
+        }
+        let Some(Positioned {
+            inner: Element::Section(section),
+            ..
+        }) = target.last_mut()
+        else {
+            unreachable!("Checked above");
+        };
+        target = &mut section.elements;
+        top_level = false;
+    }
+
+    let element = match element {
+        Element::TextBox(text_box) if level.is_some() => {
+            let hidden = text_box
+                .is_anchor
+                .as_ref()
+                .is_some_and(|anchor| folded_headings.contains(anchor));
+            Element::Section(Section::heading(text_box, hidden))
+        }
+        element => element,
+    };
+    target.push(Positioned::new(element));
+    top_level
+}
+
+/// Collects the anchors of all currently folded headings
+pub fn folded_headings(elements: &[Positioned<Element>], folded: &mut HashSet<String>) {
+    for element in elements {
+        if let Element::Section(section) = &element.inner {
+            if *section.hidden.borrow() {
+                if let Some(anchor) = section.heading_anchor() {
+                    folded.insert(anchor.to_owned());
+                }
+            }
+            folded_headings(&section.elements, folded);
+        }
+    }
+}
+
+/// Scrolls the element under `loc` to the side if it's wider than the page. Returns whether
+/// anything moved
+pub fn scroll_horizontally(elements: &mut [Positioned<Element>], loc: Point, delta: f32) -> bool {
+    let Some(element) = elements.iter_mut().find(|element| {
+        element
+            .bounds
+            .as_ref()
+            .is_some_and(|bounds| bounds.contains(loc))
+    }) else {
+        return false;
+    };
+    let visible_width = element.bounds.as_ref().map_or(0., |bounds| bounds.size.0);
+    let scroll = match &mut element.inner {
+        Element::TextBox(text_box) => &mut text_box.scroll,
+        Element::Table(table) => &mut table.scroll,
+        Element::Section(section) => {
+            return !*section.hidden.borrow()
+                && scroll_horizontally(&mut section.elements, loc, delta);
+        }
+        Element::Image(_) | Element::Spacer(_) => return false,
+    };
+    scroll
+        .as_mut()
+        .is_some_and(|scroll| scroll.scroll_by(delta, visible_width))
+}
+
+/// Finds the code block under `loc` along with where it's drawn
+pub fn find_code_block(elements: &[Positioned<Element>], loc: Point) -> Option<(&TextBox, Rect)> {
+    let element = elements.iter().find(|element| {
+        element
+            .bounds
+            .as_ref()
+            .is_some_and(|bounds| bounds.contains(loc))
+    })?;
+    match &element.inner {
+        Element::TextBox(text_box) if text_box.is_code_block => {
+            Some((text_box, element.bounds.clone()?))
+        }
+        Element::Section(section) if !*section.hidden.borrow() => {
+            find_code_block(&section.elements, loc)
+        }
+        Element::Table(table) => {
+            // Cells are positioned as if the table wasn't scrolled at all
+            let offset = table.scroll_offset();
+            let loc = (loc.0 + offset, loc.1);
+            table.cells().find_map(|cell| {
+                let (text_box, mut bounds) = find_code_block(&cell.elements, loc)?;
+                bounds.pos.0 -= offset;
+                Some((text_box, bounds))
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Expands or collapses every section
+pub fn set_all_hidden(elements: &[Positioned<Element>], hidden: bool) {
+    for element in elements {
+        if let Element::Section(section) = &element.inner {
+            *section.hidden.borrow_mut() = hidden;
+            set_all_hidden(&section.elements, hidden);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+
+    use super::{
+        folded_headings, push_outlined, scroll_horizontally, set_all_hidden, HorizontalScroll,
+        Positioned, Positioner, Section,
+    };
+    use crate::image::{Image, ImageData, ImageSize};
+    use crate::table::{Table, TableCell};
+    use crate::test_utils::image::{Sample, SamplePng};
+    use crate::test_utils::text::text_system;
+    use crate::text::{Text, TextBox};
+    use crate::utils::{Float, Rect};
+    use crate::Element;
+
+    fn heading(level: u8, anchor: &str) -> Element {
+        let mut text_box = TextBox::new(vec![Text::new(anchor.into(), 1., [0.; 4])], 1.);
+        text_box.heading_level = Some(level);
+        text_box.is_anchor = Some(anchor.into());
+        text_box.into()
+    }
+
+    fn paragraph(text: &str) -> Element {
+        TextBox::new(vec![Text::new(text.into(), 1., [0.; 4])], 1.).into()
+    }
+
+    fn code_block(code: &str) -> Element {
+        let mut text_box = TextBox::new(vec![Text::new(code.into(), 1., [0.; 4])], 1.);
+        text_box.set_code_block(true);
+        text_box.into()
+    }
+
+    fn table(text: &str) -> Element {
+        let mut table = Table::new();
+        table.push_row(vec![TableCell {
+            elements: vec![Positioned::new(paragraph(text))],
+            ..Default::default()
+        }]);
+        table.into()
+    }
+
+    fn logo(float: Float, width: u32) -> Element {
+        let bytes = Sample::from(SamplePng::Bun).pre_decode();
+        let data = ImageData::load(&bytes, true).unwrap();
+        Image::from_image_data(Arc::new(Mutex::new(Some(data))), "bun".into(), 1.)
+            .with_size(ImageSize::width(width))
+            .with_float(float)
+            .into()
+    }
+
+    fn bounds(element: &Positioned<Element>) -> Rect {
+        element.bounds.clone().unwrap()
+    }
+
+    // Where each element starts and how wide it is
+    fn columns(elements: &[Positioned<Element>]) -> Vec<(f32, f32)> {
+        elements
+            .iter()
+            .map(|element| {
+                let bounds = bounds(element);
+                (bounds.pos.0, bounds.size.0)
+            })
+            .collect()
+    }
+
+    fn center(element: &Positioned<Element>) -> (f32, f32) {
+        let bounds = element.bounds.as_ref().unwrap();
+        let max = bounds.max();
+        ((bounds.pos.0 + max.0) / 2., (bounds.pos.1 + max.1) / 2.)
+    }
+
+    fn scroll_offset(element: &Positioned<Element>) -> Option<f32> {
+        let scroll = match &element.inner {
+            Element::TextBox(text_box) => text_box.scroll,
+            Element::Table(table) => table.scroll,
+            _ => unreachable!(),
+        };
+        scroll.map(|scroll| scroll.offset)
+    }
+
+    fn document() -> Vec<Element> {
+        vec![
+            paragraph("preface"),
+            heading(1, "intro"),
+            paragraph("a"),
+            heading(2, "usage"),
+            paragraph("b"),
+            heading(3, "details"),
+            paragraph("c"),
+            heading(2, "api"),
+            paragraph("d"),
+            heading(1, "end"),
+            paragraph("e"),
+        ]
+    }
+
+    fn outline(elements: &[Positioned<Element>]) -> String {
+        let parts: Vec<_> = elements
+            .iter()
+            .map(|element| match &element.inner {
+                Element::TextBox(text_box) => text_box.texts[0].text.clone(),
+                Element::Section(section) => {
+                    let folded = if *section.hidden.borrow() { "-" } else { "" };
+                    let anchor = section.heading_anchor().unwrap();
+                    format!("{folded}{anchor}[{}]", outline(&section.elements))
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+        parts.join(", ")
+    }
+
+    fn all_bounds(elements: &[Positioned<Element>], bounds: &mut Vec<Rect>) {
+        for element in elements {
+            bounds.push(element.bounds.clone().unwrap());
+            if let Element::Section(section) = &element.inner {
+                all_bounds(section.summary.as_slice(), bounds);
+                all_bounds(&section.elements, bounds);
+            }
+        }
+    }
+
+    #[test]
+    fn headings_nest_sections() {
+        let mut elements = Vec::new();
+        let top_level: Vec<_> = document()
+            .into_iter()
+            .map(|element| push_outlined(&mut elements, element, &HashSet::new()))
+            .collect();
+        assert_eq!(
+            outline(&elements),
+            "preface, intro[a, usage[b, details[c]], api[d]], end[e]"
+        );
+        // Only the headings at the top of the outline start new top-level elements
+        let expected = [
+            true, true, false, false, false, false, false, false, false, true, false,
+        ];
+        assert_eq!(top_level, expected);
+    }
+
+    #[test]
+    fn expand_and_collapse_all() {
+        let mut elements = Vec::new();
+        for element in document() {
+            push_outlined(&mut elements, element, &HashSet::new());
+        }
+
+        set_all_hidden(&elements, true);
+        assert_eq!(
+            outline(&elements),
+            "preface, -intro[a, -usage[b, -details[c]], -api[d]], -end[e]"
+        );
+        let mut folded = HashSet::new();
+        folded_headings(&elements, &mut folded);
+        let expected: HashSet<_> = ["intro", "usage", "details", "api", "end"]
+            .map(String::from)
+            .into();
+        assert_eq!(folded, expected);
+
+        // Folded headings stay folded when the document gets outlined again
+        folded.remove("usage");
+        let mut reloaded = Vec::new();
+        for element in document() {
+            push_outlined(&mut reloaded, element, &folded);
+        }
+        assert_eq!(
+            outline(&reloaded),
+            "preface, -intro[a, usage[b, -details[c]], -api[d]], -end[e]"
+        );
+
+        set_all_hidden(&reloaded, false);
+        assert_eq!(
+            outline(&reloaded),
+            "preface, intro[a, usage[b, details[c]], api[d]], end[e]"
+        );
+    }
+
+    #[test]
+    fn appended_elements_match_full_layout() {
+        let mut text_system = text_system();
+        let mut positioner = Positioner::new((800., 600.), 1., 800.);
+
+        let mut expected = Vec::new();
+        for element in document() {
+            push_outlined(&mut expected, element, &HashSet::new());
+        }
+        positioner
+            .reposition(&mut text_system, &mut expected, 1.)
+            .unwrap();
+        let full_height = positioner.reserved_height;
+
+        // Streams the elements in a few at a time like while the document is being loaded
+        let mut positioner = Positioner::new((800., 600.), 1., 800.);
+        let mut elements = Vec::new();
+        let document = document();
+        let mut document = document.into_iter().peekable();
+        while document.peek().is_some() {
+            let mut first_unpositioned = elements.len();
+            for element in document.by_ref().take(2) {
+                let len = elements.len();
+                if !push_outlined(&mut elements, element, &HashSet::new()) {
+                    first_unpositioned = first_unpositioned.min(len - 1);
+                }
+            }
+            for element in &mut elements[first_unpositioned..] {
+                positioner
+                    .position_appended(&mut text_system, element, 1.)
+                    .unwrap();
+                positioner.advance(element, 1.).unwrap();
+            }
+        }
+        positioner.clear_floats(1.);
+
+        let (mut actual_bounds, mut expected_bounds) = (Vec::new(), Vec::new());
+        all_bounds(&elements, &mut actual_bounds);
+        all_bounds(&expected, &mut expected_bounds);
+        assert_eq!(actual_bounds, expected_bounds);
+        assert_eq!(positioner.reserved_height, full_height);
+
+        // Laying everything out again, like after zooming in, moves every element
+        positioner
+            .reposition(&mut text_system, &mut elements, 2.)
+            .unwrap();
+        let mut expected = Vec::new();
+        for element in self::document() {
+            push_outlined(&mut expected, element, &HashSet::new());
+        }
+        positioner
+            .reposition(&mut text_system, &mut expected, 2.)
+            .unwrap();
+        let (mut actual_bounds, mut expected_bounds) = (Vec::new(), Vec::new());
+        all_bounds(&elements, &mut actual_bounds);
+        all_bounds(&expected, &mut expected_bounds);
+        assert_eq!(actual_bounds, expected_bounds);
+    }
+
+    #[test]
+    fn padding_between_elements() {
+        let mut text_system = text_system();
+        let mut positioner = Positioner::new((800., 600.), 1., 800.);
+        positioner.padding = 0.;
+
+        let mut elements: Vec<_> = ["one", "two"]
+            .map(|text| Positioned::new(paragraph(text)))
+            .into();
+        positioner
+            .reposition(&mut text_system, &mut elements, 2.)
+            .unwrap();
+        let (first, second) = (bounds(&elements[0]), bounds(&elements[1]));
+        assert_eq!(first.pos.1, 0.);
+        assert_eq!(second.pos.1, first.max().1);
+
+        positioner.padding = 10.;
+        positioner
+            .reposition(&mut text_system, &mut elements, 2.)
+            .unwrap();
+        let (first, second) = (bounds(&elements[0]), bounds(&elements[1]));
+        assert_eq!(first.pos.1, 20.);
+        assert_eq!(second.pos.1, first.max().1 + 20.);
+    }
+
+    #[test]
+    fn horizontal_scroll_clamps() {
+        let mut scroll = HorizontalScroll {
+            offset: 0.,
+            content_width: 1000.,
+        };
+        assert!(scroll.scroll_by(-300., 400.));
+        assert_eq!(scroll.offset, 300.);
+        assert!(scroll.scroll_by(-1000., 400.));
+        assert_eq!(scroll.offset, 600.);
+        assert!(!scroll.scroll_by(-10., 400.));
+        assert!(scroll.scroll_by(1000., 400.));
+        assert_eq!(scroll.offset, 0.);
+        assert!(!scroll.scroll_by(10., 400.));
+
+        // Content that shrinks keeps as much of the offset as still fits
+        let mut scroll = Some(HorizontalScroll {
+            offset: 600.,
+            content_width: 1000.,
+        });
+        HorizontalScroll::update(&mut scroll, 700., 400.);
+        assert_eq!(scroll.map(|scroll| scroll.offset), Some(300.));
+        HorizontalScroll::update(&mut scroll, 300., 400.);
+        assert!(scroll.is_none());
+    }
+
+    #[test]
+    fn scrolls_hovered_element() {
+        let mut text_system = text_system();
+        let mut positioner = Positioner::new((800., 600.), 1., 800.);
+        positioner.horizontal_scroll = true;
+        let long_line = "wide ".repeat(100);
+        let mut elements: Vec<_> = [
+            code_block(&long_line),
+            table(&long_line),
+            code_block("short"),
+        ]
+        .into_iter()
+        .map(Positioned::new)
+        .collect();
+        positioner
+            .reposition(&mut text_system, &mut elements, 1.)
+            .unwrap();
+        let offsets = |elements: &[Positioned<Element>]| -> Vec<_> {
+            elements.iter().map(scroll_offset).collect()
+        };
+        assert_eq!(offsets(&elements), [Some(0.), Some(0.), None]);
+
+        let centers: Vec<_> = elements.iter().map(center).collect();
+        assert!(scroll_horizontally(&mut elements, centers[0], -50.));
+        assert_eq!(offsets(&elements), [Some(50.), Some(0.), None]);
+        assert!(scroll_horizontally(&mut elements, centers[1], -20.));
+        assert_eq!(offsets(&elements), [Some(50.), Some(20.), None]);
+        assert!(!scroll_horizontally(&mut elements, centers[2], -20.));
+        assert!(!scroll_horizontally(&mut elements, (400., 10_000.), -20.));
+        assert_eq!(offsets(&elements), [Some(50.), Some(20.), None]);
+    }
+
+    #[test]
+    fn wraps_without_horizontal_scroll() {
+        let mut text_system = text_system();
+        let mut positioner = Positioner::new((800., 600.), 1., 800.);
+        let mut elements = vec![
+            Positioned::new(code_block(&"wide ".repeat(100))),
+            Positioned::new(code_block("short")),
+        ];
+        positioner
+            .reposition(&mut text_system, &mut elements, 1.)
+            .unwrap();
+
+        assert_eq!(scroll_offset(&elements[0]), None);
+        let [wide, short] = [&elements[0], &elements[1]].map(|e| e.bounds.clone().unwrap());
+        assert_eq!(wide.size.0, short.size.0);
+        assert!(wide.size.1 > 2. * short.size.1);
+        let wide_center = center(&elements[0]);
+        assert!(!scroll_horizontally(&mut elements, wide_center, -50.));
+    }
+
+    // The page is 800px wide with 100px margins, so there's 600px of room between 100 and 700.
+    // Floats leave 5px of padding beside them
+
+    #[test]
+    fn floats_narrow_text() {
+        let mut text_system = text_system();
+        let mut positioner = Positioner::new((800., 600.), 1., 800.);
+        let mut elements: Vec<_> = [
+            logo(Float::Left, 100),
+            code_block("beside"),
+            code_block(&"wraps ".repeat(100)),
+        ]
+        .into_iter()
+        .map(Positioned::new)
+        .collect();
+        positioner
+            .reposition(&mut text_system, &mut elements, 1.)
+            .unwrap();
+        let float = bounds(&elements[0]);
+        assert_eq!(columns(&elements[..2]), [(100., 100.), (205., 495.)]);
+        // Floats don't take up any room of their own
+        assert_eq!(bounds(&elements[1]).pos.1, float.pos.1);
+        // Text that starts beside the float stays narrowed even where it runs on past it
+        let below = bounds(&elements[2]);
+        assert!(below.pos.1 < float.max().1 && below.max().1 > float.max().1);
+        assert_eq!((below.pos.0, below.size.0), (205., 495.));
+
+        let mut elements: Vec<_> = [logo(Float::Right, 100), code_block("beside")]
+            .into_iter()
+            .map(Positioned::new)
+            .collect();
+        positioner
+            .reposition(&mut text_system, &mut elements, 1.)
+            .unwrap();
+        assert_eq!(columns(&elements), [(600., 100.), (100., 495.)]);
+        let Element::TextBox(text_box) = &elements[1].inner else {
+            unreachable!();
+        };
+        assert_eq!(text_box.right_float, 105.);
+    }
+
+    #[test]
+    fn stacked_floats() {
+        let mut text_system = text_system();
+        let mut positioner = Positioner::new((800., 600.), 1., 800.);
+        let mut elements: Vec<_> = [
+            logo(Float::Right, 100),
+            logo(Float::Right, 100),
+            logo(Float::Left, 100),
+            logo(Float::Left, 50),
+            code_block("beside"),
+        ]
+        .into_iter()
+        .map(Positioned::new)
+        .collect();
+        positioner
+            .reposition(&mut text_system, &mut elements, 1.)
+            .unwrap();
+        // Floats on the same side line up next to each other with the text between all of them
+        assert_eq!(
+            columns(&elements),
+            [
+                (600., 100.),
+                (495., 100.),
+                (100., 100.),
+                (205., 50.),
+                (260., 230.),
+            ]
+        );
+        let tops: HashSet<_> = elements
+            .iter()
+            .map(|element| bounds(element).pos.1.to_bits())
+            .collect();
+        assert_eq!(tops.len(), 1);
+    }
+
+    #[test]
+    fn cramped_text_clears_floats() {
+        let mut text_system = text_system();
+        let mut positioner = Positioner::new((800., 600.), 1., 800.);
+        // Only 90px would be left between these, which is under a quarter of the page
+        let mut elements: Vec<_> = [
+            logo(Float::Left, 250),
+            logo(Float::Right, 250),
+            code_block("beside"),
+        ]
+        .into_iter()
+        .map(Positioned::new)
+        .collect();
+        positioner
+            .reposition(&mut text_system, &mut elements, 1.)
+            .unwrap();
+        let [left, right, text] = [0, 1, 2].map(|i| bounds(&elements[i]));
+        assert_eq!((left.pos.0, right.pos.0), (100., 450.));
+        // So the text moves down below them instead
+        assert_eq!((text.pos.0, text.size.0), (100., 600.));
+        assert_eq!(text.pos.1, left.max().1.max(right.max().1) + 5.);
+    }
+
+    #[test]
+    fn rewind_drops_floats() {
+        let mut text_system = text_system();
+        let mut positioner = Positioner::new((800., 600.), 1., 800.);
+        let top = positioner.reserved_height;
+        let mut float = Positioned::new(logo(Float::Left, 100));
+        positioner
+            .position(&mut text_system, &mut float, 1.)
+            .unwrap();
+        positioner.advance(&float, 1.).unwrap();
+
+        let mut text = Positioned::new(code_block("beside"));
+        let mut column_at = |height| {
+            positioner.rewind(height);
+            positioner
+                .position(&mut text_system, &mut text, 1.)
+                .unwrap();
+            columns(std::slice::from_ref(&text))[0]
+        };
+        // Rewinding to somewhere beside the float keeps it around
+        assert_eq!(column_at(top + 1.), (205., 495.));
+        // But going back up to where it started drops it
+        assert_eq!(column_at(top), (100., 600.));
+        assert_eq!(column_at(top + 1.), (100., 600.));
+    }
+
+    #[test]
+    fn hidden_sections_drop_floats() {
+        let mut text_system = text_system();
+        let mut positioner = Positioner::new((800., 600.), 1., 800.);
+        let mut section = Section::bare(1.);
+        section.elements = vec![
+            Positioned::new(logo(Float::Left, 100)),
+            Positioned::new(code_block("inside")),
+        ];
+        *section.hidden.borrow_mut() = true;
+        let mut elements = vec![
+            Positioned::new(section),
+            Positioned::new(code_block("after")),
+        ];
+        positioner
+            .reposition(&mut text_system, &mut elements, 1.)
+            .unwrap();
+        // Nothing in the folded section is shown, so there's nothing to wrap around
+        let Element::Section(section) = &elements[0].inner else {
+            unreachable!();
+        };
+        let float = bounds(&section.elements[0]);
+        assert_eq!(bounds(&elements[0]).size.1, 0.);
+        let after = bounds(&elements[1]);
+        assert_eq!((after.pos.0, after.size.0), (100., 600.));
+        assert!(after.pos.1 < float.max().1);
+
+        // Once it's expanded the section reaches down past its float
+        *section.hidden.borrow_mut() = false;
+        positioner
+            .reposition(&mut text_system, &mut elements, 1.)
+            .unwrap();
+        let Element::Section(section) = &elements[0].inner else {
+            unreachable!();
+        };
+        let float = bounds(&section.elements[0]);
+        assert_eq!(columns(&section.elements), [(100., 100.), (205., 495.)]);
+        assert_eq!(bounds(&elements[0]).max().1, float.max().1);
+        let after = bounds(&elements[1]);
+        assert_eq!((after.pos.0, after.size.0), (100., 600.));
+        assert!(after.pos.1 > float.max().1);
+    }
+
+    #[test]
+    fn floats_keep_their_side_in_table_cells() {
+        let mut text_system = text_system();
+        let mut positioner = Positioner::new((800., 600.), 1., 800.);
+        let mut table = Table::new();
+        table.push_row(vec![TableCell {
+            elements: vec![
+                Positioned::new(paragraph("a line of text that's wider than the logo")),
+                Positioned::new(logo(Float::Right, 100)),
+            ],
+            ..Default::default()
+        }]);
+        let mut elements = vec![Positioned::new(table)];
+        positioner
+            .reposition(&mut text_system, &mut elements, 1.)
+            .unwrap();
+        let Element::Table(table) = &elements[0].inner else {
+            unreachable!();
+        };
+        let cell = &table.rows[0][0];
+        let [text, logo] = [0, 1].map(|i| bounds(&cell.elements[i]));
+        assert!(text.size.0 > 100.);
+        assert_eq!(logo.size.0, 100.);
+        assert_eq!(logo.max().0, text.max().0);
+        assert!(logo.pos.1 >= text.max().1);
+    }
 }
@@ -2,17 +2,22 @@ use std::borrow::Cow;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
-use crate::color::{native_color, Theme};
-use crate::fonts::get_fonts;
-use crate::image::ImageRenderer;
-use crate::metrics::{histogram, HistTag};
+use crate::color::{mix_colors, native_color, Theme};
+use crate::fonts::{get_fonts, FontFamilies};
+use crate::image::{Image, ImageRenderer};
+use crate::interpreter::EventLoopCallback;
+use crate::metrics::{gauge, histogram, GaugeTag, HistTag};
 use crate::opts::FontOptions;
-use crate::positioner::{Positioned, Positioner, DEFAULT_MARGIN};
+use crate::positioner::{
+    HorizontalScroll, Positioned, Positioner, DEFAULT_MARGIN, HORIZONTAL_SCROLLBAR_HEIGHT,
+};
 use crate::selection::Selection;
-use crate::table::TABLE_ROW_GAP;
-use crate::text::{CachedTextArea, TextCache, TextSystem};
+use crate::table::{Table, TableCell, TABLE_COL_GAP, TABLE_ROW_GAP};
+use crate::text::{
+    CachedTextArea, Decoration, DiffLine, Text, TextBox, TextCache, TextRenderers, TextSystem,
+};
 use crate::utils::{Point, Rect, Size};
-use crate::Element;
+use crate::{Element, InlyneEvent};
 
 use anyhow::{Context, Ok};
 use bytemuck::{Pod, Zeroable};
@@ -23,8 +28,13 @@ use lyon::path::Polygon;
 use lyon::tessellation::*;
 use wgpu::util::DeviceExt;
 use wgpu::{BindGroup, Buffer, IndexFormat, MultisampleState, TextureFormat};
+use winit::event_loop::EventLoopProxy;
 use winit::window::Window;
 
+// Images further than a screen away from the viewport get their textures evicted once the total
+// size of all uploaded image textures exceeds this
+pub const IMAGE_TEXTURE_BUDGET: usize = 256 * 1_024 * 1_024;
+
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable, Debug)]
 pub struct Vertex {
@@ -40,6 +50,7 @@ pub struct Renderer {
     pub render_pipeline: wgpu::RenderPipeline,
     pub queue: wgpu::Queue,
     pub text_system: TextSystem,
+    pub text_renderers: TextRenderers,
     pub scroll_y: f32,
     pub lyon_buffer: VertexBuffers<Vertex, u16>,
     pub hidpi_scale: f32,
@@ -48,6 +59,17 @@ pub struct Renderer {
     pub theme: Theme,
     pub zoom: f32,
     pub positioner: Positioner,
+    // Text shown next to the cursor along with the cursor's position on screen
+    pub tooltip: Option<(Point, String)>,
+    // The bounds of the code block under the cursor, which reveals its copy button
+    pub hovered_code_block: Option<Rect>,
+    // Where the copy button was last drawn
+    pub copy_button: Option<Rect>,
+    // Everything drawn outside of this gets cut off while set. Used for elements that scroll
+    // horizontally
+    clip: Option<Rect>,
+    // Lets images that get decoded again at a higher resolution report back when they're done
+    event_proxy: EventLoopProxy<InlyneEvent>,
 }
 
 impl Renderer {
@@ -65,6 +87,7 @@ impl Renderer {
         hidpi_scale: f32,
         page_width: f32,
         font_opts: FontOptions,
+        event_proxy: EventLoopProxy<InlyneEvent>,
     ) -> anyhow::Result<Self> {
         let size = window.inner_size();
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
@@ -158,13 +181,18 @@ impl Renderer {
         let mut text_atlas = TextAtlas::new(&device, &queue, surface_format);
         let text_renderer =
             TextRenderer::new(&mut text_atlas, &device, MultisampleState::default(), None);
-        let text_cache = Arc::new(Mutex::new(TextCache::new()));
+        let overlay_renderer =
+            TextRenderer::new(&mut text_atlas, &device, MultisampleState::default(), None);
+        let text_cache = Arc::new(Mutex::new(TextCache::new(FontFamilies::new(&font_opts))));
         let text_system = TextSystem {
             font_system,
-            swash_cache,
+            text_cache,
+        };
+        let text_renderers = TextRenderers {
             text_renderer,
+            overlay_renderer,
             text_atlas,
-            text_cache,
+            swash_cache,
         };
 
         let lyon_buffer: VertexBuffers<Vertex, u16> = VertexBuffers::new();
@@ -178,6 +206,7 @@ impl Renderer {
             render_pipeline,
             queue,
             text_system,
+            text_renderers,
             scroll_y: 0.,
             lyon_buffer,
             hidpi_scale,
@@ -186,6 +215,11 @@ impl Renderer {
             image_renderer,
             theme,
             positioner,
+            tooltip: None,
+            hovered_code_block: None,
+            copy_button: None,
+            clip: None,
+            event_proxy,
         })
     }
 
@@ -195,6 +229,15 @@ impl Renderer {
             return Ok(());
         }
         let height = (screen_height / self.positioner.reserved_height) * screen_height;
+        if let Some(track_color) = self.theme.scrollbar_track_color {
+            self.draw_rectangle(
+                Rect::new(
+                    (screen_width - DEFAULT_MARGIN / 4., 0.),
+                    (DEFAULT_MARGIN / 4., screen_height),
+                ),
+                native_color(track_color, &self.surface_format),
+            )?;
+        }
         self.draw_rectangle(
             Rect::new(
                 (
@@ -203,8 +246,94 @@ impl Renderer {
                 ),
                 (DEFAULT_MARGIN / 4., height),
             ),
-            [0.3, 0.3, 0.3, 1.0],
+            native_color(self.theme.scrollbar_thumb_color, &self.surface_format),
+        )?;
+        Ok(())
+    }
+
+    // Draws the scrollbar for an element that's wider than the page at the bottom of its bounds
+    fn draw_horizontal_scrollbar(
+        &mut self,
+        scroll: &HorizontalScroll,
+        bounds: &Rect,
+    ) -> anyhow::Result<()> {
+        let height = HORIZONTAL_SCROLLBAR_HEIGHT / 2. * self.hidpi_scale * self.zoom;
+        let scrolled = Rect::new((bounds.pos.0, bounds.pos.1 - self.scroll_y), bounds.size);
+        if let Some(track_color) = self.theme.scrollbar_track_color {
+            let track = Rect::new(
+                (scrolled.pos.0, scrolled.max().1 - height),
+                (scrolled.size.0, height),
+            );
+            self.draw_rectangle(track, native_color(track_color, &self.surface_format))?;
+        }
+        let thumb_color = native_color(self.theme.scrollbar_thumb_color, &self.surface_format);
+        self.draw_rectangle(scroll.thumb(&scrolled, height), thumb_color)
+    }
+
+    // Fills in the header and striped body rows of a table. Each row spans from halfway into the
+    // gap above it to halfway into the gap below it
+    fn draw_table_rows(&mut self, table: &Table, left: f32, right: f32) -> anyhow::Result<()> {
+        let header_color = self.theme.table_header_color;
+        let stripe_color = self.theme.table_stripe_color;
+        if header_color.is_none() && stripe_color.is_none() {
+            return Ok(());
+        }
+
+        let row_top = |row: &Vec<TableCell>| {
+            row.iter()
+                .filter_map(|cell| cell.bounds.as_ref())
+                .map(|bounds| bounds.pos.1 - TABLE_ROW_GAP / 2.)
+                .reduce(f32::min)
+        };
+        let bottom = table
+            .cells()
+            .filter_map(|cell| cell.bounds.as_ref())
+            .fold(0., |bottom: f32, bounds| bottom.max(bounds.max().1))
+            + TABLE_ROW_GAP / 2.;
+        let rows: Vec<_> = table
+            .headers
+            .iter()
+            .map(|row| (row_top(row), header_color))
+            .chain(table.rows.iter().enumerate().map(|(i, row)| {
+                let color = if i % 2 == 1 { stripe_color } else { None };
+                (row_top(row), color)
+            }))
+            .collect();
+
+        for (i, &(top, color)) in rows.iter().enumerate() {
+            let (Some(top), Some(color)) = (top, color) else {
+                continue;
+            };
+            let next_top = rows[i + 1..].iter().find_map(|&(top, _)| top);
+            let min = (left, top - self.scroll_y);
+            let max = (right, next_top.unwrap_or(bottom) - self.scroll_y);
+            let color = native_color(color, &self.surface_format);
+            self.draw_rectangle(Rect::from_min_max(min, max), color)?;
+        }
+        Ok(())
+    }
+
+    // Draws the copy button of the hovered code block as two overlapping pages
+    fn draw_copy_button(&mut self) -> anyhow::Result<()> {
+        let Some(Rect { pos, size }) = self.copy_button.clone() else {
+            return Ok(());
+        };
+        let scale = self.hidpi_scale * self.zoom;
+        let background = native_color(self.theme.background_color, &self.surface_format);
+        let text_color = native_color(self.theme.text_color, &self.surface_format);
+        let pos = (pos.0, pos.1 - self.scroll_y);
+        self.draw_rectangle(Rect::new(pos, size), background)?;
+        self.stroke_rectangle(
+            Rect::new(pos, size),
+            mix_colors(background, text_color, 0.3),
+            1. * scale,
         )?;
+        let page = 9. * scale;
+        let back = (pos.0 + 5. * scale, pos.1 + 5. * scale);
+        let front = (back.0 + 3. * scale, back.1 + 3. * scale);
+        self.stroke_rectangle(Rect::new(back, (page, page)), text_color, 1. * scale)?;
+        self.draw_rectangle(Rect::new(front, (page, page)), background)?;
+        self.stroke_rectangle(Rect::new(front, (page, page)), text_color, 1. * scale)?;
         Ok(())
     }
 
@@ -220,9 +349,8 @@ impl Renderer {
         let mut text_areas: Vec<CachedTextArea> = Vec::new();
         let screen_size = self.screen_size();
         for element in elements.iter() {
-            let Rect { mut pos, size } =
-                element.bounds.as_ref().context("Element not positioned")?;
-            let mut scrolled_pos = (pos.0, pos.1 - self.scroll_y);
+            let Rect { pos, size } = element.bounds.as_ref().context("Element not positioned")?;
+            let scrolled_pos = (pos.0, pos.1 - self.scroll_y);
             // Dont render off screen elements
             if scrolled_pos.1 + size.1 <= 0. {
                 continue;
@@ -234,259 +362,77 @@ impl Renderer {
 
             match &element.inner {
                 Element::TextBox(text_box) => {
-                    let box_size = text_box.font_size * self.hidpi_scale * self.zoom * 0.75;
-
-                    if text_box.is_checkbox.is_some() {
-                        pos.0 += box_size * 1.5;
-                        scrolled_pos.0 += box_size * 1.5;
-                    }
-
-                    let bounds = (
-                        (screen_size.0 - pos.0 - DEFAULT_MARGIN - centering).max(0.),
-                        f32::INFINITY,
+                    // Right-to-left text is indented from the right, so it gets exactly its bounds
+                    let max_width = if text_box.is_rtl() {
+                        size.0
+                    } else {
+                        (screen_size.0
+                            - pos.0
+                            - self.positioner.margin
+                            - centering
+                            - text_box.right_float)
+                            .max(0.)
+                    };
+                    text_areas.append(
+                        &mut self.render_text_box(text_box, *pos, *size, max_width, selection)?,
                     );
-
-                    let areas = text_box.text_areas(
-                        &mut self.text_system,
-                        pos,
-                        bounds,
-                        self.zoom,
-                        self.scroll_y,
-                    );
-                    text_areas.push(areas.clone());
-                    if text_box.is_code_block || text_box.is_quote_block.is_some() {
-                        let color = if let Some(bg_color) = text_box.background_color {
-                            bg_color
-                        } else {
-                            native_color(self.theme.quote_block_color, &self.surface_format)
-                        };
-
-                        let mut min = (
-                            (scrolled_pos.0 - 10.),
-                            scrolled_pos.1 - 5. * self.hidpi_scale * self.zoom,
-                        );
-                        let max = (
-                            min.0
-                                + bounds
-                                    .0
-                                    .max(text_box.size(&mut self.text_system, bounds, self.zoom).0)
-                                + 10.,
-                            min.1 + size.1 + 12. * self.hidpi_scale * self.zoom,
-                        );
-                        if let Some(nest) = text_box.is_quote_block {
-                            min.0 -= (nest - 1) as f32 * DEFAULT_MARGIN / 2.;
-                        }
-                        if min.0 < screen_size.0 - DEFAULT_MARGIN - centering {
-                            self.draw_rectangle(Rect::from_min_max(min, max), color)?;
-                        }
-                    }
-                    if let Some(nest) = text_box.is_quote_block {
-                        for n in 0..nest {
-                            let nest_indent = n as f32 * DEFAULT_MARGIN / 2.;
-                            let min = (
-                                (scrolled_pos.0
-                                    - 10.
-                                    - 5. * self.hidpi_scale * self.zoom
-                                    - nest_indent)
-                                    .min(screen_size.0 - DEFAULT_MARGIN - centering),
-                                scrolled_pos.1,
-                            );
-                            let max = (
-                                (scrolled_pos.0 - 10. - nest_indent)
-                                    .min(screen_size.0 - DEFAULT_MARGIN - centering),
-                                min.1 + size.1 + 5. * self.hidpi_scale * self.zoom,
-                            );
-                            self.draw_rectangle(
-                                Rect::from_min_max(min, max),
-                                native_color(self.theme.select_color, &self.surface_format),
-                            )?;
-                        }
-                    }
-                    if let Some(is_checked) = text_box.is_checkbox {
-                        let line_height = text_box.line_height(self.zoom);
-                        let min = (
-                            scrolled_pos.0 - box_size * 1.5,
-                            scrolled_pos.1 + line_height / 2. - box_size / 2.,
-                        );
-                        let max = (
-                            scrolled_pos.0 + box_size - box_size * 1.5,
-                            scrolled_pos.1 + line_height / 2. + box_size / 2.,
-                        );
-                        if max.0 < screen_size.0 - DEFAULT_MARGIN - centering {
-                            if is_checked {
-                                self.draw_rectangle(
-                                    Rect::from_min_max(min, max),
-                                    native_color(self.theme.checkbox_color, &self.surface_format),
-                                )?;
-                                self.draw_tick(
-                                    min,
-                                    box_size,
-                                    native_color(self.theme.text_color, &self.surface_format),
-                                    2. * self.hidpi_scale * self.zoom,
-                                )?;
-                            }
-                            self.stroke_rectangle(
-                                Rect::from_min_max(min, max),
-                                native_color(self.theme.text_color, &self.surface_format),
-                                1. * self.hidpi_scale * self.zoom,
-                            )?;
-                        }
-                    }
-                    for line in text_box.render_lines(
-                        &mut self.text_system,
-                        scrolled_pos,
-                        bounds,
-                        self.zoom,
-                        &areas,
-                    ) {
-                        let min = (line.min.0, line.min.1);
-                        let max = (line.max.0, line.max.1 + 2. * self.hidpi_scale * self.zoom);
-                        self.draw_rectangle(Rect::from_min_max(min, max), line.color)?;
-                    }
-                    if let Some(selection_rects) = text_box.render_selection(
-                        &mut self.text_system,
-                        pos,
-                        bounds,
-                        self.zoom,
-                        selection,
-                    ) {
-                        for rect in selection_rects {
-                            self.draw_rectangle(
-                                Rect::from_min_max(
-                                    (rect.pos.0, rect.pos.1 - self.scroll_y),
-                                    (rect.max().0, rect.max().1 - self.scroll_y),
-                                ),
-                                native_color(self.theme.select_color, &self.surface_format),
-                            )?;
-                        }
-                    }
                 }
                 Element::Table(table) => {
-                    let bounds = (
-                        (screen_size.0 - pos.0 - DEFAULT_MARGIN - centering).max(0.),
-                        f32::INFINITY,
-                    );
-                    let layout = table.layout(
-                        &mut self.text_system,
-                        &mut self.positioner.taffy,
-                        bounds,
-                        self.zoom,
-                    )?;
-
-                    for (col, node) in layout.headers.iter().enumerate() {
-                        if let Some(text_box) = table.headers.get(col) {
-                            text_areas.push(text_box.text_areas(
-                                &mut self.text_system,
-                                (pos.0 + node.location.x, pos.1 + node.location.y),
-                                (node.size.width, f32::MAX),
-                                self.zoom,
-                                self.scroll_y,
-                            ));
-                            if let Some(selection_rects) = text_box.render_selection(
-                                &mut self.text_system,
-                                (pos.0 + node.location.x, pos.1 + node.location.y),
-                                (node.size.width, node.size.height),
-                                self.zoom,
-                                selection,
-                            ) {
-                                for rect in selection_rects {
-                                    self.draw_rectangle(
-                                        Rect::from_min_max(
-                                            (rect.pos.0, rect.pos.1 - self.scroll_y),
-                                            (rect.max().0, rect.max().1 - self.scroll_y),
-                                        ),
-                                        native_color(self.theme.select_color, &self.surface_format),
-                                    )?;
-                                }
+                    // Scrolled tables get shifted over and cut off at the edges of their bounds
+                    let offset = table.scroll_offset();
+                    if table.scroll.is_some() {
+                        self.clip = Some(Rect::new((pos.0, 0.), (size.0, screen_size.1)));
+                    }
+                    let left = pos.0 - offset;
+                    let right = table
+                        .cells()
+                        .filter_map(|cell| cell.bounds.as_ref())
+                        .fold(pos.0, |right, bounds| right.max(bounds.max().0))
+                        - offset;
+                    self.draw_table_rows(table, left, right)?;
+                    for cell in table.cells() {
+                        for element in &cell.elements {
+                            let Rect { pos, size } =
+                                element.bounds.as_ref().context("Element not positioned")?;
+                            let scrolled_y = pos.1 - self.scroll_y;
+                            if scrolled_y + size.1 <= 0. || scrolled_y >= screen_size.1 {
+                                continue;
+                            }
+                            if let Element::TextBox(text_box) = &element.inner {
+                                let pos = (pos.0 - offset, pos.1);
+                                text_areas
+                                    .append(&mut self.render_text_box(
+                                        text_box, pos, *size, size.0, selection,
+                                    )?);
                             }
                         }
                     }
-                    let y = layout
-                        .headers
-                        .last()
-                        .map(|last_header_node| {
-                            last_header_node.location.y
-                                + last_header_node.size.height
-                                + TABLE_ROW_GAP / 2.0
-                        })
-                        .unwrap_or(0.0);
-                    let x = layout
-                        .headers
-                        .last()
-                        .map(|f| f.location.x + f.size.width)
-                        .unwrap_or(0.);
-                    {
-                        let min = (
-                            scrolled_pos.0.max(DEFAULT_MARGIN + centering),
-                            scrolled_pos.1 + y,
-                        );
+
+                    // Draw a line under every cell, splitting the gaps between them evenly
+                    let border_color = self.theme.table_border_color;
+                    let border_color = native_color(
+                        border_color.unwrap_or(self.theme.text_color),
+                        &self.surface_format,
+                    );
+                    let header_count = table.headers.iter().map(Vec::len).sum();
+                    for (i, cell) in table.cells().enumerate() {
+                        let Some(bounds) = &cell.bounds else {
+                            continue;
+                        };
+                        let thickness = if i < header_count { 2. } else { 1. };
+                        let y = bounds.max().1 - self.scroll_y + TABLE_ROW_GAP / 2.;
+                        let min = ((bounds.pos.0 - offset - TABLE_COL_GAP / 2.).max(left), y);
                         let max = (
-                            (scrolled_pos.0 + x),
-                            scrolled_pos.1 + y + 2. * self.hidpi_scale * self.zoom,
+                            (bounds.max().0 - offset + TABLE_COL_GAP / 2.).min(right),
+                            y + thickness * self.hidpi_scale * self.zoom,
                         );
-                        self.draw_rectangle(
-                            Rect::from_min_max(min, max),
-                            native_color(self.theme.text_color, &self.surface_format),
-                        )?;
+                        self.draw_rectangle(Rect::from_min_max(min, max), border_color)?;
                     }
 
-                    for (row, node_row) in layout.rows.iter().enumerate() {
-                        for (col, node) in node_row.iter().enumerate() {
-                            if let Some(row) = table.rows.get(row) {
-                                if let Some(text_box) = row.get(col) {
-                                    text_areas.push(text_box.text_areas(
-                                        &mut self.text_system,
-                                        (pos.0 + node.location.x, pos.1 + node.location.y),
-                                        (node.size.width, f32::MAX),
-                                        self.zoom,
-                                        self.scroll_y,
-                                    ));
-
-                                    if let Some(selection_rects) = text_box.render_selection(
-                                        &mut self.text_system,
-                                        (pos.0 + node.location.x, pos.1 + node.location.y),
-                                        (node.size.width, node.size.height),
-                                        self.zoom,
-                                        selection,
-                                    ) {
-                                        for rect in selection_rects {
-                                            self.draw_rectangle(
-                                                Rect::from_min_max(
-                                                    (rect.pos.0, rect.pos.1 - self.scroll_y),
-                                                    (rect.max().0, rect.max().1 - self.scroll_y),
-                                                ),
-                                                native_color(
-                                                    self.theme.select_color,
-                                                    &self.surface_format,
-                                                ),
-                                            )?;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        let last_row_node = node_row.last().unwrap();
-                        let y = last_row_node.location.y
-                            + last_row_node.size.height
-                            + TABLE_ROW_GAP / 2.;
-                        let x = node_row
-                            .last()
-                            .map(|f| f.location.x + f.size.width)
-                            .unwrap_or(0.);
-                        {
-                            let min = (
-                                scrolled_pos.0.max(DEFAULT_MARGIN + centering),
-                                scrolled_pos.1 + y,
-                            );
-                            let max = (
-                                scrolled_pos.0 + x,
-                                scrolled_pos.1 + y + 1. * self.hidpi_scale * self.zoom,
-                            );
-                            self.draw_rectangle(
-                                Rect::from_min_max(min, max),
-                                native_color(self.theme.text_color, &self.surface_format),
-                            )?;
-                        }
+                    self.clip = None;
+                    if let Some(scroll) = &table.scroll {
+                        let bounds = Rect::new(*pos, *size);
+                        self.draw_horizontal_scrollbar(scroll, &bounds)?;
                     }
                 }
                 Element::Image(_) => {}
@@ -495,22 +441,22 @@ impl Renderer {
                         self.draw_rectangle(
                             Rect::new(
                                 (
-                                    DEFAULT_MARGIN + centering,
+                                    self.positioner.margin + centering,
                                     scrolled_pos.1 + size.1 / 2.
                                         - 2. * self.hidpi_scale * self.zoom,
                                 ),
                                 (
-                                    screen_size.0 - 2. * (DEFAULT_MARGIN + centering),
+                                    screen_size.0 - 2. * (self.positioner.margin + centering),
                                     2. * self.hidpi_scale * self.zoom,
                                 ),
                             ),
-                            native_color(self.theme.text_color, &self.surface_format),
+                            native_color(
+                                self.theme.hr_color.unwrap_or(self.theme.text_color),
+                                &self.surface_format,
+                            ),
                         )?;
                     }
                 }
-                Element::Row(row) => {
-                    text_areas.append(&mut self.render_elements(&row.elements, selection)?)
-                }
                 Element::Section(section) => {
                     if let Some(ref summary) = *section.summary {
                         let bounds = summary.bounds.as_ref().unwrap();
@@ -548,6 +494,311 @@ impl Renderer {
         Ok(text_areas)
     }
 
+    // Renders everything belonging to a text box apart from the text itself and its line numbers,
+    // which are returned to get drawn later on. Text wraps at `max_width`
+    fn render_text_box(
+        &mut self,
+        text_box: &TextBox,
+        mut pos: Point,
+        size: Size,
+        max_width: f32,
+        selection: &mut Selection,
+    ) -> anyhow::Result<Vec<CachedTextArea>> {
+        let screen_size = self.screen_size();
+        let centering = (screen_size.0 - self.page_width).max(0.) / 2.;
+        let mut scrolled_pos = (pos.0, pos.1 - self.scroll_y);
+        let box_size = text_box.font_size * self.hidpi_scale * self.zoom * 0.75;
+
+        // Right-to-left text has its checkbox and quote bars on the right
+        let is_rtl = text_box.is_rtl();
+        let mut max_width = max_width;
+        if text_box.is_checkbox.is_some() {
+            if !is_rtl {
+                pos.0 += box_size * 1.5;
+                scrolled_pos.0 += box_size * 1.5;
+            }
+            max_width -= box_size * 1.5;
+        }
+
+        let bounds = (max_width.max(0.), f32::INFINITY);
+
+        // Code blocks that scroll horizontally have their text shifted over while everything
+        // else stays in place
+        let box_pos = scrolled_pos;
+        let box_bounds = Rect::new(pos, size);
+        pos.0 -= text_box.scroll_offset();
+        scrolled_pos.0 -= text_box.scroll_offset();
+
+        let mut areas =
+            text_box.text_areas(&mut self.text_system, pos, bounds, self.zoom, self.scroll_y);
+        let mut script_areas =
+            text_box.script_areas(&mut self.text_system, pos, bounds, self.zoom, self.scroll_y);
+        let mut gutter_area = None;
+        if text_box.is_code_block || text_box.is_quote_block.is_some() {
+            let color = if let Some(bg_color) = text_box.background_color {
+                bg_color
+            } else {
+                native_color(self.theme.quote_block_color, &self.surface_format)
+            };
+
+            let gutter = text_box.gutter_width(&mut self.text_system, self.zoom);
+            let mut min = (
+                (box_pos.0 - 10. - gutter),
+                box_pos.1 - 5. * self.hidpi_scale * self.zoom,
+            );
+            let width = if text_box.scroll.is_some() {
+                bounds.0
+            } else {
+                bounds
+                    .0
+                    .max(text_box.size(&mut self.text_system, bounds, self.zoom).0)
+            };
+            let mut max = (
+                box_pos.0 + width,
+                min.1 + size.1 + 12. * self.hidpi_scale * self.zoom,
+            );
+            if let Some(nest) = text_box.is_quote_block {
+                let nest_indent = (nest - 1) as f32 * DEFAULT_MARGIN / 2.;
+                if is_rtl {
+                    min.0 = box_pos.0;
+                    max.0 += 10. + nest_indent;
+                } else {
+                    min.0 -= nest_indent;
+                }
+            }
+            if min.0 < screen_size.0 - self.positioner.margin - centering {
+                self.draw_rectangle(Rect::from_min_max(min, max), color)?;
+            }
+
+            if text_box.line_numbers || !text_box.highlighted_lines.is_empty() || text_box.is_diff {
+                let text_color = native_color(self.theme.text_color, &self.surface_format);
+                let line_spans = text_box.line_spans(&mut self.text_system, bounds, self.zoom);
+                let mut diff_lines = text_box.diff_lines();
+                for (i, (top, height)) in line_spans.iter().enumerate() {
+                    let diff_line = diff_lines.next().flatten().filter(|_| text_box.is_diff);
+                    let diff_color = diff_line.map(|diff_line| {
+                        let diff_color = match diff_line {
+                            DiffLine::Added => self.theme.diff_added_color,
+                            DiffLine::Removed => self.theme.diff_removed_color,
+                            DiffLine::Hunk => self.theme.diff_hunk_color,
+                        };
+                        native_color(diff_color, &self.surface_format)
+                    });
+                    let line_color = if text_box.is_highlighted(i + 1) {
+                        let line_color = diff_color.unwrap_or(color);
+                        Some(mix_colors(line_color, text_color, 0.12))
+                    } else {
+                        diff_color
+                    };
+                    if let Some(line_color) = line_color {
+                        let y = box_pos.1 + top;
+                        self.draw_rectangle(
+                            Rect::from_min_max((min.0, y), (max.0, y + height)),
+                            line_color,
+                        )?;
+                    }
+                }
+                if text_box.line_numbers {
+                    let number_color = mix_colors(color, text_color, 0.5);
+                    let numbers = text_box.line_number_box(&line_spans, number_color, self.zoom);
+                    gutter_area = Some(numbers.text_areas(
+                        &mut self.text_system,
+                        (box_bounds.pos.0 - gutter, box_bounds.pos.1),
+                        (f32::INFINITY, f32::INFINITY),
+                        self.zoom,
+                        self.scroll_y,
+                    ));
+                }
+            }
+        }
+        let outer_clip = self.clip.clone();
+        if text_box.scroll.is_some() {
+            let clip = Rect::new((box_pos.0, 0.), (bounds.0, screen_size.1));
+            self.clip = Some(match &outer_clip {
+                Some(outer) => outer
+                    .intersection(&clip)
+                    .unwrap_or(Rect::new(clip.pos, (0., 0.))),
+                None => clip,
+            });
+        }
+        if let Some(clip) = &self.clip {
+            areas.clip(clip);
+            for area in &mut script_areas {
+                area.clip(clip);
+            }
+        }
+        if let Some(nest) = text_box.is_quote_block {
+            for n in 0..nest {
+                let nest_indent = n as f32 * DEFAULT_MARGIN / 2.;
+                let bar_width = 5. * self.hidpi_scale * self.zoom;
+                let (left, right) = if is_rtl {
+                    let left = scrolled_pos.0 + max_width + 10. + nest_indent;
+                    (left, left + bar_width)
+                } else {
+                    let edge = screen_size.0 - self.positioner.margin - centering;
+                    let right = scrolled_pos.0 - 10. - nest_indent;
+                    ((right - bar_width).min(edge), right.min(edge))
+                };
+                let min = (left, scrolled_pos.1);
+                let max = (right, min.1 + size.1 + 5. * self.hidpi_scale * self.zoom);
+                self.draw_rectangle(
+                    Rect::from_min_max(min, max),
+                    native_color(self.theme.select_color, &self.surface_format),
+                )?;
+            }
+        }
+        if let Some(is_checked) = text_box.is_checkbox {
+            let line_height = text_box.line_height(self.zoom);
+            let left = if is_rtl {
+                scrolled_pos.0 + max_width + box_size * 0.5
+            } else {
+                scrolled_pos.0 - box_size * 1.5
+            };
+            let min = (left, scrolled_pos.1 + line_height / 2. - box_size / 2.);
+            let max = (
+                left + box_size,
+                scrolled_pos.1 + line_height / 2. + box_size / 2.,
+            );
+            if max.0 < screen_size.0 - self.positioner.margin - centering {
+                if is_checked {
+                    self.draw_rectangle(
+                        Rect::from_min_max(min, max),
+                        native_color(self.theme.checkbox_color, &self.surface_format),
+                    )?;
+                    self.draw_tick(
+                        min,
+                        box_size,
+                        native_color(self.theme.text_color, &self.surface_format),
+                        2. * self.hidpi_scale * self.zoom,
+                    )?;
+                }
+                self.stroke_rectangle(
+                    Rect::from_min_max(min, max),
+                    native_color(self.theme.text_color, &self.surface_format),
+                    1. * self.hidpi_scale * self.zoom,
+                )?;
+            }
+        }
+        for (rect, decoration) in
+            text_box.render_decorations(&mut self.text_system, bounds, self.zoom, &areas)
+        {
+            match decoration {
+                Decoration::Highlight(color) => self.draw_rectangle(rect, color)?,
+                Decoration::KeyCap(color) => {
+                    let padding = 2. * self.hidpi_scale * self.zoom;
+                    let key_cap = Rect::from_min_max(
+                        (rect.pos.0 - padding, rect.pos.1 + padding / 2.),
+                        (rect.max().0 + padding, rect.max().1 - padding / 2.),
+                    );
+                    self.stroke_rectangle(key_cap, color, 1. * self.hidpi_scale * self.zoom)?;
+                }
+            }
+        }
+        for line in text_box.render_lines(
+            &mut self.text_system,
+            scrolled_pos,
+            bounds,
+            self.zoom,
+            &areas,
+        ) {
+            let min = (line.min.0, line.min.1);
+            let max = (line.max.0, line.max.1 + 2. * self.hidpi_scale * self.zoom);
+            self.draw_rectangle(Rect::from_min_max(min, max), line.color)?;
+        }
+        if let Some(selected) =
+            text_box.render_selection(&mut self.text_system, pos, bounds, self.zoom, selection)
+        {
+            for rect in selected.rects {
+                let rect = Rect::from_min_max(
+                    (rect.pos.0, rect.pos.1 - self.scroll_y),
+                    (rect.max().0, rect.max().1 - self.scroll_y),
+                );
+                self.draw_rectangle(
+                    rect,
+                    native_color(self.theme.select_color, &self.surface_format),
+                )?;
+            }
+            // Selected text gets drawn in its own color
+            if let Some(color) = self.theme.select_text_color {
+                (areas, script_areas) = text_box.selected_text_areas(
+                    &mut self.text_system,
+                    pos,
+                    bounds,
+                    self.zoom,
+                    self.scroll_y,
+                    &selected.ranges,
+                    native_color(color, &self.surface_format),
+                );
+                if let Some(clip) = &self.clip {
+                    areas.clip(clip);
+                    for area in &mut script_areas {
+                        area.clip(clip);
+                    }
+                }
+            }
+        }
+        self.clip = outer_clip;
+        if let Some(scroll) = &text_box.scroll {
+            self.draw_horizontal_scrollbar(scroll, &box_bounds)?;
+        }
+        if text_box.is_code_block && self.hovered_code_block.as_ref() == Some(&box_bounds) {
+            let scale = self.hidpi_scale * self.zoom;
+            let size = 22. * scale;
+            let pos = (
+                box_bounds.max().0 - size - 4. * scale,
+                box_bounds.pos.1 - scale,
+            );
+            self.copy_button = Some(Rect::new(pos, (size, size)));
+        }
+        Ok([Some(areas), gutter_area]
+            .into_iter()
+            .flatten()
+            .chain(script_areas)
+            .collect())
+    }
+
+    fn render_tooltip(&mut self) -> anyhow::Result<Option<CachedTextArea>> {
+        let Some((cursor, title)) = self.tooltip.clone() else {
+            return Ok(None);
+        };
+        let screen_size = self.screen_size();
+        let text_color = native_color(self.theme.text_color, &self.surface_format);
+        let text_box = TextBox::new(
+            vec![Text::new(title, self.hidpi_scale, text_color)],
+            self.hidpi_scale,
+        );
+        let bounds = (screen_size.0 / 2., f32::INFINITY);
+        let size = text_box.size(&mut self.text_system, bounds, self.zoom);
+
+        // Keep the tooltip below and to the right of the cursor while it fits on screen
+        let padding = 4. * self.hidpi_scale * self.zoom;
+        let offset = 16. * self.hidpi_scale * self.zoom;
+        let outer_size = (size.0 + 2. * padding, size.1 + 2. * padding);
+        let x = (cursor.0 + offset / 2.)
+            .min(screen_size.0 - outer_size.0)
+            .max(0.);
+        let y = if cursor.1 + offset + outer_size.1 > screen_size.1 {
+            cursor.1 - outer_size.1
+        } else {
+            cursor.1 + offset
+        };
+
+        let rect = Rect::new((x, y), outer_size);
+        self.draw_rectangle(
+            rect.clone(),
+            native_color(self.theme.background_color, &self.surface_format),
+        )?;
+        self.stroke_rectangle(rect, text_color, 1. * self.hidpi_scale * self.zoom)?;
+        let area = text_box.text_areas(
+            &mut self.text_system,
+            (x + padding, y + padding),
+            bounds,
+            self.zoom,
+            0.,
+        );
+        Ok(Some(area))
+    }
+
     fn draw_hidden_marker(
         &mut self,
         pos: Point,
@@ -585,6 +836,13 @@ impl Renderer {
     }
 
     fn draw_rectangle(&mut self, rect: Rect, color: [f32; 4]) -> anyhow::Result<()> {
+        let rect = match &self.clip {
+            Some(clip) => match rect.intersection(clip) {
+                Some(rect) => rect,
+                None => return Ok(()),
+            },
+            None => rect,
+        };
         let min = point(rect.pos.0, rect.pos.1, self.screen_size());
         let max = point(rect.max().0, rect.max().1, self.screen_size());
         let mut fill_tessellator = FillTessellator::new();
@@ -600,6 +858,14 @@ impl Renderer {
     }
 
     fn stroke_rectangle(&mut self, rect: Rect, color: [f32; 4], width: f32) -> anyhow::Result<()> {
+        // Outlines can't be cut in half, so they're only drawn when they're fully visible
+        if self
+            .clip
+            .as_ref()
+            .is_some_and(|clip| rect.intersection(clip).as_ref() != Some(&rect))
+        {
+            return Ok(());
+        }
         let mut stroke_tessellator = StrokeTessellator::new();
         let screen_size = self.screen_size();
         stroke_tessellator.tessellate_rectangle(
@@ -645,15 +911,17 @@ impl Renderer {
         Ok(())
     }
 
+    // Images are shifted over by `scroll_x` and get cut off at the current clip
     fn image_bindgroups(
         &mut self,
         elements: &mut [Positioned<Element>],
-    ) -> Vec<(Arc<BindGroup>, Buffer)> {
+        scroll_x: f32,
+    ) -> Vec<(Arc<BindGroup>, Buffer, Option<Rect>)> {
         let screen_size = self.screen_size();
         let mut bind_groups = Vec::new();
         for element in elements.iter_mut() {
             let Rect { pos, size } = element.bounds.as_ref().unwrap();
-            let pos = (pos.0, pos.1 - self.scroll_y);
+            let pos = (pos.0 - scroll_x, pos.1 - self.scroll_y);
             if pos.1 + size.1 <= 0. {
                 continue;
             } else if pos.1 >= screen_size.1 {
@@ -661,69 +929,29 @@ impl Renderer {
             }
             match &mut element.inner {
                 Element::Image(ref mut image) => {
-                    if let Some(bind_group) = image.bind_group.clone().or_else(|| {
-                        image.create_bind_group(
-                            &self.device,
-                            &self.queue,
-                            &self.image_renderer.sampler,
-                            &self.image_renderer.bindgroup_layout,
-                        )
-                    }) {
-                        let vertex_buf =
-                            ImageRenderer::vertex_buf(&self.device, pos, *size, screen_size);
-                        bind_groups.push((bind_group.clone(), vertex_buf));
-                    }
+                    bind_groups.extend(self.image_bindgroup(image, pos, *size));
                 }
-                Element::Row(ref mut row) => {
-                    for element in row.elements.iter_mut() {
-                        let Rect { pos, size } = element.bounds.as_ref().unwrap();
-                        let pos = (pos.0, pos.1 - self.scroll_y);
-                        if let Element::Image(ref mut image) = &mut element.inner {
-                            if let Some(bind_group) = image.bind_group.clone().or_else(|| {
-                                image.create_bind_group(
-                                    &self.device,
-                                    &self.queue,
-                                    &self.image_renderer.sampler,
-                                    &self.image_renderer.bindgroup_layout,
-                                )
-                            }) {
-                                let vertex_buf = ImageRenderer::vertex_buf(
-                                    &self.device,
-                                    pos,
-                                    *size,
-                                    screen_size,
-                                );
-                                bind_groups.push((bind_group.clone(), vertex_buf));
-                            }
-                        }
+                Element::TextBox(ref mut text_box) => {
+                    for image in &mut text_box.images {
+                        let Some(Rect { pos, size }) = image.bounds.clone() else {
+                            continue;
+                        };
+                        let pos = (pos.0 - scroll_x, pos.1 - self.scroll_y);
+                        bind_groups.extend(self.image_bindgroup(&mut image.inner, pos, size));
                     }
                 }
-                Element::Section(ref mut section) => {
-                    if *section.hidden.borrow() {
-                        continue;
+                Element::Section(ref mut section) if !*section.hidden.borrow() => {
+                    bind_groups.append(&mut self.image_bindgroups(&mut section.elements, scroll_x));
+                }
+                Element::Table(ref mut table) => {
+                    let offset = table.scroll_offset();
+                    if table.scroll.is_some() {
+                        self.clip = Some(Rect::new(pos, *size));
                     }
-                    for element in section.elements.iter_mut() {
-                        let Rect { pos, size } = element.bounds.as_ref().unwrap();
-                        let pos = (pos.0, pos.1 - self.scroll_y);
-                        if let Element::Image(ref mut image) = &mut element.inner {
-                            if let Some(bind_group) = image.bind_group.clone().or_else(|| {
-                                image.create_bind_group(
-                                    &self.device,
-                                    &self.queue,
-                                    &self.image_renderer.sampler,
-                                    &self.image_renderer.bindgroup_layout,
-                                )
-                            }) {
-                                let vertex_buf = ImageRenderer::vertex_buf(
-                                    &self.device,
-                                    pos,
-                                    *size,
-                                    screen_size,
-                                );
-                                bind_groups.push((bind_group.clone(), vertex_buf));
-                            }
-                        }
+                    for cell in table.cells_mut() {
+                        bind_groups.append(&mut self.image_bindgroups(&mut cell.elements, offset));
                     }
+                    self.clip = None;
                 }
                 _ => {}
             }
@@ -731,12 +959,40 @@ impl Renderer {
         bind_groups
     }
 
+    // Uploads the image if it isn't already and places it at `pos` on the screen
+    fn image_bindgroup(
+        &mut self,
+        image: &mut Image,
+        pos: Point,
+        size: Size,
+    ) -> Option<(Arc<BindGroup>, Buffer, Option<Rect>)> {
+        let event_proxy = &self.event_proxy;
+        image.ensure_resolution(size.0, || Box::new(EventLoopCallback(event_proxy.clone())));
+        let bind_group = image.bind_group.clone().or_else(|| {
+            image.create_bind_group(
+                &self.device,
+                &self.queue,
+                &self.image_renderer.sampler,
+                &self.image_renderer.bindgroup_layout,
+            )
+        })?;
+        let vertex_buf = ImageRenderer::vertex_buf(&self.device, pos, size, self.screen_size());
+        Some((bind_group, vertex_buf, self.clip.clone()))
+    }
+
+    fn enforce_texture_budget(&mut self, elements: &mut [Positioned<Element>]) {
+        let viewport = (self.scroll_y, self.scroll_y + self.screen_height());
+        let used = evict_textures(elements, viewport, IMAGE_TEXTURE_BUDGET);
+        gauge!(GaugeTag::ImageTextureBytes).set(used as f64);
+    }
+
     pub fn redraw(
         &mut self,
         elements: &mut [Positioned<Element>],
         selection: &mut Selection,
     ) -> anyhow::Result<()> {
         selection.text.clear();
+        self.copy_button = None;
         let frame = self
             .surface
             .get_current_texture()
@@ -768,27 +1024,68 @@ impl Renderer {
             });
 
         // Prepare image bind groups for drawing
-        let image_bindgroups = self.image_bindgroups(elements);
+        let image_bindgroups = self.image_bindgroups(elements, 0.);
+        self.enforce_texture_budget(elements);
+
+        // The copy button and tooltip get their own buffers, so that they can be drawn over the text
+        let mut overlay_buffer = VertexBuffers::new();
+        std::mem::swap(&mut self.lyon_buffer, &mut overlay_buffer);
+        let copy_button = self.draw_copy_button();
+        let tooltip = self.render_tooltip();
+        std::mem::swap(&mut self.lyon_buffer, &mut overlay_buffer);
+        copy_button?;
+        let tooltip_area = tooltip?;
+        let overlay_buffers = (!overlay_buffer.indices.is_empty()).then(|| {
+            let vertex_buf = self
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Overlay Vertex Buffer"),
+                    contents: bytemuck::cast_slice(&overlay_buffer.vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+            let index_buf = self
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Overlay Index Buffer"),
+                    contents: bytemuck::cast_slice(&overlay_buffer.indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                });
+            (vertex_buf, index_buf)
+        });
 
         {
             let mut text_cache = self.text_system.text_cache.lock().unwrap();
             let text_areas: Vec<TextArea> = cached_text_areas
                 .iter()
-                .map(|c| c.text_area(&text_cache))
+                .flat_map(|c| c.text_areas(&text_cache))
                 .collect();
 
-            self.text_system.text_renderer.prepare(
+            self.text_renderers.text_renderer.prepare(
                 &self.device,
                 &self.queue,
                 &mut self.text_system.font_system.lock().unwrap(),
-                &mut self.text_system.text_atlas,
+                &mut self.text_renderers.text_atlas,
                 Resolution {
                     width: self.config.width,
                     height: self.config.height,
                 },
                 text_areas,
-                &mut self.text_system.swash_cache,
+                &mut self.text_renderers.swash_cache,
             )?;
+            if let Some(tooltip_area) = &tooltip_area {
+                self.text_renderers.overlay_renderer.prepare(
+                    &self.device,
+                    &self.queue,
+                    &mut self.text_system.font_system.lock().unwrap(),
+                    &mut self.text_renderers.text_atlas,
+                    Resolution {
+                        width: self.config.width,
+                        height: self.config.height,
+                    },
+                    tooltip_area.text_areas(&text_cache),
+                    &mut self.text_renderers.swash_cache,
+                )?;
+            }
             text_cache.trim();
         }
 
@@ -824,21 +1121,49 @@ impl Renderer {
             // Draw images
             rpass.set_pipeline(&self.image_renderer.render_pipeline);
             rpass.set_index_buffer(self.image_renderer.index_buf.slice(..), IndexFormat::Uint16);
-            for (bindgroup, vertex_buf) in image_bindgroups.iter() {
+            for (bindgroup, vertex_buf, clip) in image_bindgroups.iter() {
+                let (width, height) = (self.config.width, self.config.height);
+                match clip {
+                    Some(clip) => {
+                        let x = (clip.pos.0.max(0.) as u32).min(width);
+                        let y = (clip.pos.1.max(0.) as u32).min(height);
+                        let max_x = (clip.max().0.max(0.) as u32).clamp(x, width);
+                        let max_y = (clip.max().1.max(0.) as u32).clamp(y, height);
+                        if max_x == x || max_y == y {
+                            continue;
+                        }
+                        rpass.set_scissor_rect(x, y, max_x - x, max_y - y);
+                    }
+                    None => rpass.set_scissor_rect(0, 0, width, height),
+                }
                 rpass.set_bind_group(0, bindgroup, &[]);
                 rpass.set_vertex_buffer(0, vertex_buf.slice(..));
                 rpass.draw_indexed(0..6, 0, 0..1);
             }
+            rpass.set_scissor_rect(0, 0, self.config.width, self.config.height);
 
-            self.text_system
+            self.text_renderers
                 .text_renderer
-                .render(&self.text_system.text_atlas, &mut rpass)
+                .render(&self.text_renderers.text_atlas, &mut rpass)
                 .unwrap();
+
+            if let Some((vertex_buf, index_buf)) = &overlay_buffers {
+                rpass.set_pipeline(&self.render_pipeline);
+                rpass.set_vertex_buffer(0, vertex_buf.slice(..));
+                rpass.set_index_buffer(index_buf.slice(..), wgpu::IndexFormat::Uint16);
+                rpass.draw_indexed(0..overlay_buffer.indices.len() as u32, 0, 0..1);
+            }
+            if tooltip_area.is_some() {
+                self.text_renderers
+                    .overlay_renderer
+                    .render(&self.text_renderers.text_atlas, &mut rpass)
+                    .unwrap();
+            }
         }
 
         self.queue.submit(Some(encoder.finish()));
         frame.present();
-        self.text_system.text_atlas.trim();
+        self.text_renderers.text_atlas.trim();
 
         Ok(())
     }
@@ -860,6 +1185,90 @@ impl Renderer {
     }
 }
 
+// Evicts the textures of the images furthest from the viewport until they all fit in `budget`.
+// Images within a screen of the viewport are always kept. Returns the bytes still in use
+fn evict_textures(
+    elements: &mut [Positioned<Element>],
+    viewport: (f32, f32),
+    budget: usize,
+) -> usize {
+    let screen_height = viewport.1 - viewport.0;
+    let mut images = Vec::new();
+    collect_textured_images(elements, viewport, &mut images);
+
+    let mut used: usize = images
+        .iter()
+        .map(|(_, image)| image.texture_byte_size())
+        .sum();
+    if used > budget {
+        // Evict the furthest images first
+        images.sort_by(|(dist_a, _), (dist_b, _)| dist_b.total_cmp(dist_a));
+        for (dist, image) in images {
+            if used <= budget || dist <= screen_height {
+                break;
+            }
+            used -= image.texture_byte_size();
+            image.evict_texture();
+        }
+    }
+    used
+}
+
+// Gathers all images that currently hold a texture along with their distance from the viewport
+fn collect_textured_images<'a>(
+    elements: &'a mut [Positioned<Element>],
+    viewport: (f32, f32),
+    images: &mut Vec<(f32, &'a mut Image)>,
+) {
+    for Positioned { inner, bounds } in elements {
+        match inner {
+            Element::Image(image) => {
+                if image.texture_dimensions.is_some() {
+                    images.push((viewport_distance(bounds, viewport), image));
+                }
+            }
+            Element::TextBox(text_box) => {
+                for image in &mut text_box.images {
+                    if image.inner.texture_dimensions.is_some() {
+                        images.push((viewport_distance(&image.bounds, viewport), &mut image.inner));
+                    }
+                }
+            }
+            Element::Section(section) => {
+                if *section.hidden.borrow() {
+                    // Hidden images aren't drawn at all, so they're always fair game
+                    let mut hidden = Vec::new();
+                    collect_textured_images(&mut section.elements, viewport, &mut hidden);
+                    images.extend(hidden.into_iter().map(|(_, image)| (f32::INFINITY, image)));
+                } else {
+                    collect_textured_images(&mut section.elements, viewport, images);
+                }
+            }
+            Element::Table(table) => {
+                for cell in table.cells_mut() {
+                    collect_textured_images(&mut cell.elements, viewport, images);
+                }
+            }
+            Element::Spacer(_) => {}
+        }
+    }
+}
+
+fn viewport_distance(bounds: &Option<Rect>, viewport: (f32, f32)) -> f32 {
+    match bounds {
+        Some(Rect { pos, size }) => {
+            if pos.1 + size.1 < viewport.0 {
+                viewport.0 - (pos.1 + size.1)
+            } else if pos.1 > viewport.1 {
+                pos.1 - viewport.1
+            } else {
+                0.
+            }
+        }
+        None => f32::INFINITY,
+    }
+}
+
 // Translates points from pixel coordinates to wgpu coordinates
 pub fn point(x: f32, y: f32, screen: Size) -> [f32; 2] {
     let scale_x = 2. / screen.0;
@@ -868,3 +1277,99 @@ pub fn point(x: f32, y: f32, screen: Size) -> [f32; 2] {
     let new_y = 1. - (y * scale_y);
     [new_x, new_y]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::evict_textures;
+    use crate::image::Image;
+    use crate::positioner::{Positioned, Section};
+    use crate::table::{Table, TableCell};
+    use crate::text::TextBox;
+    use crate::utils::Rect;
+    use crate::Element;
+
+    // Each texture takes up 100 * 100 * 4 bytes
+    const TEXTURE: usize = 40_000;
+
+    fn textured(top: f32) -> Positioned<Image> {
+        let image = Image {
+            texture_dimensions: Some((100, 100)),
+            ..Default::default()
+        };
+        Positioned {
+            inner: image,
+            bounds: Some(Rect::new((0., top), (100., 100.))),
+        }
+    }
+
+    fn element(image: Positioned<Image>) -> Positioned<Element> {
+        Positioned {
+            inner: image.inner.into(),
+            bounds: image.bounds,
+        }
+    }
+
+    fn is_textured(element: &Positioned<Element>) -> bool {
+        match &element.inner {
+            Element::Image(image) => image.texture_dimensions.is_some(),
+            Element::TextBox(text_box) => text_box.images[0].inner.texture_dimensions.is_some(),
+            Element::Section(section) => is_textured(&section.elements[0]),
+            Element::Table(table) => is_textured(&table.rows[0][0].elements[0]),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn evicts_furthest_textures_first() {
+        // The viewport covers 0 to 600, so everything within 600 of it stays put
+        let viewport = (0., 600.);
+        let mut hidden = Section::bare(1.);
+        hidden.elements.push(element(textured(100.)));
+        *hidden.hidden.borrow_mut() = true;
+        let mut table = Table::new();
+        table.push_row(vec![TableCell {
+            elements: vec![element(textured(3_000.))],
+            ..Default::default()
+        }]);
+        let mut text_box = TextBox::default();
+        text_box.images.push(textured(4_000.));
+        let mut elements = vec![
+            element(textured(0.)),
+            Positioned::new(hidden),
+            element(textured(5_000.)),
+            Positioned::new(table),
+            Positioned::new(text_box),
+            element(textured(1_000.)),
+        ];
+        let textured = |elements: &[Positioned<Element>]| -> Vec<_> {
+            elements.iter().map(is_textured).collect()
+        };
+
+        // Everything fits within the budget
+        assert_eq!(
+            evict_textures(&mut elements, viewport, 6 * TEXTURE),
+            6 * TEXTURE
+        );
+        assert_eq!(textured(&elements), [true; 6]);
+
+        // Hidden images go first, then the ones furthest from the viewport
+        let expected = [
+            [true, false, true, true, true, true],
+            [true, false, false, true, true, true],
+            [true, false, false, true, false, true],
+            [true, false, false, false, false, true],
+        ];
+        for (evicted, expected) in expected.into_iter().enumerate() {
+            let budget = (5 - evicted) * TEXTURE;
+            assert_eq!(evict_textures(&mut elements, viewport, budget), budget);
+            assert_eq!(textured(&elements), expected);
+        }
+
+        // Images on or near the screen are never evicted
+        assert_eq!(evict_textures(&mut elements, viewport, 0), 2 * TEXTURE);
+        assert_eq!(
+            textured(&elements),
+            [true, false, false, false, false, true]
+        );
+    }
+}
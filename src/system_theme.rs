@@ -0,0 +1,34 @@
+use std::thread;
+use std::time::Duration;
+
+use crate::opts::ResolvedTheme;
+use crate::InlyneEvent;
+
+use winit::event_loop::EventLoopProxy;
+
+// Detecting the color scheme can involve a D-Bus round trip, so don't go overboard
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Polls for changes to the system's dark/light preference since most platforms lack a reliable
+/// way to get notified
+pub fn watch(event_proxy: EventLoopProxy<InlyneEvent>, initial: Option<ResolvedTheme>) {
+    thread::spawn(move || {
+        let mut last = initial;
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let detected = ResolvedTheme::try_detect();
+            if detected == last {
+                continue;
+            }
+            last = detected;
+            if let Some(color_scheme) = detected {
+                tracing::debug!("System color scheme changed to {color_scheme:?}");
+                let event = InlyneEvent::SystemThemeChange(color_scheme);
+                if event_proxy.send_event(event).is_err() {
+                    // The event loop is gone
+                    break;
+                }
+            }
+        }
+    });
+}
@@ -1,28 +1,73 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 
-use crate::text::{Text, TextBox, TextBoxMeasure, TextSystem};
-use crate::utils::{default, Point, Rect, Size};
+use crate::positioner::{HorizontalScroll, Positioned};
+use crate::text::{TextBoxMeasure, TextSystem};
+use crate::utils::{default, Align, Float, Point, Rect, Size};
+use crate::{Element, Hoverable};
 
+use smart_debug::SmartDebug;
+use taffy::geometry::Line;
 use taffy::node::MeasureFunc;
 use taffy::prelude::{
-    auto, line, points, AvailableSpace, Display, Layout, Size as TaffySize, Style, Taffy,
+    auto, line, points, span, AlignSelf, AvailableSpace, Display, FlexDirection, Node,
+    Rect as TaffyRect, Size as TaffySize, Style, Taffy,
 };
 use taffy::style::JustifyContent;
 
 pub const TABLE_ROW_GAP: f32 = 20.;
 pub const TABLE_COL_GAP: f32 = 20.;
 
-#[derive(Debug)]
-pub struct TableLayout {
-    pub headers: Vec<Layout>,
-    pub rows: Vec<Vec<Layout>>,
-    pub size: Size,
+#[derive(SmartDebug, PartialEq)]
+#[debug(skip_defaults)]
+pub struct TableCell {
+    #[debug(no_skip)]
+    pub elements: Vec<Positioned<Element>>,
+    pub colspan: u16,
+    pub rowspan: u16,
+    pub bounds: Option<Rect>,
 }
 
-#[derive(Default, Debug, PartialEq)]
+impl Default for TableCell {
+    fn default() -> Self {
+        Self {
+            elements: Vec::new(),
+            colspan: 1,
+            rowspan: 1,
+            bounds: None,
+        }
+    }
+}
+
+impl TableCell {
+    /// Adds an element to the end of the cell. Cells can't lay out tables or sections of their
+    /// own, so everything within those gets added one after the other instead
+    pub fn push(&mut self, element: Element) {
+        match element {
+            Element::Table(table) => {
+                for cell in table.headers.into_iter().chain(table.rows).flatten() {
+                    for element in cell.elements {
+                        self.push(element.inner);
+                    }
+                }
+            }
+            Element::Section(section) => {
+                for element in section.summary.into_iter().chain(section.elements) {
+                    self.push(element.inner);
+                }
+            }
+            element => self.elements.push(Positioned::new(element)),
+        }
+    }
+}
+
+#[derive(Default, SmartDebug, PartialEq)]
 pub struct Table {
-    pub headers: Vec<TextBox>,
-    pub rows: Vec<Vec<TextBox>>,
+    pub headers: Vec<Vec<TableCell>>,
+    pub rows: Vec<Vec<TableCell>>,
+    // Set when the table keeps its natural width and that's wider than the page
+    #[debug(skip)]
+    pub scroll: Option<HorizontalScroll>,
 }
 
 impl Table {
@@ -30,70 +75,81 @@ impl Table {
         Table::default()
     }
 
+    pub fn cells(&self) -> impl Iterator<Item = &TableCell> {
+        self.headers.iter().chain(&self.rows).flatten()
+    }
+
+    pub fn cells_mut(&mut self) -> impl Iterator<Item = &mut TableCell> {
+        self.headers.iter_mut().chain(&mut self.rows).flatten()
+    }
+
     pub fn find_hoverable<'a>(
         &'a self,
         text_system: &mut TextSystem,
-        taffy: &mut Taffy,
         loc: Point,
-        pos: Point,
-        bounds: Size,
         zoom: f32,
-    ) -> Option<&'a Text> {
-        let table_layout = self.layout(text_system, taffy, bounds, zoom).ok()?;
-        for (header, layout) in self.headers.iter().zip(table_layout.headers.iter()) {
-            if Rect::new(
-                (pos.0 + layout.location.x, pos.1 + layout.location.y),
-                (layout.size.width, layout.size.height),
-            )
-            .contains(loc)
-            {
-                return header.find_hoverable(
-                    text_system,
-                    loc,
-                    (pos.0 + layout.location.x, pos.1 + layout.location.y),
-                    (layout.size.width, layout.size.height),
-                    zoom,
-                );
-            }
-        }
-        for (row, row_layout) in self.rows.iter().zip(table_layout.rows.iter()) {
-            for (item, layout) in row.iter().zip(row_layout.iter()) {
-                if Rect::new(
-                    (pos.0 + layout.location.x, pos.1 + layout.location.y),
-                    (layout.size.width, layout.size.height),
-                )
-                .contains(loc)
-                {
-                    return item.find_hoverable(
-                        text_system,
-                        loc,
-                        (pos.0 + layout.location.x, pos.1 + layout.location.y),
-                        (layout.size.width, layout.size.height),
-                        zoom,
-                    );
-                }
-            }
+    ) -> Option<Hoverable<'a>> {
+        // Cells are positioned as if the table wasn't scrolled at all
+        let loc = (loc.0 + self.scroll_offset(), loc.1);
+        let cell = self.cells().find(|cell| {
+            cell.bounds
+                .as_ref()
+                .is_some_and(|bounds| bounds.contains(loc))
+        })?;
+        let element = cell.elements.iter().find(|e| e.contains(loc))?;
+        let bounds = element.bounds.as_ref()?;
+        match &element.inner {
+            Element::TextBox(text_box) => match text_box.find_image(loc) {
+                Some(image) => Some(Hoverable::Image(image)),
+                None => text_box
+                    .find_hoverable(text_system, loc, bounds.pos, bounds.size, zoom)
+                    .map(Hoverable::Text),
+            },
+            Element::Image(image) => Some(Hoverable::Image(image)),
+            Element::Spacer(_) | Element::Table(_) | Element::Section(_) => None,
         }
-        None
     }
 
+    pub fn scroll_offset(&self) -> f32 {
+        self.scroll.map_or(0., |scroll| scroll.offset)
+    }
+
+    /// Lays the cells out on a grid with `pos` as the top left corner and positions all of the
+    /// elements within them. Returns the size of the whole table, which can be wider than `bounds`
+    /// with `natural_width`
+    #[allow(clippy::too_many_arguments)]
     pub fn layout(
-        &self,
+        &mut self,
         text_system: &mut TextSystem,
         taffy: &mut Taffy,
+        pos: Point,
         bounds: Size,
+        hidpi_scale: f32,
         zoom: f32,
-    ) -> anyhow::Result<TableLayout> {
-        let max_columns = self
-            .rows
+        natural_width: bool,
+    ) -> anyhow::Result<Size> {
+        let spans: Vec<Vec<_>> = self
+            .headers
             .iter()
-            .fold(self.headers.len(), |max, row| std::cmp::max(row.len(), max));
+            .chain(&self.rows)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| (cell.colspan, cell.rowspan))
+                    .collect()
+            })
+            .collect();
+        let (placements, max_columns) = place_cells(&spans);
 
         // Setup the grid
+        let (root_width, available_width) = if natural_width {
+            (auto(), AvailableSpace::MaxContent)
+        } else {
+            (points(bounds.0), AvailableSpace::Definite(bounds.0))
+        };
         let root_style = Style {
             display: Display::Flex,
             size: TaffySize {
-                width: points(bounds.0),
+                width: root_width,
                 height: auto(),
             },
             justify_content: Some(JustifyContent::Start),
@@ -110,95 +166,231 @@ impl Table {
             ..default()
         };
 
-        let mut nodes = Vec::new();
-        let mut node_row = Vec::new();
         // Define the child nodes
-        for (x, header) in self.headers.iter().enumerate() {
-            let textbox_measure = TextBoxMeasure {
-                font_system: text_system.font_system.clone(),
-                text_cache: text_system.text_cache.clone(),
-                textbox: Arc::new(header.clone()),
-                zoom,
-            };
-            node_row.push(taffy.new_leaf_with_measure(
-                Style {
-                    grid_row: line(1),
-                    grid_column: line(x as i16 + 1),
-                    ..default()
-                },
-                MeasureFunc::Boxed(Box::new(move |known_dimensions, available_space| {
-                    textbox_measure.measure(known_dimensions, available_space)
-                })),
-            )?);
-        }
-        nodes.push(node_row.clone());
-        node_row.clear();
-
-        for (y, row) in self.rows.iter().enumerate() {
-            for (x, item) in row.iter().enumerate() {
-                let item = item.clone();
-                let textbox_measure = TextBoxMeasure {
-                    font_system: text_system.font_system.clone(),
-                    text_cache: text_system.text_cache.clone(),
-                    textbox: Arc::new(item.clone()),
+        let mut cell_nodes = Vec::new();
+        for (cell, placement) in self.cells_mut().zip(placements) {
+            let mut children = Vec::new();
+            for element in &mut cell.elements {
+                children.push(element_node(
+                    &mut element.inner,
+                    text_system,
+                    taffy,
+                    bounds,
+                    hidpi_scale,
                     zoom,
-                };
-                node_row.push(taffy.new_leaf_with_measure(
-                    Style {
-                        grid_row: line(1 + y as i16 + 1),
-                        grid_column: line(x as i16 + 1),
-                        ..default()
-                    },
-                    MeasureFunc::Boxed(Box::new(move |known_dimensions, available_space| {
-                        textbox_measure.measure(known_dimensions, available_space)
-                    })),
                 )?);
             }
-            nodes.push(node_row.clone());
-            node_row.clear();
-        }
-
-        let mut flattened_nodes = Vec::new();
-        for row in &nodes {
-            flattened_nodes.append(&mut row.clone());
+            let Placement { x, y } = placement;
+            let cell_style = Style {
+                display: Display::Flex,
+                flex_direction: FlexDirection::Column,
+                grid_row: Line {
+                    start: line(y as i16 + 1),
+                    end: span(cell.rowspan),
+                },
+                grid_column: Line {
+                    start: line(x as i16 + 1),
+                    end: span(cell.colspan),
+                },
+                ..default()
+            };
+            cell_nodes.push(taffy.new_with_children(cell_style, &children)?);
         }
 
-        let grid = taffy.new_with_children(grid_style, &flattened_nodes)?;
+        let grid = taffy.new_with_children(grid_style, &cell_nodes)?;
         let root = taffy.new_with_children(root_style, &[grid])?;
 
         taffy.compute_layout(
             root,
             TaffySize::<AvailableSpace> {
-                width: AvailableSpace::Definite(bounds.0),
+                width: available_width,
                 height: AvailableSpace::MaxContent,
             },
         )?;
 
-        let mut rows = nodes.into_iter();
-        let header_layout = rows
-            .next()
-            .unwrap_or_default()
-            .iter()
-            .map(|n| *taffy.layout(*n).unwrap())
-            .collect();
+        let grid_location = taffy.layout(grid)?.location;
+        let grid_pos = (pos.0 + grid_location.x, pos.1 + grid_location.y);
+        for (cell, node) in self.cells_mut().zip(cell_nodes) {
+            let cell_pos = set_bounds(&mut cell.bounds, taffy, node, grid_pos)?;
+            set_element_bounds(&mut cell.elements, text_system, taffy, node, cell_pos, zoom)?;
+        }
 
-        let rows_layout: Vec<Vec<Layout>> = rows
-            .map(|row| row.iter().map(|n| *taffy.layout(*n).unwrap()).collect())
-            .collect();
         let size = taffy.layout(root)?.size;
-
-        Ok(TableLayout {
-            headers: header_layout,
-            rows: rows_layout,
-            size: (size.width, size.height),
-        })
+        Ok((size.width, size.height))
     }
 
-    pub fn push_header(&mut self, header: TextBox) {
+    pub fn push_header(&mut self, header: Vec<TableCell>) {
         self.headers.push(header);
     }
 
-    pub fn push_row(&mut self, row: Vec<TextBox>) {
+    pub fn push_row(&mut self, row: Vec<TableCell>) {
         self.rows.push(row);
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Placement {
+    x: usize,
+    y: usize,
+}
+
+// Places cells on the grid the same way that HTML does, where a cell spanning multiple rows pushes
+// the cells below it over to the right. Takes the `(colspan, rowspan)` for each cell and returns
+// where each of them goes along with the total number of columns
+fn place_cells(rows: &[Vec<(u16, u16)>]) -> (Vec<Placement>, usize) {
+    let mut taken = HashSet::new();
+    let mut placements = Vec::new();
+    let mut max_columns = 0;
+    for (y, row) in rows.iter().enumerate() {
+        let mut x = 0;
+        for &(colspan, rowspan) in row {
+            while taken.contains(&(x, y)) {
+                x += 1;
+            }
+            // Spans can't reach past the last row
+            let rowspan = usize::from(rowspan).min(rows.len() - y);
+            let colspan = usize::from(colspan);
+            for spanned_y in y..y + rowspan {
+                for spanned_x in x..x + colspan {
+                    taken.insert((spanned_x, spanned_y));
+                }
+            }
+            placements.push(Placement { x, y });
+            x += colspan;
+            max_columns = max_columns.max(x);
+        }
+    }
+    (placements, max_columns)
+}
+
+fn element_node(
+    element: &mut Element,
+    text_system: &mut TextSystem,
+    taffy: &mut Taffy,
+    bounds: Size,
+    hidpi_scale: f32,
+    zoom: f32,
+) -> anyhow::Result<Node> {
+    let node = match element {
+        Element::TextBox(text_box) => {
+            text_box.size_images(bounds.0, zoom);
+            let textbox_measure = TextBoxMeasure {
+                font_system: text_system.font_system.clone(),
+                text_cache: text_system.text_cache.clone(),
+                textbox: Arc::new(text_box.clone()),
+                zoom,
+            };
+            let gutter = text_box.gutter_width(text_system, zoom);
+            taffy.new_leaf_with_measure(
+                Style {
+                    margin: TaffyRect {
+                        left: points(text_box.indent + gutter),
+                        ..TaffyRect::zero()
+                    },
+                    ..default()
+                },
+                MeasureFunc::Boxed(Box::new(move |known_dimensions, available_space| {
+                    textbox_measure.measure(known_dimensions, available_space)
+                })),
+            )?
+        }
+        Element::Image(image) => {
+            let size = image
+                .size((bounds.0, f32::INFINITY), zoom)
+                .unwrap_or_default();
+            // Nothing wraps around floats within a cell, so they just go to their side of it
+            let align_self = match (image.float, image.is_aligned) {
+                (Some(Float::Right), _) | (None, Some(Align::Right)) => Some(AlignSelf::FlexEnd),
+                (None, Some(Align::Center)) => Some(AlignSelf::Center),
+                (Some(Float::Left), _) | (None, Some(Align::Left | Align::Justify) | None) => None,
+            };
+            taffy.new_leaf(Style {
+                size: TaffySize {
+                    width: points(size.0),
+                    height: points(size.1),
+                },
+                flex_shrink: 0.,
+                align_self,
+                ..default()
+            })?
+        }
+        Element::Spacer(spacer) => taffy.new_leaf(Style {
+            size: TaffySize {
+                width: auto(),
+                height: points(spacer.space * hidpi_scale * zoom),
+            },
+            ..default()
+        })?,
+        // Cells flatten these when they get added
+        Element::Table(_) | Element::Section(_) => taffy.new_leaf(default())?,
+    };
+    Ok(node)
+}
+
+fn set_bounds(
+    bounds: &mut Option<Rect>,
+    taffy: &Taffy,
+    node: Node,
+    offset: Point,
+) -> anyhow::Result<Point> {
+    let layout = taffy.layout(node)?;
+    let pos = (offset.0 + layout.location.x, offset.1 + layout.location.y);
+    *bounds = Some(Rect::new(pos, (layout.size.width, layout.size.height)));
+    Ok(pos)
+}
+
+// Nodes mirror the elements that they were created from, so they can be walked in lockstep
+fn set_element_bounds(
+    elements: &mut [Positioned<Element>],
+    text_system: &mut TextSystem,
+    taffy: &Taffy,
+    parent: Node,
+    offset: Point,
+    zoom: f32,
+) -> anyhow::Result<()> {
+    for (element, node) in elements.iter_mut().zip(taffy.children(parent)?) {
+        let pos = set_bounds(&mut element.bounds, taffy, node, offset)?;
+        if let Element::TextBox(text_box) = &mut element.inner {
+            let width = taffy.layout(node)?.size.width;
+            text_box.position_images(text_system, pos, (width, f32::INFINITY), zoom);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{place_cells, Placement};
+
+    #[test]
+    fn spans_push_cells_over() {
+        // | a (2 cols)  | b (2 rows) |
+        // | c | d       |            |
+        // | e | f       | g          |
+        let rows = [
+            vec![(2, 1), (1, 2)],
+            vec![(1, 1), (1, 1)],
+            vec![(1, 1), (1, 1), (1, 1)],
+        ];
+        let (placements, max_columns) = place_cells(&rows);
+        let placements: Vec<_> = placements
+            .into_iter()
+            .map(|Placement { x, y }| (x, y))
+            .collect();
+        assert_eq!(
+            placements,
+            [(0, 0), (2, 0), (0, 1), (1, 1), (0, 2), (1, 2), (2, 2)]
+        );
+        assert_eq!(max_columns, 3);
+
+        // A cell spanning down pushes the next row's cells past it
+        let rows = [vec![(1, 3), (1, 1)], vec![(1, 1)], vec![(1, 1), (1, 1)]];
+        let (placements, max_columns) = place_cells(&rows);
+        let placements: Vec<_> = placements
+            .into_iter()
+            .map(|Placement { x, y }| (x, y))
+            .collect();
+        assert_eq!(placements, [(0, 0), (1, 0), (1, 1), (1, 2), (2, 2)]);
+        assert_eq!(max_columns, 3);
+    }
+}
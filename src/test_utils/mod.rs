@@ -1,3 +1,4 @@
 pub mod image;
 pub mod log;
 pub mod server;
+pub mod text;
@@ -0,0 +1,23 @@
+use std::sync::{Arc, Mutex};
+
+use crate::fonts::{self, FontFamilies};
+use crate::text::{TextCache, TextSystem};
+
+use glyphon::{fontdb, FontSystem};
+
+/// A text system that only knows about the bundled fonts, so that text gets laid out the same
+/// everywhere
+pub fn text_system() -> TextSystem {
+    let mut db = fontdb::Database::new();
+    db.load_font_data(include_bytes!("../../assets/fonts/DejaVuSans.ttf").to_vec());
+    db.load_font_data(include_bytes!("../../assets/fonts/DejaVuSansMono.ttf").to_vec());
+    db.set_sans_serif_family("DejaVu Sans");
+    db.set_serif_family("DejaVu Sans");
+    db.set_monospace_family("DejaVu Sans Mono");
+    fonts::load_placeholder(&mut db);
+    let font_system = FontSystem::new_with_locale_and_db("en-US".to_owned(), db);
+    TextSystem {
+        font_system: Arc::new(Mutex::new(font_system)),
+        text_cache: Arc::new(Mutex::new(TextCache::new(FontFamilies::default()))),
+    }
+}
@@ -1,21 +1,30 @@
 use std::borrow::BorrowMut;
-use std::collections::hash_map;
 use std::fmt;
 use std::hash::{BuildHasher, Hash, Hasher};
-use std::ops::Range;
+use std::ops::{Range, RangeInclusive};
 use std::sync::{Arc, Mutex};
 
 use fxhash::{FxHashMap, FxHashSet};
 use glyphon::{
-    Affinity, Attrs, AttrsList, BufferLine, Color, Cursor, FamilyOwned, FontSystem, LayoutGlyph,
-    Shaping, Style, SwashCache, TextArea, TextBounds, Weight,
+    cosmic_text, Affinity, Attrs, AttrsList, AttrsOwned, BufferLine, Color, Cursor, FamilyOwned,
+    FontSystem, LayoutGlyph, Shaping, Style, SwashCache, TextArea, TextBounds, Weight,
 };
 use smart_debug::SmartDebug;
 use taffy::prelude::{AvailableSpace, Size as TaffySize};
 
 use crate::debug_impls::{self, DebugInline, DebugInlineMaybeF32Color};
+use crate::fonts::{self, FontFamilies};
+use crate::image::Image;
+use crate::positioner::{HorizontalScroll, Positioned};
 use crate::selection::{Selection, SelectionKind, SelectionMode};
-use crate::utils::{Align, Line, Point, Rect, Size};
+use crate::utils::{Align, Direction, Line, Point, Rect, Size};
+
+// Room between the line numbers of a code block and its text
+pub const GUTTER_PADDING: f32 = 16.;
+
+const LEFT_TO_RIGHT_MARK: char = '\u{200E}';
+const RIGHT_TO_LEFT_MARK: char = '\u{200F}';
+const DIRECTION_MARKS: [char; 2] = [LEFT_TO_RIGHT_MARK, RIGHT_TO_LEFT_MARK];
 
 type KeyHash = u64;
 type HashBuilder = twox_hash::RandomXxHashBuilder64;
@@ -53,26 +62,52 @@ impl TextBoxMeasure {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine {
+    Added,
+    Removed,
+    Hunk,
+}
+
 #[derive(SmartDebug, Clone, PartialEq)]
 #[debug(skip_defaults)]
 pub struct TextBox {
     pub font_size: f32,
+    // Height of each line as a multiple of the font size
+    #[debug(skip)]
+    pub line_height: f32,
     pub align: Align,
+    pub direction: Direction,
     pub indent: f32,
     pub padding_height: f32,
     #[debug(wrapper = DebugInlineMaybeF32Color)]
     pub background_color: Option<[f32; 4]>,
     pub is_code_block: bool,
+    pub line_numbers: bool,
+    pub highlighted_lines: Vec<RangeInclusive<usize>>,
+    pub is_diff: bool,
     #[debug(wrapper = DebugInline)]
     pub is_quote_block: Option<usize>,
     #[debug(wrapper = DebugInline)]
     pub is_checkbox: Option<bool>,
     #[debug(wrapper = DebugInline)]
     pub is_anchor: Option<String>,
+    #[debug(skip)]
+    pub heading_level: Option<u8>,
     #[debug(no_skip)]
     pub texts: Vec<Text>,
+    pub images: Vec<Positioned<Image>>,
+    // Room taken up by floated images on the right the last time this was positioned
+    #[debug(skip)]
+    pub right_float: f32,
     #[debug(skip)]
     pub hidpi_scale: f32,
+    // Set on code blocks that don't wrap and are wider than the page
+    #[debug(skip)]
+    pub scroll: Option<HorizontalScroll>,
+    // The source of a code block exactly as it was written
+    #[debug(skip)]
+    pub code: String,
 }
 
 impl Default for TextBox {
@@ -80,15 +115,25 @@ impl Default for TextBox {
         Self {
             indent: 0.0,
             font_size: 16.0,
+            line_height: 1.1,
             texts: Vec::new(),
+            images: Vec::new(),
+            right_float: 0.,
             is_code_block: false,
+            line_numbers: false,
+            highlighted_lines: Vec::new(),
+            is_diff: false,
             is_quote_block: None,
             is_checkbox: None,
             is_anchor: None,
+            heading_level: None,
             align: Align::default(),
+            direction: Direction::default(),
             hidpi_scale: 1.0,
             padding_height: 0.0,
             background_color: None,
+            scroll: None,
+            code: String::new(),
         }
     }
 }
@@ -100,18 +145,57 @@ pub struct CachedTextArea {
     top: f32,
     bounds: TextBounds,
     default_color: Color,
+    // Set when some of the lines get pushed down to make room for inline images
+    shifted: Vec<ShiftedLines>,
+}
+
+// Lines that get drawn `offset` further down than where they were laid out, along with where they
+// start and end once they're moved relative to the top of the text
+#[derive(Clone)]
+struct ShiftedLines {
+    offset: f32,
+    top: f32,
+    bottom: f32,
 }
 
 impl CachedTextArea {
-    pub fn text_area<'a>(&self, cache: &'a TextCache) -> TextArea<'a> {
-        TextArea {
+    /// Hides any text that's outside of `rect`
+    pub fn clip(&mut self, rect: &Rect) {
+        let max = rect.max();
+        self.bounds = TextBounds {
+            left: rect.pos.0 as i32,
+            top: rect.pos.1 as i32,
+            right: max.0 as i32,
+            bottom: max.1 as i32,
+        };
+    }
+
+    /// The areas that get drawn, where each run of lines that's moved a different amount gets
+    /// drawn on its own
+    pub fn text_areas<'a>(&self, cache: &'a TextCache) -> Vec<TextArea<'a>> {
+        let area = TextArea {
             buffer: cache.get(&self.key).expect("Get cached buffer"),
             left: self.left,
             top: self.top,
             bounds: self.bounds,
             default_color: self.default_color,
             scale: 1.,
+        };
+        if self.shifted.is_empty() {
+            return vec![area];
         }
+        self.shifted
+            .iter()
+            .map(|lines| TextArea {
+                top: self.top + lines.offset,
+                bounds: TextBounds {
+                    top: self.bounds.top.max((self.top + lines.top) as i32),
+                    bottom: self.bounds.bottom.min((self.top + lines.bottom) as i32),
+                    ..self.bounds
+                },
+                ..area.clone()
+            })
+            .collect()
     }
 }
 
@@ -124,10 +208,50 @@ impl TextBox {
         }
     }
 
+    pub fn scroll_offset(&self) -> f32 {
+        self.scroll.map_or(0., |scroll| scroll.offset)
+    }
+
     pub fn set_code_block(&mut self, is_code_block: bool) {
         self.is_code_block = is_code_block;
     }
 
+    pub fn set_line_numbers(&mut self, line_numbers: bool) {
+        self.line_numbers = line_numbers;
+    }
+
+    pub fn set_highlighted_lines(&mut self, lines: Vec<RangeInclusive<usize>>) {
+        self.highlighted_lines = lines;
+    }
+
+    pub fn is_highlighted(&self, line_number: usize) -> bool {
+        self.highlighted_lines
+            .iter()
+            .any(|lines| lines.contains(&line_number))
+    }
+
+    pub fn set_diff(&mut self, is_diff: bool) {
+        self.is_diff = is_diff;
+    }
+
+    /// What kind of diff line each line of the code is, if any
+    pub fn diff_lines(&self) -> impl Iterator<Item = Option<DiffLine>> + '_ {
+        self.code.lines().map(|line| {
+            // File headers look like added and removed lines, but aren't
+            if line.starts_with("+++ ") || line.starts_with("--- ") {
+                None
+            } else if line.starts_with('+') {
+                Some(DiffLine::Added)
+            } else if line.starts_with('-') {
+                Some(DiffLine::Removed)
+            } else if line.starts_with("@@") {
+                Some(DiffLine::Hunk)
+            } else {
+                None
+            }
+        })
+    }
+
     pub fn set_quote_block(&mut self, nest: usize) {
         self.is_quote_block = Some(nest);
     }
@@ -144,6 +268,10 @@ impl TextBox {
         self.is_anchor = Some(anchor);
     }
 
+    pub fn set_heading_level(&mut self, level: u8) {
+        self.heading_level = Some(level);
+    }
+
     pub fn set_background_color(&mut self, color: [f32; 4]) {
         self.background_color = Some(color);
     }
@@ -161,11 +289,161 @@ impl TextBox {
         self.set_align(maybe_align.unwrap_or_default());
     }
 
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+    }
+
+    /// Whether the text reads right-to-left. Code always reads left-to-right unless it's told
+    /// otherwise
+    pub fn is_rtl(&self) -> bool {
+        match self.direction {
+            Direction::Ltr => false,
+            Direction::Rtl => true,
+            Direction::Auto if self.is_code_block => false,
+            Direction::Auto => self
+                .texts
+                .iter()
+                .find_map(
+                    |text| match unicode_bidi::get_base_direction_full(&*text.text) {
+                        unicode_bidi::Direction::Ltr => Some(false),
+                        unicode_bidi::Direction::Rtl => Some(true),
+                        unicode_bidi::Direction::Mixed => None,
+                    },
+                )
+                .unwrap_or(false),
+        }
+    }
+
     pub fn line_height(&self, zoom: f32) -> f32 {
-        self.font_size * 1.1 * self.hidpi_scale * zoom
+        self.font_size * self.line_height * self.hidpi_scale * zoom
+    }
+
+    // How far down each run of the laid out text gets pushed along with the room that's left
+    // above it. Runs grow to fit the inline images that sit on their baseline, which pushes down
+    // everything after them too
+    fn run_offsets(&self, buffer: &glyphon::Buffer) -> Vec<(f32, f32)> {
+        let mut offset = 0.;
+        buffer
+            .layout_runs()
+            .map(|run| {
+                let room = run
+                    .glyphs
+                    .iter()
+                    .filter_map(|glyph| self.texts[glyph.metadata].image)
+                    .filter_map(|index| self.images[index].bounds.as_ref())
+                    .map(|bounds| bounds.size.1 - (run.line_y - run.line_top))
+                    .fold(0., f32::max);
+                offset += room;
+                (offset, room)
+            })
+            .collect()
+    }
+
+    // Where `y` from the top of the text lands in the laid out text, which doesn't know about the
+    // room left for inline images
+    fn unshifted_y(&self, buffer: &glyphon::Buffer, y: f32) -> f32 {
+        let line_height = buffer.metrics().line_height;
+        let mut last_offset = 0.;
+        for (run, (offset, _)) in buffer.layout_runs().zip(self.run_offsets(buffer)) {
+            if y < run.line_top + offset + line_height {
+                return (y - offset).max(run.line_top);
+            }
+            last_offset = offset;
+        }
+        y - last_offset
+    }
+
+    /// Adds an image that gets laid out within the lines of text where `text` holds its place
+    pub fn push_image(&mut self, image: Image, mut text: Text) {
+        text.image = Some(self.images.len());
+        text.font_family = FamilyOwned::Name(fonts::PLACEHOLDER_FAMILY.to_owned());
+        self.texts.push(text);
+        self.images.push(Positioned::new(image));
+    }
+
+    /// Sizes the inline images for text that wraps at `width` and makes room for them in the
+    /// text. Images that don't have their size yet don't take up any room
+    pub fn size_images(&mut self, width: f32, zoom: f32) {
+        let em = self.font_size * self.hidpi_scale * zoom;
+        for text in &mut self.texts {
+            let Some(index) = text.image else {
+                continue;
+            };
+            let image = &mut self.images[index];
+            let size = image
+                .inner
+                .size((width, f32::INFINITY), zoom)
+                .unwrap_or_default();
+            image.bounds = Some(Rect::new((0., 0.), size));
+            text.text = fonts::placeholder_text(size.0 / em);
+        }
+    }
+
+    /// Places the inline images over the text that holds their place. Images sit on the baseline
+    /// of their line, which grows to fit them
+    pub fn position_images(
+        &mut self,
+        text_system: &mut TextSystem,
+        screen_position: Point,
+        bounds: Size,
+        zoom: f32,
+    ) {
+        if self.images.is_empty() {
+            return;
+        }
+
+        // Matches the room that the renderer leaves for the checkbox
+        let (mut screen_position, mut bounds) = (screen_position, bounds);
+        if self.is_checkbox.is_some() {
+            let checkbox = self.font_size * self.hidpi_scale * zoom * 0.75 * 1.5;
+            if !self.is_rtl() {
+                screen_position.0 += checkbox;
+            }
+            bounds.0 -= checkbox;
+        }
+
+        let mut cache = text_system.text_cache.lock().unwrap();
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().unwrap().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+        let left = screen_position.0 + self.align_offset(buffer, bounds);
+
+        let mut placed = vec![None; self.images.len()];
+        let offsets = self.run_offsets(buffer);
+        for (run, (offset, _)) in buffer.layout_runs().zip(offsets) {
+            for glyph in run.glyphs {
+                let Some(index) = self.texts[glyph.metadata].image else {
+                    continue;
+                };
+                let Some(size) = self.images[index].bounds.as_ref().map(|bounds| bounds.size)
+                else {
+                    continue;
+                };
+                let top = run.line_y + offset - size.1;
+                let pos = (left + glyph.x, screen_position.1 + top);
+                // Glyphs can go in either direction, so the image starts at the leftmost one
+                placed[index] = match placed[index] {
+                    Some((x, y)) if x <= pos.0 => Some((x, y)),
+                    _ => Some(pos),
+                };
+            }
+        }
+        for (image, pos) in self.images.iter_mut().zip(placed) {
+            if let (Some(bounds), Some(pos)) = (&mut image.bounds, pos) {
+                bounds.pos = pos;
+            } else {
+                image.bounds = None;
+            }
+        }
     }
 
     pub fn key(&self, bounds: Size, zoom: f32) -> Key<'_> {
+        // Text that scrolls horizontally never wraps
+        let bounds = match self.scroll {
+            Some(_) => (f32::INFINITY, bounds.1),
+            None => bounds,
+        };
         let mut lines = Vec::new();
         let mut sections = Vec::new();
         for (i, text) in self.texts.iter().enumerate() {
@@ -182,9 +460,13 @@ impl TextBox {
 
         Key {
             lines,
+            heading: self.heading_level.is_some(),
+            rtl: self.is_rtl(),
+            justify: self.align == Align::Justify,
             size: self.font_size * self.hidpi_scale * zoom,
             line_height: self.line_height(zoom),
             bounds,
+            recolor: None,
         }
     }
 
@@ -208,7 +490,8 @@ impl TextBox {
             self.key(bounds, zoom),
         );
 
-        if let Some(cursor) = buffer.hit(loc.0 - screen_position.0, loc.1 - screen_position.1) {
+        let y = self.unshifted_y(buffer, loc.1 - screen_position.1);
+        if let Some(cursor) = buffer.hit(loc.0 - screen_position.0, y) {
             let line = &buffer.lines[cursor.line];
             let mut index = cursor.index;
             if cursor.affinity == Affinity::Before {
@@ -221,6 +504,84 @@ impl TextBox {
         }
     }
 
+    /// The top and height of every line of the text relative to the text box, where lines that
+    /// wrap take up the height of multiple lines
+    pub fn line_spans(
+        &self,
+        text_system: &mut TextSystem,
+        bounds: Size,
+        zoom: f32,
+    ) -> Vec<(f32, f32)> {
+        let line_height = self.line_height(zoom);
+        let mut cache = text_system.text_cache.lock().unwrap();
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().unwrap().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
+        let mut spans: Vec<(f32, f32)> = Vec::new();
+        let offsets = self.run_offsets(buffer);
+        for (run, (offset, room)) in buffer.layout_runs().zip(offsets) {
+            let top = run.line_top + offset - room;
+            let bottom = run.line_top + offset + line_height;
+            match spans.get_mut(run.line_i) {
+                Some((span_top, height)) => *height = bottom - *span_top,
+                None => spans.push((top, bottom - top)),
+            }
+        }
+        spans
+    }
+
+    /// A text box holding the number for each line in `line_spans` that lines up with the lines
+    /// when placed to the left of this one. Numbers are padded to all have the same width
+    pub fn line_number_box(
+        &self,
+        line_spans: &[(f32, f32)],
+        color: [f32; 4],
+        zoom: f32,
+    ) -> TextBox {
+        let line_height = self.line_height(zoom);
+        let digits = line_spans.len().to_string().len();
+        let mut texts = Vec::new();
+        for (i, (_, height)) in line_spans.iter().enumerate() {
+            let number = format!("{:>digits$}\n", i + 1);
+            texts.push(self.line_number_text(number, color));
+            // Leave the space next to wrapped lines empty
+            let wrapped = (height / line_height).round() as usize;
+            for _ in 1..wrapped {
+                texts.push(self.line_number_text("\n".to_owned(), color));
+            }
+        }
+        TextBox {
+            font_size: self.font_size,
+            line_height: self.line_height,
+            ..TextBox::new(texts, self.hidpi_scale)
+        }
+    }
+
+    /// How much room the line numbers take up to the left of a code block
+    pub fn gutter_width(&self, text_system: &mut TextSystem, zoom: f32) -> f32 {
+        if !self.line_numbers {
+            return 0.;
+        }
+        // The line numbers all have the same width, so measure the last one
+        let lines = self.key((f32::INFINITY, f32::INFINITY), zoom).lines.len();
+        let widest = TextBox {
+            font_size: self.font_size,
+            line_height: self.line_height,
+            ..TextBox::new(
+                vec![self.line_number_text(lines.to_string(), [0.; 4])],
+                self.hidpi_scale,
+            )
+        };
+        let (width, _) = widest.size(text_system, (f32::INFINITY, f32::INFINITY), zoom);
+        width + GUTTER_PADDING * self.hidpi_scale * zoom
+    }
+
+    fn line_number_text(&self, number: String, color: [f32; 4]) -> Text {
+        Text::new(number, self.hidpi_scale, color).with_family(FamilyOwned::Monospace)
+    }
+
     pub fn size(&self, text_system: &mut TextSystem, bounds: Size, zoom: f32) -> Size {
         self.size_without_system(
             &text_system.text_cache,
@@ -256,10 +617,16 @@ impl TextBox {
             .fold((0, 0.0), |(_, max), (i, buffer)| {
                 (i + 1, buffer.line_w.max(max))
             });
+        let image_room = self
+            .run_offsets(paragraph)
+            .last()
+            .map_or(0., |(offset, _)| *offset);
 
         (
             max_width,
-            total_lines as f32 * line_height + self.padding_height * self.hidpi_scale * zoom,
+            total_lines as f32 * line_height
+                + image_room
+                + self.padding_height * self.hidpi_scale * zoom,
         )
     }
 
@@ -271,26 +638,55 @@ impl TextBox {
         zoom: f32,
         scroll_y: f32,
     ) -> CachedTextArea {
-        let cache = text_system.text_cache.borrow_mut();
-
-        let (key, max_width) = {
-            let mut cache = cache.lock().unwrap();
-            let (key, paragraph) = cache.allocate(
-                text_system.font_system.lock().unwrap().borrow_mut(),
-                self.key(bounds, zoom),
-            );
+        let key = self.key(bounds, zoom);
+        self.text_area_for_key(text_system, key, screen_position, bounds, scroll_y)
+    }
 
-            let max_width = paragraph
-                .layout_runs()
-                .fold(0., |max, buffer| buffer.line_w.max(max));
-            (key, max_width)
+    /// The text areas for both the text and its raised and lowered text where the glyphs in
+    /// `selected` get drawn in `color`. `selected` holds the byte ranges of the glyphs on each line
+    #[allow(clippy::too_many_arguments)]
+    pub fn selected_text_areas(
+        &self,
+        text_system: &mut TextSystem,
+        screen_position: Point,
+        bounds: Size,
+        zoom: f32,
+        scroll_y: f32,
+        selected: &[(usize, Range<usize>)],
+        color: [f32; 4],
+    ) -> (CachedTextArea, Vec<CachedTextArea>) {
+        let key = Key {
+            recolor: Some(Recolor {
+                ranges: selected,
+                color: glyphon_color(color),
+            }),
+            ..self.key(bounds, zoom)
         };
+        let area =
+            self.text_area_for_key(text_system, key.clone(), screen_position, bounds, scroll_y);
+        let script_areas =
+            self.script_areas_for_key(text_system, key, screen_position, bounds, scroll_y);
+        (area, script_areas)
+    }
+
+    fn text_area_for_key(
+        &self,
+        text_system: &mut TextSystem,
+        key: Key<'_>,
+        screen_position: Point,
+        bounds: Size,
+        scroll_y: f32,
+    ) -> CachedTextArea {
+        let cache = text_system.text_cache.borrow_mut();
 
-        let left = match self.align {
-            Align::Left => screen_position.0,
-            Align::Center => screen_position.0 + (bounds.0 - max_width) / 2.,
-            Align::Right => screen_position.0 + bounds.0 - max_width,
+        let (key, offset, shifted) = {
+            let mut cache = cache.lock().unwrap();
+            let (key, paragraph) =
+                cache.allocate(text_system.font_system.lock().unwrap().borrow_mut(), key);
+            let shifted = self.shifted_lines(paragraph);
+            (key, self.align_offset(paragraph, bounds), shifted)
         };
+        let left = screen_position.0 + offset;
 
         CachedTextArea {
             key,
@@ -298,6 +694,143 @@ impl TextBox {
             top: (screen_position.1 - scroll_y),
             bounds: TextBounds::default(),
             default_color: Color::rgb(255, 255, 255),
+            shifted,
+        }
+    }
+
+    // Groups together the runs of the laid out text that get moved down by the same amount. The
+    // first and last groups reach past the text so that nothing around it gets cut off
+    fn shifted_lines(&self, buffer: &glyphon::Buffer) -> Vec<ShiftedLines> {
+        let offsets = self.run_offsets(buffer);
+        if offsets.last().map_or(true, |(offset, _)| *offset == 0.) {
+            return Vec::new();
+        }
+
+        let line_height = buffer.metrics().line_height;
+        let mut shifted: Vec<ShiftedLines> = Vec::new();
+        for (run, (offset, room)) in buffer.layout_runs().zip(offsets) {
+            let bottom = run.line_top + offset + line_height;
+            match shifted.last_mut() {
+                Some(lines) if lines.offset == offset => lines.bottom = bottom,
+                _ => shifted.push(ShiftedLines {
+                    offset,
+                    top: run.line_top + offset - room,
+                    bottom,
+                }),
+            }
+        }
+        if let Some(first) = shifted.first_mut() {
+            first.top = f32::NEG_INFINITY;
+        }
+        if let Some(last) = shifted.last_mut() {
+            last.bottom = f32::INFINITY;
+        }
+        shifted
+    }
+
+    /// The raised and lowered text, which gets drawn separately over the room that's left for it
+    /// in the lines of text
+    pub fn script_areas(
+        &self,
+        text_system: &mut TextSystem,
+        screen_position: Point,
+        bounds: Size,
+        zoom: f32,
+        scroll_y: f32,
+    ) -> Vec<CachedTextArea> {
+        let key = self.key(bounds, zoom);
+        self.script_areas_for_key(text_system, key, screen_position, bounds, scroll_y)
+    }
+
+    fn script_areas_for_key(
+        &self,
+        text_system: &mut TextSystem,
+        key: Key<'_>,
+        screen_position: Point,
+        bounds: Size,
+        scroll_y: f32,
+    ) -> Vec<CachedTextArea> {
+        if self.texts.iter().all(|text| text.script.is_none()) {
+            return Vec::new();
+        }
+
+        let mut cache = text_system.text_cache.lock().unwrap();
+        let mut font_system = text_system.font_system.lock().unwrap();
+        let (_, buffer) = cache.allocate(&mut font_system, key.clone());
+        let left = screen_position.0 + self.align_offset(buffer, bounds);
+
+        // The leftmost glyph of each text along with the baseline of its line
+        let mut placed = vec![None; self.texts.len()];
+        let mut recolored = vec![false; self.texts.len()];
+        let offsets = self.run_offsets(buffer);
+        for (run, (offset, _)) in buffer.layout_runs().zip(offsets) {
+            for glyph in run.glyphs {
+                if self.texts[glyph.metadata].script.is_none() {
+                    continue;
+                }
+                if key
+                    .recolor
+                    .is_some_and(|recolor| recolor.contains(run.line_i, glyph.start))
+                {
+                    recolored[glyph.metadata] = true;
+                }
+                placed[glyph.metadata] = match placed[glyph.metadata] {
+                    Some((x, y)) if x <= glyph.x => Some((x, y)),
+                    _ => Some((glyph.x, run.line_y + offset)),
+                };
+            }
+        }
+
+        let mut areas = Vec::new();
+        for section in key.lines.iter().flatten() {
+            let (Some(script), Some((x, baseline))) = (section.script, placed[section.index])
+            else {
+                continue;
+            };
+            let mut script_key = key.script(section);
+            if let (Some(recolor), true) = (key.recolor, recolored[section.index]) {
+                script_key.lines[0][0].color = recolor.color;
+            }
+            let (hash, buffer) = cache.allocate(&mut font_system, script_key);
+            let script_baseline = buffer.layout_runs().next().map_or(0., |run| run.line_y);
+            let top = baseline - script.baseline_offset(key.size) - script_baseline;
+            areas.push(CachedTextArea {
+                key: hash,
+                left: left + x,
+                top: screen_position.1 + top - scroll_y,
+                bounds: TextBounds::default(),
+                default_color: Color::rgb(255, 255, 255),
+                shifted: Vec::new(),
+            });
+        }
+        areas
+    }
+
+    /// Finds the inline image at `loc`
+    pub fn find_image(&self, loc: Point) -> Option<&Image> {
+        self.images
+            .iter()
+            .find(|image| {
+                image
+                    .bounds
+                    .as_ref()
+                    .is_some_and(|bounds| bounds.contains(loc))
+            })
+            .map(|image| &image.inner)
+    }
+
+    // How far over the text gets shifted to line it up with its alignment
+    fn align_offset(&self, buffer: &glyphon::Buffer, bounds: Size) -> f32 {
+        let max_width = buffer
+            .layout_runs()
+            .fold(0., |max, run| run.line_w.max(max));
+        // Right-to-left lines already get laid out against the right edge of the bounds, where
+        // left alignment sticks to the side that the text starts on
+        match (self.align, self.is_rtl()) {
+            (Align::Left | Align::Justify, _) | (Align::Right, true) => 0.,
+            (Align::Center, false) => (bounds.0 - max_width) / 2.,
+            (Align::Center, true) => -(bounds.0 - max_width) / 2.,
+            (Align::Right, false) => bounds.0 - max_width,
         }
     }
 
@@ -348,8 +881,9 @@ impl TextBox {
             self.key(bounds, zoom),
         );
 
-        let mut y = screen_position.1 + line_height;
-        for line in buffer.layout_runs() {
+        let offsets = self.run_offsets(buffer);
+        for (line, (offset, _)) in buffer.layout_runs().zip(offsets) {
+            let y = screen_position.1 + line.line_top + offset + line_height;
             let mut underlines = Vec::new();
             let mut current_underline: Option<ThinLine> = None;
             let mut strikes = Vec::new();
@@ -359,7 +893,7 @@ impl TextBox {
             for glyph in line.glyphs {
                 let text = &self.texts[glyph.metadata];
                 let color = text.color.unwrap_or(text.default_color);
-                if text.is_underlined {
+                if text.is_underlined && text.image.is_none() {
                     let underline =
                         push_line_segment(&mut underlines, current_underline, glyph, color);
                     current_underline = Some(underline);
@@ -402,12 +936,74 @@ impl TextBox {
                     lines.push(line);
                 }
             }
-            y += line_height;
         }
 
         lines
     }
 
+    pub fn render_decorations(
+        &self,
+        text_system: &mut TextSystem,
+        bounds: Size,
+        zoom: f32,
+        text_area: &CachedTextArea,
+    ) -> Vec<(Rect, Decoration)> {
+        let has_decorations = self
+            .texts
+            .iter()
+            .any(|text| text.is_key || text.highlight.is_some());
+        if !has_decorations {
+            return Vec::new();
+        }
+
+        let line_height = self.line_height(zoom);
+        let mut decorations = Vec::new();
+
+        let mut cache = text_system.text_cache.lock().unwrap();
+
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().unwrap().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
+        let offsets = self.run_offsets(buffer);
+        for (line, (offset, _)) in buffer.layout_runs().zip(offsets) {
+            let y = text_area.top + line.line_top + offset;
+            // Consecutive glyphs from the same text share a single box
+            let mut runs: Vec<(Range<usize>, usize)> = Vec::new();
+            for glyph in line.glyphs {
+                match runs.last_mut() {
+                    Some((range, index)) if *index == glyph.metadata => range.end = glyph.end,
+                    _ => runs.push((glyph.start..glyph.end, glyph.metadata)),
+                }
+            }
+            for (range, index) in runs {
+                let text = &self.texts[index];
+                let decoration = if text.image.is_some() {
+                    continue;
+                } else if text.is_key {
+                    Decoration::KeyCap(text.color())
+                } else if let Some(color) = text.highlight {
+                    Decoration::Highlight(color)
+                } else {
+                    continue;
+                };
+                let start_cursor = Cursor::new(line.line_i, range.start);
+                let end_cursor = Cursor::new(line.line_i, range.end);
+                if let Some((highlight_x, highlight_w)) = line.highlight(start_cursor, end_cursor) {
+                    let x = text_area.left + highlight_x;
+                    let rect = Rect::from_min_max(
+                        (x.floor(), y),
+                        ((x + highlight_w).ceil(), y + line_height),
+                    );
+                    decorations.push((rect, decoration));
+                }
+            }
+        }
+
+        decorations
+    }
+
     pub fn render_selection(
         &self,
         text_system: &mut TextSystem,
@@ -415,8 +1011,9 @@ impl TextBox {
         bounds: Size,
         zoom: f32,
         selection: &mut Selection,
-    ) -> Option<Vec<Rect>> {
+    ) -> Option<SelectedText> {
         let mut rects = Vec::new();
+        let mut ranges: Vec<(usize, Range<usize>)> = Vec::new();
         let mut selected_text = String::new();
 
         let line_height = self.line_height(zoom);
@@ -429,24 +1026,28 @@ impl TextBox {
 
         let (start_cursor, end_cursor, start_y, end_y) = match &selection.selection {
             SelectionKind::Drag { mut start, mut end } => {
-                if start.1 > end.1 || start.0 > end.0 {
+                if start.1 > end.1 {
                     std::mem::swap(&mut start, &mut end);
                 }
                 if screen_position.1 > end.1 || screen_position.1 + bounds.1 < start.1 {
                     return None;
                 }
 
-                let start_cursor =
-                    buffer.hit(start.0 - screen_position.0, start.1 - screen_position.1)?;
-                let end_cursor =
-                    buffer.hit(end.0 - screen_position.0, end.1 - screen_position.1)?;
+                let hit = |(x, y): Point| {
+                    let y = self.unshifted_y(buffer, y - screen_position.1);
+                    buffer.hit(x - screen_position.0, y)
+                };
+                let mut start_cursor = hit(start)?;
+                let mut end_cursor = hit(end)?;
+                // Dragging to the right over right-to-left text moves backwards through it
+                if start_cursor > end_cursor {
+                    std::mem::swap(&mut start_cursor, &mut end_cursor);
+                }
                 (start_cursor, end_cursor, start.1, end.1)
             }
             SelectionKind::Click { mode, position, .. } => {
-                let mut cursor = buffer.hit(
-                    position.0 - screen_position.0,
-                    position.1 - screen_position.1,
-                )?;
+                let y = self.unshifted_y(buffer, position.1 - screen_position.1);
+                let mut cursor = buffer.hit(position.0 - screen_position.0, y)?;
 
                 let line = buffer.lines.get(cursor.line)?;
 
@@ -523,20 +1124,17 @@ impl TextBox {
             }
         };
 
-        let mut y = screen_position.1;
-        for line in buffer.layout_runs() {
-            let line_contains = move |y_point: f32| y_point >= y && y_point <= y + line_height;
-            if line_contains(start_y)
-                || line_contains(end_y)
-                || (start_y < y && end_y > y + line_height)
-            {
-                if let Some((highlight_x, highlight_w)) = line.highlight(start_cursor, end_cursor) {
-                    let x = screen_position.0 + highlight_x;
-                    rects.push(Rect::from_min_max(
-                        (x.floor(), y),
-                        ((x + highlight_w).ceil(), y + line_height),
-                    ));
-                }
+        let offsets = self.run_offsets(buffer);
+        for (line, (offset, room)) in buffer.layout_runs().zip(offsets) {
+            // Lines holding inline images are taller, with the extra room above their text
+            let top = screen_position.1 + line.line_top + offset - room;
+            let bottom = screen_position.1 + line.line_top + offset + line_height;
+            let line_contains = move |y_point: f32| y_point >= top && y_point <= bottom;
+            if line_contains(start_y) || line_contains(end_y) || (start_y < top && end_y > bottom) {
+                // A selection over text in mixed directions can cover several separate spans of
+                // the line, so each one gets its own rect
+                let mut spans: Vec<(f32, f32)> = Vec::new();
+                let mut selected_glyphs = Vec::new();
                 // See https://docs.rs/cosmic-text/0.8.0/cosmic_text/struct.LayoutRun.html#method.highlight implementation
                 for glyph in line.glyphs.iter() {
                     let left_glyph_cursor = if line.rtl {
@@ -552,22 +1150,75 @@ impl TextBox {
                     if (left_glyph_cursor >= start_cursor && left_glyph_cursor <= end_cursor)
                         && (right_glyph_cursor >= start_cursor && right_glyph_cursor <= end_cursor)
                     {
-                        selected_text.push_str(&line.text[glyph.start..glyph.end]);
+                        let (left, right) = (glyph.x, glyph.x + glyph.w);
+                        match spans.last_mut() {
+                            Some((start, end)) if left <= *end + 0.5 && right >= *start - 0.5 => {
+                                *start = start.min(left);
+                                *end = end.max(right);
+                            }
+                            _ => spans.push((left, right)),
+                        }
+                        match ranges.last_mut() {
+                            Some((l, range)) if *l == line.line_i && range.end == glyph.start => {
+                                range.end = glyph.end;
+                            }
+                            _ => ranges.push((line.line_i, glyph.start..glyph.end)),
+                        }
+                        if self.texts[glyph.metadata].image.is_none() {
+                            selected_glyphs.push((glyph.start..glyph.end, glyph.metadata));
+                        }
+                    }
+                }
+                for (start_x, end_x) in spans {
+                    rects.push(Rect::from_min_max(
+                        ((screen_position.0 + start_x).floor(), top),
+                        ((screen_position.0 + end_x).ceil(), bottom),
+                    ));
+                }
+                // The text gets copied in reading order rather than the order it's shown in
+                selected_glyphs.sort_by_key(|(range, _)| range.start);
+                selected_glyphs.dedup();
+                let mut last_script = None;
+                for (range, index) in selected_glyphs {
+                    // Raised and lowered text only holds its place in the line, so any of it
+                    // being selected copies all of it
+                    let text = &self.texts[index];
+                    if text.script.is_some() {
+                        if last_script != Some(index) {
+                            selected_text.push_str(&text.text);
+                        }
+                        last_script = Some(index);
+                        continue;
                     }
+                    last_script = None;
+                    selected_text.push_str(line.text[range].trim_start_matches(DIRECTION_MARKS));
                 }
-                if end_y > y + line_height {
+                if end_y > bottom {
                     selected_text.push(' ')
                 }
             }
-            y += line_height;
         }
 
         selection.add_line(&selected_text);
 
-        Some(rects)
+        Some(SelectedText { rects, ranges })
     }
 }
 
+/// The part of a text box that's selected
+pub struct SelectedText {
+    /// Where the selection gets drawn
+    pub rects: Vec<Rect>,
+    /// The byte ranges of the selected glyphs on each line of the laid out text
+    pub ranges: Vec<(usize, Range<usize>)>,
+}
+
+/// A box drawn around a run of text
+pub enum Decoration {
+    Highlight([f32; 4]),
+    KeyCap([f32; 4]),
+}
+
 #[derive(Clone)]
 struct ThinLine {
     range: Range<usize>,
@@ -583,7 +1234,13 @@ pub struct Text {
     pub is_italic: bool,
     pub is_underlined: bool,
     pub is_striked: bool,
+    pub is_key: bool,
+    pub highlight: Option<[f32; 4]>,
+    pub title: Option<String>,
     pub font_family: FamilyOwned,
+    pub script: Option<Script>,
+    // Index of the inline image in the text box that this text holds the room for
+    pub image: Option<usize>,
     pub hidpi_scale: f32,
     pub default_color: [f32; 4],
 }
@@ -606,7 +1263,12 @@ impl Text {
             is_italic: false,
             is_underlined: false,
             is_striked: false,
+            is_key: false,
+            highlight: None,
+            title: None,
             font_family: FamilyOwned::SansSerif,
+            script: None,
+            image: None,
         }
     }
 
@@ -640,11 +1302,31 @@ impl Text {
         self
     }
 
+    pub fn make_key(mut self, key: bool) -> Self {
+        self.is_key = key;
+        self
+    }
+
+    pub fn with_highlight(mut self, color: [f32; 4]) -> Self {
+        self.highlight = Some(color);
+        self
+    }
+
+    pub fn with_title(mut self, title: String) -> Self {
+        self.title = Some(title);
+        self
+    }
+
     pub fn with_family(mut self, family: FamilyOwned) -> Self {
         self.font_family = family;
         self
     }
 
+    pub fn with_script(mut self, script: Script) -> Self {
+        self.script = Some(script);
+        self
+    }
+
     fn color(&self) -> [f32; 4] {
         self.color.unwrap_or(self.default_color)
     }
@@ -666,13 +1348,7 @@ impl Text {
     }
 
     pub fn section_keys(&self, index: usize) -> Vec<SectionKey<'_>> {
-        let color = self.color();
-        let color = Color::rgba(
-            (color[0] * 255.) as u8,
-            (color[1] * 255.) as u8,
-            (color[2] * 255.) as u8,
-            (color[3] * 255.) as u8,
-        );
+        let color = glyphon_color(self.color());
         let font = Font {
             family: self.font_family.as_family(),
             weight: self.weight(),
@@ -684,12 +1360,42 @@ impl Text {
                 content: line,
                 font,
                 color,
+                script: self.script,
                 index,
             })
             .collect()
     }
 }
 
+fn glyphon_color(color: [f32; 4]) -> Color {
+    Color::rgba(
+        (color[0] * 255.) as u8,
+        (color[1] * 255.) as u8,
+        (color[2] * 255.) as u8,
+        (color[3] * 255.) as u8,
+    )
+}
+
+/// Text that gets shrunk down and raised above or lowered below the baseline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Script {
+    Super,
+    Sub,
+}
+
+impl Script {
+    // Size of the text relative to the text around it
+    const SCALE: f32 = 0.7;
+
+    // How far the baseline gets raised for text around it of the given size
+    fn baseline_offset(self, size: f32) -> f32 {
+        match self {
+            Self::Super => size * 0.35,
+            Self::Sub => -size * 0.15,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Hash)]
 struct Font<'a> {
     family: glyphon::Family<'a>,
@@ -702,15 +1408,56 @@ pub struct SectionKey<'a> {
     content: &'a str,
     font: Font<'a>,
     color: Color,
+    script: Option<Script>,
     index: usize,
 }
 
 #[derive(Clone)]
 pub struct Key<'a> {
     lines: Vec<Vec<SectionKey<'a>>>,
+    heading: bool,
+    rtl: bool,
+    justify: bool,
     size: f32,
     line_height: f32,
     bounds: Size,
+    recolor: Option<Recolor<'a>>,
+}
+
+// Glyphs that get drawn in a different color than their text, given by the byte ranges that they
+// cover on each line
+#[derive(Clone, Copy, Hash)]
+struct Recolor<'a> {
+    ranges: &'a [(usize, Range<usize>)],
+    color: Color,
+}
+
+impl Recolor<'_> {
+    fn contains(&self, line: usize, index: usize) -> bool {
+        self.ranges
+            .iter()
+            .any(|(l, range)| *l == line && range.contains(&index))
+    }
+}
+
+impl<'a> Key<'a> {
+    // Raised or lowered text gets laid out on its own in a smaller size and then placed over the
+    // room that's left for it in the line
+    fn script(&self, section: &SectionKey<'a>) -> Self {
+        Self {
+            lines: vec![vec![SectionKey {
+                script: None,
+                ..*section
+            }]],
+            heading: self.heading,
+            rtl: false,
+            justify: false,
+            size: self.size * Script::SCALE,
+            line_height: self.line_height * Script::SCALE,
+            bounds: (f32::INFINITY, f32::INFINITY),
+            recolor: None,
+        }
+    }
 }
 
 #[derive(Default)]
@@ -718,11 +1465,15 @@ pub struct TextCache {
     entries: FxHashMap<KeyHash, glyphon::Buffer>,
     recently_used: FxHashSet<KeyHash>,
     hasher: HashBuilder,
+    families: FontFamilies,
 }
 
 impl TextCache {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(families: FontFamilies) -> Self {
+        Self {
+            families,
+            ..Self::default()
+        }
     }
 
     pub fn get(&self, key: &KeyHash) -> Option<&glyphon::Buffer> {
@@ -738,15 +1489,19 @@ impl TextCache {
             let mut hasher = self.hasher.build_hasher();
 
             key.lines.hash(&mut hasher);
+            key.heading.hash(&mut hasher);
+            key.rtl.hash(&mut hasher);
+            key.justify.hash(&mut hasher);
             key.size.to_bits().hash(&mut hasher);
             key.line_height.to_bits().hash(&mut hasher);
             key.bounds.0.to_bits().hash(&mut hasher);
             key.bounds.1.to_bits().hash(&mut hasher);
+            key.recolor.hash(&mut hasher);
 
             hasher.finish()
         };
 
-        if let hash_map::Entry::Vacant(entry) = self.entries.entry(hash) {
+        if !self.entries.contains_key(&hash) {
             let metrics = glyphon::Metrics::new(key.size, key.line_height);
             let mut buffer = glyphon::Buffer::new(font_system, metrics);
 
@@ -754,30 +1509,84 @@ impl TextCache {
 
             buffer.lines.clear();
 
-            for line in key.lines {
+            for (line_i, line) in key.lines.iter().enumerate() {
                 let mut line_str = String::new();
                 let mut attrs_list = AttrsList::new(Attrs::new());
+                // Each line gets laid out in the direction of its first strong character, so
+                // lines that disagree with the whole text box get led by a direction mark
+                let line_rtl =
+                    line.iter().find_map(|section| {
+                        match unicode_bidi::get_base_direction(section.content) {
+                            unicode_bidi::Direction::Ltr => Some(false),
+                            unicode_bidi::Direction::Rtl => Some(true),
+                            unicode_bidi::Direction::Mixed => None,
+                        }
+                    });
+                match (key.rtl, line_rtl) {
+                    (true, None | Some(false)) => line_str.push(RIGHT_TO_LEFT_MARK),
+                    (false, Some(true)) => line_str.push(LEFT_TO_RIGHT_MARK),
+                    _ => {}
+                }
                 for section in line {
                     let start = line_str.len();
+                    if section.script.is_some() {
+                        let (_, script) = self.allocate(font_system, key.script(section));
+                        let width = script.layout_runs().fold(0., |w, run| run.line_w.max(w));
+                        line_str.push_str(&fonts::placeholder_text(width / key.size));
+                        attrs_list.add_span(
+                            start..line_str.len(),
+                            Attrs::new()
+                                .family(glyphon::Family::Name(fonts::PLACEHOLDER_FAMILY))
+                                .color(section.color)
+                                .metadata(section.index),
+                        );
+                        continue;
+                    }
                     line_str.push_str(section.content);
-                    let end = line_str.len();
-                    attrs_list.add_span(
-                        start..end,
-                        Attrs::new()
-                            .family(section.font.family)
-                            .weight(section.font.weight)
-                            .style(section.font.style)
-                            .color(section.color)
-                            .metadata(section.index),
-                    )
+                    let Font {
+                        family,
+                        weight,
+                        style,
+                    } = section.font;
+                    let family = self.families.family_for(family, key.heading);
+                    let runs = self.families.fallback_runs(
+                        font_system,
+                        section.content,
+                        family,
+                        weight,
+                        style,
+                    );
+                    for (range, family) in runs {
+                        attrs_list.add_span(
+                            start + range.start..start + range.end,
+                            Attrs::new()
+                                .family(family)
+                                .weight(weight)
+                                .style(style)
+                                .color(section.color)
+                                .metadata(section.index),
+                        )
+                    }
+                }
+                if let Some(recolor) = key.recolor {
+                    let ranges = recolor.ranges.iter().filter(|(l, _)| *l == line_i);
+                    for (_, range) in ranges {
+                        let attrs = AttrsOwned::new(attrs_list.get_span(range.start));
+                        attrs_list.add_span(range.clone(), attrs.as_attrs().color(recolor.color));
+                    }
+                }
+                let mut buffer_line = BufferLine::new(line_str, attrs_list, Shaping::Advanced);
+                // Justified text gets its spaces widened while being laid out, so hit testing and
+                // selection work on the final glyph positions
+                if key.justify {
+                    buffer_line.set_align(Some(cosmic_text::Align::Justified));
                 }
-                let buffer_line = BufferLine::new(line_str, attrs_list, Shaping::Advanced);
                 buffer.lines.push(buffer_line);
             }
 
             buffer.shape_until_scroll(font_system);
 
-            let _ = entry.insert(buffer);
+            let _ = self.entries.insert(hash, buffer);
         }
 
         let _ = self.recently_used.insert(hash);
@@ -793,10 +1602,260 @@ impl TextCache {
     }
 }
 
+/// Everything needed to lay out text
 pub struct TextSystem {
     pub font_system: Arc<Mutex<FontSystem>>,
+    pub text_cache: Arc<Mutex<TextCache>>,
+}
+
+/// Draws the text that was laid out by the `TextSystem` on the GPU
+pub struct TextRenderers {
     pub text_renderer: glyphon::TextRenderer,
+    // Draws text that sits on top of everything else, like tooltips
+    pub overlay_renderer: glyphon::TextRenderer,
     pub text_atlas: glyphon::TextAtlas,
-    pub text_cache: Arc<Mutex<TextCache>>,
     pub swash_cache: SwashCache,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use super::{glyphon_color, Script, Text, TextBox};
+    use crate::image::{Image, ImageData, ImageSize};
+    use crate::selection::{Selection, SelectionKind, SelectionMode};
+    use crate::test_utils::image::{Sample, SamplePng};
+    use crate::test_utils::text::text_system;
+    use crate::utils::Align;
+
+    const BOUNDS: (f32, f32) = (800., f32::INFINITY);
+
+    fn text(text: &str) -> Text {
+        Text::new(text.into(), 1., [0., 0., 0., 1.])
+    }
+
+    #[test]
+    fn raised_and_lowered_text() {
+        let mut text_system = text_system();
+        let scripts = TextBox::new(
+            vec![
+                text("x"),
+                text("2").with_script(Script::Super),
+                text(" H"),
+                text("2").with_script(Script::Sub),
+                text("O"),
+            ],
+            1.,
+        );
+        let plain = TextBox::new(vec![text("x2 H2O")], 1.);
+
+        // The smaller text takes up less room in the line without changing its height
+        let scripts_size = scripts.size(&mut text_system, BOUNDS, 1.);
+        let plain_size = plain.size(&mut text_system, BOUNDS, 1.);
+        assert!(scripts_size.0 < plain_size.0);
+        assert_eq!(scripts_size.1, plain_size.1);
+
+        let areas = scripts.script_areas(&mut text_system, (0., 0.), BOUNDS, 1., 0.);
+        assert_eq!(areas.len(), 2);
+        let (sup, sub) = (&areas[0], &areas[1]);
+        assert!(sup.left < sub.left);
+        assert!(sup.top < sub.top);
+
+        let mut selection = Selection {
+            selection: SelectionKind::Drag {
+                start: (0., 1.),
+                end: (scripts_size.0, 1.),
+            },
+            ..Selection::new()
+        };
+        scripts
+            .render_selection(&mut text_system, (0., 0.), BOUNDS, 1., &mut selection)
+            .unwrap();
+        assert_eq!(selection.text, "x2 H2O\n");
+    }
+
+    #[test]
+    fn only_lines_with_images_grow() {
+        let mut text_system = text_system();
+        let bytes = Sample::from(SamplePng::Bun).pre_decode();
+        let data = ImageData::load(&bytes, true).unwrap();
+        let image = Image::from_image_data(Arc::new(Mutex::new(Some(data))), "bun".into(), 1.)
+            .with_size(ImageSize::width(40));
+
+        let mut text_box = TextBox::new(vec![text("first line\n"), text("with an ")], 1.);
+        text_box.push_image(image, text(""));
+        text_box.texts.push(text(" image\n"));
+        text_box.texts.push(text("last line"));
+        text_box.size_images(BOUNDS.0, 1.);
+        text_box.position_images(&mut text_system, (0., 0.), BOUNDS, 1.);
+
+        let line_height = text_box.line_height(1.);
+        let spans = text_box.line_spans(&mut text_system, BOUNDS, 1.);
+        let [first, with_image, last] = spans[..] else {
+            panic!("Expected three lines, got {spans:?}");
+        };
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+        assert_eq!(first, (0., line_height));
+        assert!(with_image.1 > line_height);
+        assert!(close(with_image.0, line_height));
+        assert!(close(last.0, with_image.0 + with_image.1));
+        assert!(close(last.1, line_height));
+        let size = text_box.size(&mut text_system, BOUNDS, 1.);
+        assert!(close(size.1, last.0 + last.1));
+
+        // The line grows just enough to fit the image on top of its text
+        let image = text_box.images[0].bounds.clone().unwrap();
+        assert_eq!(image.size.0, 40.);
+        assert!(close(image.pos.1, with_image.0));
+
+        // Hovering over the room above the text still finds the line's text
+        let mut hovered = |y| {
+            text_box
+                .find_hoverable(&mut text_system, (5., y), (0., 0.), BOUNDS, 1.)
+                .map(|text| text.text.clone())
+        };
+        assert_eq!(hovered(with_image.0 + 1.).as_deref(), Some("with an "));
+        assert_eq!(hovered(last.0 + 1.).as_deref(), Some("last line"));
+    }
+
+    #[test]
+    fn select_mixed_direction_text() {
+        let mut text_system = text_system();
+        // "abc " followed by an Arabic word that gets shown right-to-left, then " xyz"
+        let text_box = TextBox::new(vec![text("abc مرحبا xyz")], 1.);
+        let arabic_end = {
+            let mut cache = text_system.text_cache.lock().unwrap();
+            let (_, buffer) = cache.allocate(
+                &mut text_system.font_system.lock().unwrap(),
+                text_box.key(BOUNDS, 1.),
+            );
+            let run = buffer.layout_runs().next().unwrap();
+            let space = run.glyphs.iter().find(|glyph| glyph.start == 14).unwrap();
+            space.x
+        };
+
+        // Dragging from the start of the line into the middle of the Arabic word picks up the
+        // first letters of it in reading order, which get shown on its right side
+        let mut selection = Selection {
+            selection: SelectionKind::Drag {
+                start: (0., 1.),
+                end: (50., 1.),
+            },
+            ..Selection::new()
+        };
+        let selected = text_box
+            .render_selection(&mut text_system, (0., 0.), BOUNDS, 1., &mut selection)
+            .unwrap();
+        assert_eq!(selection.text, "abc مر\n");
+        assert_eq!(selected.ranges, [(0, 0..4), (0, 6..8), (0, 4..6)]);
+
+        let [latin, arabic] = &selected.rects[..] else {
+            panic!("Expected two separate rects, got {:?}", selected.rects);
+        };
+        assert_eq!(latin.pos.0, 0.);
+        let latin_end = latin.pos.0 + latin.size.0;
+        assert!(latin_end < arabic.pos.0);
+        assert!(arabic.pos.0 < arabic_end);
+        assert_eq!(arabic.pos.0 + arabic.size.0, arabic_end.ceil());
+    }
+
+    #[test]
+    fn justified_lines_fill_the_width() {
+        let mut text_system = text_system();
+        let mut text_box = TextBox::new(
+            vec![
+                text("The quick brown fox jumps over the lazy "),
+                text("dog").with_link("https://example.org".into()),
+                text(" while the cat sits at the end of a line"),
+            ],
+            1.,
+        );
+        text_box.set_align(Align::Justify);
+        let width = 200.;
+        let bounds = (width, f32::INFINITY);
+
+        {
+            let mut cache = text_system.text_cache.lock().unwrap();
+            let (_, buffer) = cache.allocate(
+                &mut text_system.font_system.lock().unwrap(),
+                text_box.key(bounds, 1.),
+            );
+            let runs: Vec<_> = buffer.layout_runs().collect();
+            assert!(runs.len() > 2);
+            // Every line gets stretched out to the edge except for the last one
+            let (last, lines) = runs.split_last().unwrap();
+            for run in lines {
+                assert!(
+                    (run.line_w - width).abs() < 0.5,
+                    "{} != {width}",
+                    run.line_w
+                );
+            }
+            assert!(last.line_w < width - 0.5);
+        }
+
+        // The link ends the second line, so it gets pushed all the way over to the right
+        let line_height = text_box.line_height(1.);
+        let near_edge = (width - 1., line_height * 1.5);
+        let hovered = text_box
+            .find_hoverable(&mut text_system, near_edge, (0., 0.), bounds, 1.)
+            .unwrap();
+        assert_eq!(hovered.text, "dog");
+    }
+
+    #[test]
+    fn selected_glyphs_get_recolored() {
+        let mut text_system = text_system();
+        let text_box = TextBox::new(vec![text("hello "), text("world")], 1.);
+        let mut selection = Selection {
+            selection: SelectionKind::Click {
+                mode: SelectionMode::Word,
+                time: Instant::now(),
+                position: (5., 5.),
+            },
+            ..Selection::new()
+        };
+        let selected = text_box
+            .render_selection(&mut text_system, (0., 0.), BOUNDS, 1., &mut selection)
+            .unwrap();
+        assert_eq!(selection.text, "hello\n");
+        assert_eq!(selected.ranges, [(0, 0..5)]);
+
+        let red = [1., 0., 0., 1.];
+        let plain = text_box.text_areas(&mut text_system, (0., 0.), BOUNDS, 1., 0.);
+        let (recolored, _) = text_box.selected_text_areas(
+            &mut text_system,
+            (0., 0.),
+            BOUNDS,
+            1.,
+            0.,
+            &selected.ranges,
+            red,
+        );
+        let cache = text_system.text_cache.lock().unwrap();
+        let glyphs = |key| -> Vec<_> {
+            let buffer = cache.get(key).unwrap();
+            buffer
+                .layout_runs()
+                .flat_map(|run| run.glyphs.iter())
+                .map(|glyph| (glyph.x, glyph.color_opt))
+                .collect()
+        };
+        let (plain, recolored) = (glyphs(&plain.key), glyphs(&recolored.key));
+
+        // Only the colors change, so the recolored glyphs sit right where they were
+        assert_eq!(plain.len(), recolored.len());
+        let black = Some(glyphon_color([0., 0., 0., 1.]));
+        for (i, (plain, recolored)) in plain.into_iter().zip(recolored).enumerate() {
+            assert_eq!(plain.0, recolored.0);
+            assert_eq!(plain.1, black);
+            let expected = if i < 5 {
+                Some(glyphon_color(red))
+            } else {
+                black
+            };
+            assert_eq!(recolored.1, expected);
+        }
+    }
+}
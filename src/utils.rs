@@ -4,6 +4,7 @@ use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, OnceLock};
 
 use crate::image::ImageData;
+use crate::opts::SyntaxOptions;
 
 use comrak::adapters::SyntaxHighlighterAdapter;
 use comrak::plugins::syntect::{SyntectAdapter, SyntectAdapterBuilder};
@@ -11,9 +12,16 @@ use comrak::{markdown_to_html_with_plugins, ComrakOptions};
 use indexmap::IndexMap;
 use serde::Deserialize;
 use syntect::highlighting::{Theme as SyntectTheme, ThemeSet as SyntectThemeSet};
-use syntect::parsing::SyntaxSet;
+use syntect::parsing::{SyntaxDefinition, SyntaxSet};
 use winit::window::CursorIcon;
 
+/// A directory within inlyne's configuration directory e.g. `~/.config/inlyne/themes`
+pub fn user_config_dir(name: &str) -> Option<PathBuf> {
+    dirs::config_dir()
+        .map(|dir| dir.join("inlyne").join(name))
+        .filter(|dir| dir.is_dir())
+}
+
 pub fn format_title(file_path: &Path) -> String {
     match root_filepath_to_vcs_dir(file_path) {
         Some(path) => format!("Inlyne - {}", path.to_string_lossy()),
@@ -109,6 +117,16 @@ impl Rect {
     pub fn contains(&self, loc: Point) -> bool {
         self.pos.0 <= loc.0 && loc.0 <= self.max().0 && self.pos.1 <= loc.1 && loc.1 <= self.max().1
     }
+
+    /// The area covered by both rects, if any
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let min = (self.pos.0.max(other.pos.0), self.pos.1.max(other.pos.1));
+        let max = (
+            self.max().0.min(other.max().0),
+            self.max().1.min(other.max().1),
+        );
+        (min.0 < max.0 && min.1 < max.1).then(|| Rect::from_min_max(min, max))
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
@@ -117,6 +135,8 @@ pub enum Align {
     Left,
     Center,
     Right,
+    /// Spreads out the words of every line but the last one to fill the width
+    Justify,
 }
 
 impl Align {
@@ -125,6 +145,7 @@ impl Align {
             "left" => Self::Left,
             "center" => Self::Center,
             "right" => Self::Right,
+            "justify" => Self::Justify,
             _ => return None,
         };
 
@@ -132,6 +153,48 @@ impl Align {
     }
 }
 
+/// The direction that a block of text reads in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// Picked from the first strongly directional character
+    #[default]
+    Auto,
+    Ltr,
+    Rtl,
+}
+
+impl Direction {
+    pub fn new(s: &str) -> Option<Self> {
+        let direction = match s {
+            "auto" => Self::Auto,
+            "ltr" => Self::Ltr,
+            "rtl" => Self::Rtl,
+            _ => return None,
+        };
+
+        Some(direction)
+    }
+}
+
+/// The side of the page that an image gets pulled to with the text wrapping around it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Float {
+    Left,
+    Right,
+}
+
+impl Float {
+    pub fn new(s: &str) -> Option<Self> {
+        let float = match s {
+            "left" => Self::Left,
+            "right" => Self::Right,
+            _ => return None,
+        };
+
+        Some(float)
+    }
+}
+
 #[derive(Default)]
 pub struct HoverInfo {
     pub cursor_icon: CursorIcon,
@@ -150,17 +213,26 @@ impl From<CursorIcon> for HoverInfo {
 // TODO(cosmic): Remove after `comrak` supports code block info strings that have a comma
 //     (like ```rust,ignore)
 //     https://github.com/kivikakk/comrak/issues/246
-struct CustomSyntectAdapter(SyntectAdapter);
+struct CustomSyntectAdapter<'a> {
+    adapter: SyntectAdapter,
+    syntax_opts: &'a SyntaxOptions,
+}
 
-impl SyntaxHighlighterAdapter for CustomSyntectAdapter {
+impl SyntaxHighlighterAdapter for CustomSyntectAdapter<'_> {
     fn write_highlighted(
         &self,
         output: &mut dyn io::Write,
         lang: Option<&str>,
         code: &str,
     ) -> io::Result<()> {
-        let norm_lang = lang.map(|l| l.split_once(',').map(|(lang, _)| lang).unwrap_or(l));
-        self.0.write_highlighted(output, norm_lang, code)
+        let norm_lang = lang
+            .map(|l| l.split_once(',').map(|(lang, _)| lang).unwrap_or(l))
+            // `diff-<lang>` highlights the code as `<lang>` with diff backgrounds on top
+            .map(|l| l.strip_prefix("diff-").unwrap_or(l))
+            .filter(|l| !l.is_empty())
+            .or(self.syntax_opts.default.as_deref())
+            .map(|l| self.syntax_opts.aliases.get(l).map_or(l, String::as_str));
+        self.adapter.write_highlighted(output, norm_lang, code)
     }
 
     fn write_pre_tag(
@@ -168,19 +240,104 @@ impl SyntaxHighlighterAdapter for CustomSyntectAdapter {
         output: &mut dyn io::Write,
         attributes: HashMap<String, String>,
     ) -> io::Result<()> {
-        self.0.write_pre_tag(output, attributes)
+        self.adapter.write_pre_tag(output, attributes)
     }
 
     fn write_code_tag(
         &self,
         output: &mut dyn io::Write,
-        attributes: HashMap<String, String>,
+        mut attributes: HashMap<String, String>,
     ) -> io::Result<()> {
-        self.0.write_code_tag(output, attributes)
+        // Options can follow the language after a comma (```rust,linenos) or come after it in
+        // braces (```rust {linenos, hl=3-5})
+        let after_comma = attributes
+            .get("class")
+            .and_then(|class| class.split_once(','))
+            .map(|(_, options)| options.to_owned());
+        let meta = attributes.remove("data-meta");
+        let options = after_comma.iter().chain(&meta).flat_map(|options| {
+            options
+                .split(|c: char| c == ',' || c.is_whitespace() || c == '{' || c == '}')
+                .filter(|option| !option.is_empty())
+        });
+        let mut highlighted = Vec::new();
+        let mut in_highlight = false;
+        for option in options {
+            if option == "linenos" {
+                attributes.insert("data-linenos".to_owned(), String::new());
+                in_highlight = false;
+            } else if let Some(lines) = option.strip_prefix("hl=") {
+                highlighted.push(lines);
+                in_highlight = true;
+            } else if in_highlight && option.starts_with(|c: char| c.is_ascii_digit()) {
+                // More lines for `hl=3-5,8`
+                highlighted.push(option);
+            } else {
+                in_highlight = false;
+            }
+        }
+        if !highlighted.is_empty() {
+            attributes.insert("data-hl".to_owned(), highlighted.join(" "));
+        }
+
+        let lang = attributes
+            .get("class")
+            .and_then(|class| class.strip_prefix("language-"))
+            .map(|lang| lang.split_once(',').map_or(lang, |(lang, _)| lang));
+        if lang.is_some_and(|lang| matches!(lang, "diff" | "patch") || lang.starts_with("diff-")) {
+            attributes.insert("data-diff".to_owned(), String::new());
+        }
+
+        self.adapter.write_code_tag(output, attributes)
     }
 }
 
-pub fn markdown_to_html(md: &str, syntax_theme: SyntectTheme) -> String {
+/// Extends `syn_set` with the `.sublime-syntax` files from `user_dir`. `.tmLanguage` files get
+/// skipped with a warning since `syntect` can't parse them
+fn add_user_syntaxes(syn_set: SyntaxSet, user_dir: &Path) -> SyntaxSet {
+    let entries = match std::fs::read_dir(user_dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            tracing::warn!("Failed reading syntaxes from {}: {err}", user_dir.display());
+            return syn_set;
+        }
+    };
+
+    let mut builder = syn_set.into_builder();
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("sublime-syntax") => {
+                let name = path.file_stem().and_then(|stem| stem.to_str());
+                let syntax = std::fs::read_to_string(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|contents| {
+                        Ok(SyntaxDefinition::load_from_str(&contents, false, name)?)
+                    });
+                match syntax {
+                    Ok(syntax) => builder.add(syntax),
+                    Err(err) => {
+                        tracing::warn!("Failed loading syntax from {}: {err}", path.display())
+                    }
+                }
+            }
+            // `syntect` can only parse the newer `.sublime-syntax` format
+            Some("tmLanguage") => tracing::warn!(
+                "Skipping {}. `.tmLanguage` files need to be converted to `.sublime-syntax` \
+                (Sublime Text can do this with `Plugin Development: Convert Syntax to \
+                .sublime-syntax`)",
+                path.display()
+            ),
+            _ => {}
+        }
+    }
+    builder.build()
+}
+
+pub fn markdown_to_html(
+    md: &str,
+    syntax_theme: SyntectTheme,
+    syntax_opts: &SyntaxOptions,
+) -> String {
     let mut options = ComrakOptions::default();
     options.extension.autolink = true;
     options.extension.table = true;
@@ -189,8 +346,10 @@ pub fn markdown_to_html(md: &str, syntax_theme: SyntectTheme) -> String {
     // options.extension.footnotes = true;
     options.extension.front_matter_delimiter = Some("---".to_owned());
     options.extension.shortcodes = true;
+    options.extension.description_lists = true;
     options.parse.smart = true;
     options.render.unsafe_ = true;
+    options.render.full_info_string = true;
 
     // TODO(cosmic): gonna send a PR so that a plugin can pass in a single theme too
     let dummy_name = "theme";
@@ -201,7 +360,13 @@ pub fn markdown_to_html(md: &str, syntax_theme: SyntectTheme) -> String {
     static CACHED_SYN_SET: OnceLock<SyntaxSet> = OnceLock::new();
     // Initializing this is non-trivial. Cache so it only runs once
     let syn_set = CACHED_SYN_SET
-        .get_or_init(two_face::syntax::extra_no_newlines)
+        .get_or_init(|| {
+            let syn_set = two_face::syntax::extra_no_newlines();
+            match user_config_dir("syntaxes") {
+                Some(dir) => add_user_syntaxes(syn_set, &dir),
+                None => syn_set,
+            }
+        })
         .to_owned();
     let adapter = SyntectAdapterBuilder::new()
         .syntax_set(syn_set)
@@ -210,7 +375,10 @@ pub fn markdown_to_html(md: &str, syntax_theme: SyntectTheme) -> String {
         .build();
 
     let mut plugins = comrak::ComrakPlugins::default();
-    let custom = CustomSyntectAdapter(adapter);
+    let custom = CustomSyntectAdapter {
+        adapter,
+        syntax_opts,
+    };
     plugins.render.codefence_syntax_highlighter = Some(&custom);
 
     let htmlified = markdown_to_html_with_plugins(md, &options, &plugins);
@@ -295,3 +463,38 @@ impl Cell {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_syntaxes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("dsl.sublime-syntax"),
+            "\
+%YAML 1.2
+---
+name: In-house DSL
+file_extensions: [dsl]
+scope: source.dsl
+contexts:
+  main:
+    - match: '\\bfrobnicate\\b'
+      scope: keyword.control.dsl
+",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("broken.sublime-syntax"), "not: [yaml").unwrap();
+        std::fs::write(dir.path().join("old.tmLanguage"), "<plist></plist>").unwrap();
+
+        // Relinking all of the built-in syntaxes is slow, so start from a minimal set instead
+        let mut builder = syntect::parsing::SyntaxSetBuilder::new();
+        builder.add_plain_text_syntax();
+        let syn_set = add_user_syntaxes(builder.build(), dir.path());
+        assert!(syn_set.find_syntax_by_token("dsl").is_some());
+        // Plain text is still around and the broken and `.tmLanguage` syntaxes got skipped
+        assert_eq!(syn_set.syntaxes().len(), 2);
+    }
+}
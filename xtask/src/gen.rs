@@ -2,7 +2,7 @@
 
 include!("../../src/opts/cli.rs");
 
-use clap::CommandFactory;
+use clap::{CommandFactory, ValueEnum};
 use std::fs;
 
 use clap_complete::{generate_to, Shell};
@@ -7,6 +7,7 @@ use std::path::{Path, PathBuf};
 use clap::{Parser, Subcommand};
 
 mod gen;
+mod placeholder;
 
 #[derive(Parser)]
 struct Cli {
@@ -18,6 +19,8 @@ struct Cli {
 enum Commands {
     /// Generate shell completions to `completions` dir
     Gen,
+    /// Generate the font that holds the room for inline images to `assets/fonts/placeholder.ttf`
+    PlaceholderFont,
 }
 
 fn main() {
@@ -27,6 +30,7 @@ fn main() {
 
     match command {
         Commands::Gen => gen::gen(),
+        Commands::PlaceholderFont => placeholder::gen(),
     }
 }
 
@@ -0,0 +1,145 @@
+use std::fs;
+
+// These have to match the ones in `src/fonts.rs`
+const PLACEHOLDER_FAMILY: &str = "Inlyne Placeholder";
+// The placeholder glyphs sit in the private use area. The first one is an em wide and each one
+// after it is half as wide as the one before
+const PLACEHOLDER_START: u32 = 0xE000;
+const PLACEHOLDER_GLYPHS: u16 = 8;
+const PLACEHOLDER_UNITS_PER_EM: u16 = 1024;
+
+pub fn gen() {
+    fs::write("assets/fonts/placeholder.ttf", placeholder_font()).unwrap();
+}
+
+/// Builds a font without any outlines where each of the placeholder characters is just an
+/// advance of its width
+fn placeholder_font() -> Vec<u8> {
+    fn u16s(data: &mut Vec<u8>, values: &[u16]) {
+        data.extend(values.iter().flat_map(|value| value.to_be_bytes()));
+    }
+    fn u32s(data: &mut Vec<u8>, values: &[u32]) {
+        data.extend(values.iter().flat_map(|value| value.to_be_bytes()));
+    }
+
+    let num_glyphs = PLACEHOLDER_GLYPHS + 1;
+    let first_char = PLACEHOLDER_START as u16;
+    let last_char = first_char + PLACEHOLDER_GLYPHS - 1;
+
+    let mut head = Vec::new();
+    // Version, revision, checksum adjustment, magic number
+    u32s(&mut head, &[0x10000, 0x10000, 0, 0x5F0F3CF5]);
+    // Flags and units per em
+    u16s(&mut head, &[0, PLACEHOLDER_UNITS_PER_EM]);
+    // Created and modified dates
+    head.extend([0; 16]);
+    // Bounding box, mac style, lowest ppem, direction hint, loca format, and glyf format
+    u16s(&mut head, &[0, 0, 0, 0, 0, 8, 2, 0, 0]);
+
+    let mut hhea = Vec::new();
+    u32s(&mut hhea, &[0x10000]);
+    // Ascender, descender, line gap, max advance, min bearings, max extent, caret slope rise and
+    // run, caret offset, reserved, metric format, and the number of metrics
+    let max_advance = PLACEHOLDER_UNITS_PER_EM;
+    u16s(
+        &mut hhea,
+        &[
+            0,
+            0,
+            0,
+            max_advance,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            num_glyphs,
+        ],
+    );
+
+    let mut maxp = Vec::new();
+    u32s(&mut maxp, &[0x5000]);
+    u16s(&mut maxp, &[num_glyphs]);
+
+    // The advance and left side bearing of `.notdef` followed by the placeholders
+    let mut hmtx = Vec::new();
+    u16s(&mut hmtx, &[0, 0]);
+    for i in 0..PLACEHOLDER_GLYPHS {
+        u16s(&mut hmtx, &[PLACEHOLDER_UNITS_PER_EM >> i, 0]);
+    }
+
+    // A single Windows Unicode subtable in format 4 with a segment for the placeholders followed
+    // by the required final segment
+    let mut cmap = Vec::new();
+    u16s(&mut cmap, &[0, 1, 3, 1]);
+    u32s(&mut cmap, &[12]);
+    let delta = 1u16.wrapping_sub(first_char);
+    #[rustfmt::skip]
+    u16s(&mut cmap, &[
+        // Format, length, language, segment count times two, and the search hints
+        4, 32, 0, 4, 4, 1, 0,
+        // End codes, padding, start codes, deltas, and range offsets
+        last_char, 0xFFFF, 0, first_char, 0xFFFF, delta, 1, 0, 0,
+    ]);
+
+    let to_utf16 = |s: &str| -> Vec<u8> { s.encode_utf16().flat_map(u16::to_be_bytes).collect() };
+    let family = to_utf16(PLACEHOLDER_FAMILY);
+    let post_script = to_utf16(&PLACEHOLDER_FAMILY.replace(' ', ""));
+    let mut name = Vec::new();
+    u16s(&mut name, &[0, 2, 6 + 2 * 12]);
+    // Platform, encoding, language, name ID, length, and offset of the family and PostScript
+    // names
+    u16s(&mut name, &[3, 1, 0x409, 1, family.len() as u16, 0]);
+    u16s(
+        &mut name,
+        &[
+            3,
+            1,
+            0x409,
+            6,
+            post_script.len() as u16,
+            family.len() as u16,
+        ],
+    );
+    name.extend(family);
+    name.extend(post_script);
+
+    // Tables are sorted by their tags
+    let tables = [
+        (b"cmap", cmap),
+        (b"head", head),
+        (b"hhea", hhea),
+        (b"hmtx", hmtx),
+        (b"maxp", maxp),
+        (b"name", name),
+    ];
+    let mut font = Vec::new();
+    // Version, number of tables, search range, entry selector, and range shift
+    u32s(&mut font, &[0x10000]);
+    u16s(&mut font, &[tables.len() as u16, 64, 2, 32]);
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, data) in &tables {
+        let checksum = data
+            .chunks(4)
+            .map(|chunk| {
+                let mut word = [0; 4];
+                word[..chunk.len()].copy_from_slice(chunk);
+                u32::from_be_bytes(word)
+            })
+            .fold(0, u32::wrapping_add);
+        font.extend(*tag);
+        u32s(&mut font, &[checksum, offset as u32, data.len() as u32]);
+        offset += data.len().next_multiple_of(4);
+    }
+    for (_, data) in tables {
+        font.extend(data);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+    font
+}